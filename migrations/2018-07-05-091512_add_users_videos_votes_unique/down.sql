@@ -0,0 +1 @@
+ALTER TABLE `users_videos_votes` DROP INDEX `user_video`;
@@ -0,0 +1 @@
+ALTER TABLE `users_videos_votes` ADD UNIQUE KEY `user_video` (`user_id`, `video_id`);
@@ -0,0 +1,15 @@
+CREATE TABLE `users_videos_comments` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `uuid` varchar(255) NOT NULL,
+    `user_id` bigint(20) unsigned NOT NULL,
+    `video_id` bigint(20) unsigned NOT NULL,
+    `parent_id` bigint(20) unsigned DEFAULT NULL,
+    `body` text NOT NULL,
+    `hidden` tinyint(1) NOT NULL DEFAULT '0',
+    `deleted` tinyint(1) NOT NULL DEFAULT '0',
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uuid` (`uuid`),
+    KEY `video_id` (`video_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
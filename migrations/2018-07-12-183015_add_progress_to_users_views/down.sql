@@ -0,0 +1,2 @@
+ALTER TABLE `users_views` DROP INDEX `user_video`;
+ALTER TABLE `users_views` DROP COLUMN `seconds`, DROP COLUMN `duration`;
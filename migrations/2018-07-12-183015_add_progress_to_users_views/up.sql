@@ -0,0 +1,20 @@
+ALTER TABLE `users_views` ADD (
+  `seconds` int(11) NOT NULL DEFAULT '0',
+  `duration` int(11) NOT NULL DEFAULT '0'
+);
+UPDATE `users_views` JOIN (
+  SELECT MAX(`id`) AS `keep_id`, MIN(`created`) AS `first_created`
+  FROM `users_views`
+  GROUP BY `user_id`, `video_id`
+  HAVING COUNT(*) > 1
+) AS `duplicates` ON `users_views`.`id` = `duplicates`.`keep_id`
+SET `users_views`.`created` = `duplicates`.`first_created`,
+  `users_views`.`updated` = `users_views`.`updated`;
+DELETE `users_views` FROM `users_views` JOIN (
+  SELECT `user_id`, `video_id`, MAX(`id`) AS `keep_id`
+  FROM `users_views`
+  GROUP BY `user_id`, `video_id`
+) AS `latest` ON `users_views`.`user_id` = `latest`.`user_id`
+  AND `users_views`.`video_id` = `latest`.`video_id`
+  AND `users_views`.`id` <> `latest`.`keep_id`;
+ALTER TABLE `users_views` ADD UNIQUE KEY `user_video` (`user_id`, `video_id`);
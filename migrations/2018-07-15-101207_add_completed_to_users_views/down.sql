@@ -0,0 +1 @@
+ALTER TABLE `users_views` DROP COLUMN `completed`;
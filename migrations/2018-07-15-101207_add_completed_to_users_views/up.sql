@@ -0,0 +1,3 @@
+ALTER TABLE `users_views` ADD (
+  `completed` tinyint(1) NOT NULL DEFAULT '0'
+);
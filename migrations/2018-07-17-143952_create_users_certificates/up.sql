@@ -0,0 +1,11 @@
+CREATE TABLE `users_certificates` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `uuid` varchar(255) NOT NULL,
+    `user_id` bigint(20) unsigned NOT NULL,
+    `serie_id` bigint(20) unsigned NOT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uuid` (`uuid`),
+    UNIQUE KEY `user_serie` (`user_id`, `serie_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,2 @@
+ALTER TABLE `users_sessions` DROP INDEX `token`;
+ALTER TABLE `users_sessions` DROP COLUMN `ip`, DROP COLUMN `user_agent`, DROP COLUMN `last_seen`, DROP COLUMN `expires`;
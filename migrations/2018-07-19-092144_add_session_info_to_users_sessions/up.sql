@@ -0,0 +1,8 @@
+ALTER TABLE `users_sessions` ADD (
+  `ip` varchar(45) NOT NULL DEFAULT '',
+  `user_agent` varchar(255) NOT NULL DEFAULT '',
+  `last_seen` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  `expires` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+ALTER TABLE `users_sessions` ADD UNIQUE KEY `token` (`token`);
+UPDATE `users_sessions` SET `last_seen` = `updated`, `expires` = CURRENT_TIMESTAMP + INTERVAL 24 HOUR;
@@ -0,0 +1,2 @@
+DROP TABLE `users_two_factor_recovery`;
+DROP TABLE `users_two_factor`;
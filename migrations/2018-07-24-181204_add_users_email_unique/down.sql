@@ -0,0 +1,4 @@
+ALTER TABLE `users` DROP INDEX `email`;
+UPDATE `users` JOIN `users_email_backup` ON `users`.`id` = `users_email_backup`.`user_id`
+SET `users`.`email` = `users_email_backup`.`email`;
+DROP TABLE `users_email_backup`;
@@ -0,0 +1,19 @@
+CREATE TABLE `users_email_backup` (
+    `user_id` bigint(20) unsigned NOT NULL,
+    `email` varchar(255) NOT NULL,
+    PRIMARY KEY (`user_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `users_email_backup` (`user_id`, `email`) SELECT `id`, `email` FROM `users`;
+-- Emails that only differ by case or whitespace are kept on the
+-- oldest account, the others are marked so the unique key can be added.
+-- The original emails are kept in users_email_backup.
+UPDATE `users` JOIN (
+  SELECT LOWER(TRIM(`email`)) AS `normalized`, MIN(`id`) AS `keep_id`
+  FROM `users`
+  GROUP BY `normalized`
+  HAVING COUNT(*) > 1
+) AS `duplicates` ON LOWER(TRIM(`users`.`email`)) = `duplicates`.`normalized`
+  AND `users`.`id` <> `duplicates`.`keep_id`
+SET `users`.`email` = LEFT(CONCAT('duplicate-', `users`.`id`, '-', `duplicates`.`normalized`), 255);
+UPDATE `users` SET `email` = LOWER(TRIM(`email`));
+ALTER TABLE `users` ADD UNIQUE KEY `email` (`email`);
@@ -0,0 +1 @@
+ALTER TABLE `users_verify_email` DROP COLUMN `new_email`;
@@ -0,0 +1 @@
+ALTER TABLE `users_verify_email` ADD `new_email` varchar(255) DEFAULT NULL;
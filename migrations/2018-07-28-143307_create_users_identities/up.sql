@@ -0,0 +1,12 @@
+CREATE TABLE `users_identities` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `user_id` bigint(20) unsigned NOT NULL,
+    `provider` varchar(255) NOT NULL,
+    `provider_user_id` varchar(255) NOT NULL,
+    `provider_username` varchar(255) DEFAULT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `provider_user_id` (`provider`, `provider_user_id`),
+    UNIQUE KEY `user_id_provider` (`user_id`, `provider`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,13 @@
+CREATE TABLE `users_audit_log` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `user_id` bigint(20) unsigned DEFAULT NULL,
+    `actor_id` bigint(20) unsigned DEFAULT NULL,
+    `event` varchar(255) NOT NULL,
+    `ip` varchar(255) DEFAULT NULL,
+    `user_agent` varchar(255) DEFAULT NULL,
+    `details` text DEFAULT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    KEY `user_id` (`user_id`),
+    KEY `event` (`event`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
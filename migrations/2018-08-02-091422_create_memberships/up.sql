@@ -0,0 +1,14 @@
+CREATE TABLE `memberships` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `user_id` bigint(20) unsigned NOT NULL,
+    `plan` varchar(255) NOT NULL,
+    `stripe_subscription_id` varchar(255) NOT NULL,
+    `status` varchar(255) NOT NULL,
+    `cancel_at_period_end` tinyint(1) NOT NULL DEFAULT '0',
+    `current_period_end` bigint(20) NOT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `user_id` (`user_id`),
+    UNIQUE KEY `stripe_subscription_id` (`stripe_subscription_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
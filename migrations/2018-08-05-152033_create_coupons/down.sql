@@ -0,0 +1,3 @@
+DROP TABLE `coupons_redemptions`;
+DROP TABLE `coupons_series`;
+DROP TABLE `coupons`;
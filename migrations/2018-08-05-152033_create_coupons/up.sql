@@ -0,0 +1,34 @@
+CREATE TABLE `coupons` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `code` varchar(255) NOT NULL,
+    `kind` varchar(255) NOT NULL,
+    `value` int(11) NOT NULL,
+    `expires` timestamp NULL DEFAULT NULL,
+    `max_redemptions` int(11) DEFAULT NULL,
+    `times_redeemed` int(11) NOT NULL DEFAULT '0',
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `code` (`code`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `coupons_series` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `coupon_id` bigint(20) unsigned NOT NULL,
+    `series_id` bigint(20) unsigned NOT NULL,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `coupon_id_series_id` (`coupon_id`, `series_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE `coupons_redemptions` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `coupon_id` bigint(20) unsigned NOT NULL,
+    `user_id` bigint(20) unsigned NOT NULL,
+    `series_id` bigint(20) unsigned NOT NULL,
+    `charge_uuid` varchar(255) DEFAULT NULL,
+    `discount` int(11) NOT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `coupon_id_user_id` (`coupon_id`, `user_id`),
+    KEY `charge_uuid` (`charge_uuid`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,3 @@
+ALTER TABLE `users_stripe_charge` DROP COLUMN `currency`;
+ALTER TABLE `users` DROP COLUMN `currency`;
+DROP TABLE `series_prices`;
@@ -0,0 +1,13 @@
+CREATE TABLE `series_prices` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT,
+    `series_id` bigint(20) unsigned NOT NULL,
+    `currency` varchar(3) NOT NULL,
+    `price` int(11) NOT NULL,
+    `created` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `updated` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `series_id_currency` (`series_id`, `currency`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+ALTER TABLE `users` ADD `currency` varchar(3) DEFAULT NULL;
+ALTER TABLE `users_stripe_charge` ADD `currency` varchar(3) NOT NULL DEFAULT 'usd';
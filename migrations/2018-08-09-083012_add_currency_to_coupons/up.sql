@@ -0,0 +1 @@
+ALTER TABLE `coupons` ADD `currency` varchar(3) NOT NULL DEFAULT 'usd';
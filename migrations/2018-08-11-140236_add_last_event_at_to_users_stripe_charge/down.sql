@@ -0,0 +1 @@
+ALTER TABLE `users_stripe_charge` DROP COLUMN `last_event_at`;
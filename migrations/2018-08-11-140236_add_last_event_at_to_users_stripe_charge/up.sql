@@ -0,0 +1 @@
+ALTER TABLE `users_stripe_charge` ADD `last_event_at` bigint(20) NOT NULL DEFAULT 0;
@@ -0,0 +1,272 @@
+use club_coding::models::{AuditEventExport, CardExport, CertificateExport, ChargeExport,
+                          CommentExport, SeriesAccessExport, SessionExport, Users, ViewExport,
+                          VoteExport};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel;
+use diesel::prelude::*;
+
+/// Gets the user from the id.
+pub fn get_user(connection: &DbConn, uid: i64) -> Option<Users> {
+    use club_coding::schema::users::dsl::*;
+
+    match users.find(uid).first::<Users>(&**connection) {
+        Ok(user) => Some(user),
+        Err(_) => None,
+    }
+}
+
+/// Gets every session of the user.
+pub fn get_sessions(connection: &DbConn, uid: i64) -> Vec<SessionExport> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    match users_sessions
+        .filter(user_id.eq(uid))
+        .order(created.asc())
+        .select((ip, user_agent, created, last_seen, expires))
+        .load::<SessionExport>(&**connection)
+    {
+        Ok(sessions) => sessions,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets every view of the user with
+/// the title of the video.
+pub fn get_views(connection: &DbConn, uid: i64) -> Vec<ViewExport> {
+    use club_coding::schema::{users_views, videos};
+
+    match users_views::table
+        .inner_join(videos::table.on(videos::id.eq(users_views::video_id)))
+        .filter(users_views::user_id.eq(uid))
+        .order(users_views::created.asc())
+        .select((
+            videos::title,
+            users_views::seconds,
+            users_views::duration,
+            users_views::completed,
+            users_views::created,
+            users_views::updated,
+        ))
+        .load::<ViewExport>(&**connection)
+    {
+        Ok(views) => views,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets every vote of the user with
+/// the title of the video.
+pub fn get_votes(connection: &DbConn, uid: i64) -> Vec<VoteExport> {
+    use club_coding::schema::{users_videos_votes, videos};
+
+    match users_videos_votes::table
+        .inner_join(videos::table.on(videos::id.eq(users_videos_votes::video_id)))
+        .filter(users_videos_votes::user_id.eq(uid))
+        .order(users_videos_votes::created.asc())
+        .select((
+            videos::title,
+            users_videos_votes::is_like,
+            users_videos_votes::created,
+        ))
+        .load::<VoteExport>(&**connection)
+    {
+        Ok(votes) => votes,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the comments of the user that have
+/// not been deleted with the title of the video.
+pub fn get_comments(connection: &DbConn, uid: i64) -> Vec<CommentExport> {
+    use club_coding::schema::{users_videos_comments, videos};
+
+    match users_videos_comments::table
+        .inner_join(videos::table.on(videos::id.eq(users_videos_comments::video_id)))
+        .filter(users_videos_comments::user_id.eq(uid))
+        .filter(users_videos_comments::deleted.eq(false))
+        .order(users_videos_comments::created.asc())
+        .select((
+            videos::title,
+            users_videos_comments::body,
+            users_videos_comments::created,
+        ))
+        .load::<CommentExport>(&**connection)
+    {
+        Ok(comments) => comments,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the series the user has access
+/// to with the title of the series.
+pub fn get_series_access(connection: &DbConn, uid: i64) -> Vec<SeriesAccessExport> {
+    use club_coding::schema::{series, users_series_access};
+
+    match users_series_access::table
+        .inner_join(series::table.on(series::id.eq(users_series_access::series_id)))
+        .filter(users_series_access::user_id.eq(uid))
+        .order(users_series_access::created.asc())
+        .select((
+            series::title,
+            users_series_access::bought,
+            users_series_access::created,
+        ))
+        .load::<SeriesAccessExport>(&**connection)
+    {
+        Ok(access) => access,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the certificates of the user
+/// with the title of the series.
+pub fn get_certificates(connection: &DbConn, uid: i64) -> Vec<CertificateExport> {
+    use club_coding::schema::{series, users_certificates};
+
+    match users_certificates::table
+        .inner_join(series::table.on(series::id.eq(users_certificates::serie_id)))
+        .filter(users_certificates::user_id.eq(uid))
+        .order(users_certificates::created.asc())
+        .select((
+            users_certificates::uuid,
+            series::title,
+            users_certificates::created,
+        ))
+        .load::<CertificateExport>(&**connection)
+    {
+        Ok(certificates) => certificates,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the charges of the user
+/// with the title of the series.
+pub fn get_charges(connection: &DbConn, uid: i64) -> Vec<ChargeExport> {
+    use club_coding::schema::{series, users_stripe_charge};
+
+    match users_stripe_charge::table
+        .inner_join(series::table.on(series::id.eq(users_stripe_charge::series_id)))
+        .filter(users_stripe_charge::user_id.eq(uid))
+        .order(users_stripe_charge::created_at_stripe.asc())
+        .select((
+            users_stripe_charge::uuid,
+            series::title,
+            users_stripe_charge::amount,
+            users_stripe_charge::currency,
+            users_stripe_charge::amount_refunded,
+            users_stripe_charge::refunded,
+            users_stripe_charge::status,
+            users_stripe_charge::created_at_stripe,
+        ))
+        .load::<ChargeExport>(&**connection)
+    {
+        Ok(charges) => charges,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the metadata of
+/// the cards of the user.
+pub fn get_cards(connection: &DbConn, uid: i64) -> Vec<CardExport> {
+    use club_coding::schema::users_stripe_card::dsl::*;
+
+    match users_stripe_card
+        .filter(user_id.eq(uid))
+        .select((brand, country, last4, exp_month, exp_year, name))
+        .load::<CardExport>(&**connection)
+    {
+        Ok(cards) => cards,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the audit log events of the user.
+pub fn get_audit_events(connection: &DbConn, uid: i64) -> Vec<AuditEventExport> {
+    use club_coding::schema::users_audit_log::dsl::*;
+
+    match users_audit_log
+        .filter(user_id.eq(uid))
+        .order(created.asc())
+        .select((event, ip, user_agent, details, created))
+        .load::<AuditEventExport>(&**connection)
+    {
+        Ok(events) => events,
+        Err(_) => vec![],
+    }
+}
+
+/// Deletes the account of the user. Sessions,
+/// tokens, two-factor settings, linked identities,
+/// cards and group memberships are removed and the user row is
+/// anonymised. Charges are kept for accounting
+/// and only refer to the anonymised user. Audit
+/// log events are kept without the ip and user agent.
+/// Comments are blanked and marked as deleted so
+/// the replies to them are kept.
+/// Everything is done in one transaction so an
+/// account is never left half deleted.
+pub fn delete_account(connection: &DbConn, uid: i64) -> Result<(), Error> {
+    use club_coding::schema::{users, users_audit_log, users_group, users_identities,
+                              users_recover_email, users_sessions, users_stripe_card,
+                              users_stripe_customer, users_stripe_token, users_two_factor,
+                              users_two_factor_recovery, users_verify_email,
+                              users_videos_comments};
+
+    let result = connection.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::delete(users_sessions::table.filter(users_sessions::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_verify_email::table.filter(users_verify_email::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_recover_email::table.filter(users_recover_email::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_two_factor::table.filter(users_two_factor::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(
+            users_two_factor_recovery::table.filter(users_two_factor_recovery::user_id.eq(uid)),
+        ).execute(&**connection)?;
+        diesel::delete(users_identities::table.filter(users_identities::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_stripe_card::table.filter(users_stripe_card::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_stripe_token::table.filter(users_stripe_token::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::delete(users_group::table.filter(users_group::user_id.eq(uid)))
+            .execute(&**connection)?;
+        diesel::update(users_stripe_customer::table.filter(users_stripe_customer::user_id.eq(uid)))
+            .set(users_stripe_customer::email.eq(None::<String>))
+            .execute(&**connection)?;
+        diesel::update(
+            users_audit_log::table.filter(
+                users_audit_log::user_id
+                    .eq(uid)
+                    .or(users_audit_log::actor_id.eq(uid)),
+            ),
+        ).set((
+            users_audit_log::ip.eq(None::<String>),
+            users_audit_log::user_agent.eq(None::<String>),
+        ))
+            .execute(&**connection)?;
+        diesel::update(
+            users_videos_comments::table.filter(users_videos_comments::user_id.eq(uid)),
+        ).set((
+            users_videos_comments::body.eq(""),
+            users_videos_comments::deleted.eq(true),
+        ))
+            .execute(&**connection)?;
+        diesel::update(users::table.find(uid))
+            .set((
+                users::username.eq(format!("deleted-{}", uid)),
+                users::email.eq(format!("deleted-{}@deleted.clubcoding.com", uid)),
+                users::password.eq(""),
+                users::verified.eq(false),
+            ))
+            .execute(&**connection)?;
+        Ok(())
+    });
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete account.")),
+    }
+}
@@ -0,0 +1,432 @@
+pub mod database;
+
+use chrono::NaiveDateTime;
+use database::{DbConn, RedisConnection};
+use passwords;
+use payment;
+use rocket::Route;
+use rocket::http::{ContentType, Cookie, Cookies};
+use rocket::response::{Redirect, Response};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use sessions;
+use std::io::Cursor;
+use structs::LoggedInContext;
+use users::User;
+
+#[cfg(test)]
+mod tests;
+
+/// The profile of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportProfile {
+    username: String,
+    email: String,
+    verified: bool,
+    created: NaiveDateTime,
+}
+
+/// A session of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportSession {
+    ip: String,
+    user_agent: String,
+    created: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    expires: NaiveDateTime,
+}
+
+/// A video the user has watched
+/// in the data export.
+#[derive(Serialize)]
+struct ExportView {
+    video: String,
+    seconds: i32,
+    duration: i32,
+    completed: bool,
+    created: NaiveDateTime,
+    updated: NaiveDateTime,
+}
+
+/// A vote of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportVote {
+    video: String,
+    like: bool,
+    created: NaiveDateTime,
+}
+
+/// A comment of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportComment {
+    video: String,
+    body: String,
+    created: NaiveDateTime,
+}
+
+/// A series the user has access
+/// to in the data export.
+#[derive(Serialize)]
+struct ExportSeriesAccess {
+    serie: String,
+    bought: bool,
+    created: NaiveDateTime,
+}
+
+/// A certificate of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportCertificate {
+    uuid: String,
+    serie: String,
+    created: NaiveDateTime,
+}
+
+/// A charge of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportCharge {
+    uuid: String,
+    serie: String,
+    amount: i32,
+    currency: String,
+    amount_refunded: i32,
+    refunded: bool,
+    status: String,
+    created: i64,
+}
+
+/// A card of the user in the data export.
+/// Only the metadata we store is included,
+/// never the full card number.
+#[derive(Serialize)]
+struct ExportCard {
+    brand: String,
+    country: String,
+    last4: String,
+    exp_month: i32,
+    exp_year: i32,
+    name: Option<String>,
+}
+
+/// A security event of the user
+/// in the data export.
+#[derive(Serialize)]
+struct ExportAuditEvent {
+    event: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    details: Option<String>,
+    created: NaiveDateTime,
+}
+
+/// Everything tied to the user
+/// in the data export.
+#[derive(Serialize)]
+struct Export {
+    profile: ExportProfile,
+    sessions: Vec<ExportSession>,
+    views: Vec<ExportView>,
+    votes: Vec<ExportVote>,
+    comments: Vec<ExportComment>,
+    series_access: Vec<ExportSeriesAccess>,
+    certificates: Vec<ExportCertificate>,
+    charges: Vec<ExportCharge>,
+    cards: Vec<ExportCard>,
+    audit_events: Vec<ExportAuditEvent>,
+}
+
+/// Collects everything tied to
+/// the user for the data export.
+fn export_user(conn: &DbConn, user: &User) -> Option<Export> {
+    let profile = match database::get_user(conn, user.id) {
+        Some(profile) => ExportProfile {
+            username: profile.username,
+            email: profile.email,
+            verified: profile.verified,
+            created: profile.created,
+        },
+        None => return None,
+    };
+
+    Some(Export {
+        profile: profile,
+        sessions: database::get_sessions(conn, user.id)
+            .into_iter()
+            .map(|session| ExportSession {
+                ip: session.ip,
+                user_agent: session.user_agent,
+                created: session.created,
+                last_seen: session.last_seen,
+                expires: session.expires,
+            })
+            .collect(),
+        views: database::get_views(conn, user.id)
+            .into_iter()
+            .map(|view| ExportView {
+                video: view.video,
+                seconds: view.seconds,
+                duration: view.duration,
+                completed: view.completed,
+                created: view.created,
+                updated: view.updated,
+            })
+            .collect(),
+        votes: database::get_votes(conn, user.id)
+            .into_iter()
+            .map(|vote| ExportVote {
+                video: vote.video,
+                like: vote.is_like,
+                created: vote.created,
+            })
+            .collect(),
+        comments: database::get_comments(conn, user.id)
+            .into_iter()
+            .map(|comment| ExportComment {
+                video: comment.video,
+                body: comment.body,
+                created: comment.created,
+            })
+            .collect(),
+        series_access: database::get_series_access(conn, user.id)
+            .into_iter()
+            .map(|access| ExportSeriesAccess {
+                serie: access.serie,
+                bought: access.bought,
+                created: access.created,
+            })
+            .collect(),
+        certificates: database::get_certificates(conn, user.id)
+            .into_iter()
+            .map(|certificate| ExportCertificate {
+                uuid: certificate.uuid,
+                serie: certificate.serie,
+                created: certificate.created,
+            })
+            .collect(),
+        charges: database::get_charges(conn, user.id)
+            .into_iter()
+            .map(|charge| ExportCharge {
+                uuid: charge.uuid,
+                serie: charge.serie,
+                amount: charge.amount,
+                currency: charge.currency,
+                amount_refunded: charge.amount_refunded,
+                refunded: charge.refunded,
+                status: charge.status,
+                created: charge.created_at_stripe,
+            })
+            .collect(),
+        cards: database::get_cards(conn, user.id)
+            .into_iter()
+            .map(|card| ExportCard {
+                brand: card.brand,
+                country: card.country,
+                last4: card.last4,
+                exp_month: card.exp_month,
+                exp_year: card.exp_year,
+                name: card.name,
+            })
+            .collect(),
+        audit_events: database::get_audit_events(conn, user.id)
+            .into_iter()
+            .map(|audit_event| ExportAuditEvent {
+                event: audit_event.event,
+                ip: audit_event.ip,
+                user_agent: audit_event.user_agent,
+                details: audit_event.details,
+                created: audit_event.created,
+            })
+            .collect(),
+    })
+}
+
+/// GET Endpoint for the page to export
+/// your data and delete your account.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// Responds with the Account Template
+/// in the settings folder.
+#[get("/settings/account")]
+fn account_page(user: User) -> Template {
+    let context = LoggedInContext {
+        header: "Account",
+        user: user,
+    };
+    Template::render("settings/account", &context)
+}
+
+/// GET Endpoint for the page to export
+/// your data and delete your account.
+/// This endpoint will kick in if the user
+/// is not logged in and will redirect the
+/// user to the index.
+#[get("/settings/account", rank = 2)]
+fn account_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint to download everything
+/// tied to the user as a JSON file.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+#[get("/settings/account/export")]
+fn export(conn: DbConn, user: User) -> Option<Response<'static>> {
+    let data = match export_user(&conn, &user) {
+        Some(data) => data,
+        None => return None,
+    };
+    let json_string = match serde_json::to_string_pretty(&data) {
+        Ok(json_string) => json_string,
+        Err(_) => return None,
+    };
+    Some(
+        Response::build()
+            .header(ContentType::JSON)
+            .raw_header(
+                "Content-Disposition",
+                "attachment; filename=\"club-coding-data.json\"",
+            )
+            .sized_body(Cursor::new(json_string))
+            .finalize(),
+    )
+}
+
+/// GET Endpoint to download your data.
+/// This endpoint will kick in if the user
+/// is not logged in and will redirect the
+/// user to the index.
+#[get("/settings/account/export", rank = 2)]
+fn export_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for deleting
+/// the account of a user.
+#[derive(Deserialize)]
+struct DeleteAccountStruct {
+    /// The current password of the user.
+    password: String,
+    /// The username typed again to
+    /// confirm the deletion.
+    username: String,
+}
+
+/// Struct for responding with a JSON
+/// message.
+#[derive(Serialize)]
+struct Message<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+    /// Boolean of whether the
+    /// account was deleted.
+    deleted: bool,
+}
+
+/// POST Endpoint to delete your account.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// It requires all of the parameters in the
+/// DeleteAccountStruct in a JSON format.
+/// Logs the user out everywhere before the
+/// account is deleted.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/account/delete", format = "application/json", data = "<json_data>")]
+fn delete_account<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    mut cookies: Cookies,
+    json_data: Json<DeleteAccountStruct>,
+) -> Json<Message<'a>> {
+    if json_data.username != user.username {
+        return Json(Message {
+            text: "The username doesn't match.",
+            deleted: false,
+        });
+    }
+
+    let password_hash = match database::get_user(&conn, user.id) {
+        Some(result) => result.password,
+        None => {
+            return Json(Message {
+                text: "An unknown error occured. Please try again later.",
+                deleted: false,
+            })
+        }
+    };
+    match passwords::verify_password(&json_data.password, &password_hash) {
+        Ok(true) => {}
+        _ => {
+            return Json(Message {
+                text: "The password is incorrect.",
+                deleted: false,
+            })
+        }
+    }
+
+    // Deleting the account doesn't stop the
+    // subscription at Stripe from renewing.
+    match payment::database::get_membership(&conn, user.id) {
+        Some(ref membership)
+            if payment::database::is_active_member(&conn, user.id)
+                && !membership.cancel_at_period_end =>
+        {
+            return Json(Message {
+                text: "Cancel your membership before deleting your account.",
+                deleted: false,
+            })
+        }
+        _ => {}
+    }
+
+    match sessions::database::revoke_other_sessions(&conn, &redis_conn, user.id, None) {
+        Ok(_) => {}
+        Err(_) => {
+            return Json(Message {
+                text: "An unknown error occured. Please try again later.",
+                deleted: false,
+            })
+        }
+    }
+
+    match database::delete_account(&conn, user.id) {
+        Ok(_) => {
+            cookies.remove_private(Cookie::named("session_token"));
+            Json(Message {
+                text: "Your account has been deleted.",
+                deleted: true,
+            })
+        }
+        Err(_) => Json(Message {
+            text: "An unknown error occured. Please try again later.",
+            deleted: false,
+        }),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![
+        account_page,
+        account_page_nouser,
+        export,
+        export_nouser,
+        delete_account
+    ]
+}
@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+
+    #[test]
+    fn account_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/account").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
+
+    #[test]
+    fn export_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/account/export").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
+}
@@ -0,0 +1,132 @@
+use rocket_contrib::templates::Template;
+use admin::structs::Administrator;
+use audit::{self, AuditEvent, ALL_EVENTS};
+use chrono::NaiveDateTime;
+use database::DbConn;
+use diesel::prelude::*;
+use rocket::Route;
+use std::collections::HashMap;
+
+/// Number of events on each page.
+static EVENTS_PER_PAGE: i64 = 50;
+
+#[derive(Serialize)]
+struct Event {
+    user_id: Option<i64>,
+    username: Option<String>,
+    actor: Option<String>,
+    event: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    details: Option<String>,
+    created: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct EventFilter {
+    name: &'static str,
+    description: &'static str,
+}
+
+#[derive(Serialize)]
+struct AuditContext<'a> {
+    header: &'a str,
+    user: Administrator,
+    events: Vec<Event>,
+    filters: Vec<EventFilter>,
+    event: String,
+    user_id: Option<i64>,
+    page: i64,
+    next_page: bool,
+}
+
+/// Gets the usernames of the users
+/// the events belong to or were done by.
+fn get_usernames(connection: &DbConn, ids: Vec<i64>) -> HashMap<i64, String> {
+    use club_coding::schema::users::dsl::*;
+
+    match users
+        .filter(id.eq_any(ids))
+        .select((id, username))
+        .load::<(i64, String)>(&**connection)
+    {
+        Ok(result) => result.into_iter().collect(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+#[get("/audit?<event>&<user_id>&<page>")]
+pub fn audit_log(
+    conn: DbConn,
+    user: Administrator,
+    event: Option<String>,
+    user_id: Option<i64>,
+    page: Option<i64>,
+) -> Template {
+    let event = event
+        .and_then(|name| AuditEvent::from_name(&name))
+        .map(|event| event.name());
+    let page = match page {
+        Some(page) if page > 0 => page,
+        _ => 1,
+    };
+
+    let events = audit::database::get_events(
+        &conn,
+        event,
+        user_id,
+        (page - 1) * EVENTS_PER_PAGE,
+        EVENTS_PER_PAGE,
+    );
+
+    let mut ids = vec![];
+    for entry in &events {
+        ids.extend(entry.user_id);
+        ids.extend(entry.actor_id);
+    }
+    let usernames = get_usernames(&conn, ids);
+
+    let next_page = events.len() as i64 == EVENTS_PER_PAGE;
+    let events = events
+        .into_iter()
+        .map(|entry| Event {
+            user_id: entry.user_id,
+            username: entry.user_id.and_then(|uid| usernames.get(&uid).cloned()),
+            actor: entry.actor_id.and_then(|uid| usernames.get(&uid).cloned()),
+            event: match AuditEvent::from_name(&entry.event) {
+                Some(audit_event) => audit_event.description().to_string(),
+                None => entry.event,
+            },
+            ip: entry.ip,
+            user_agent: entry.user_agent,
+            details: entry.details,
+            created: entry.created,
+        })
+        .collect();
+
+    let context = AuditContext {
+        header: "Club Coding",
+        user: user,
+        events: events,
+        filters: ALL_EVENTS
+            .iter()
+            .map(|event| EventFilter {
+                name: event.name(),
+                description: event.description(),
+            })
+            .collect(),
+        event: event.unwrap_or("").to_string(),
+        user_id: user_id,
+        page: page,
+        next_page: next_page,
+    };
+    Template::render("admin/audit", &context)
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![audit_log]
+}
@@ -0,0 +1,92 @@
+use rocket_contrib::templates::Template;
+use admin::structs::Administrator;
+use chrono::NaiveDateTime;
+use comments::database::{delete_comment_and_replies, get_comment, get_newest_comments,
+                         set_comment_hidden};
+use database::DbConn;
+use rocket_contrib::json::Json;
+use rocket::Route;
+
+#[derive(Serialize)]
+struct Comment {
+    uuid: String,
+    username: String,
+    video_uuid: String,
+    video: String,
+    body: String,
+    hidden: bool,
+    deleted: bool,
+    created: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct CommentsContext<'a> {
+    header: &'a str,
+    user: Administrator,
+    comments: Vec<Comment>,
+}
+
+fn get_all_comments(connection: &DbConn) -> Vec<Comment> {
+    let mut ret: Vec<Comment> = vec![];
+
+    for comment in get_newest_comments(connection, 100) {
+        ret.push(Comment {
+            uuid: comment.uuid,
+            username: comment.username,
+            video_uuid: comment.video_uuid,
+            video: comment.video_title,
+            body: comment.body,
+            hidden: comment.hidden,
+            deleted: comment.deleted,
+            created: comment.created,
+        })
+    }
+    ret
+}
+
+#[get("/comments")]
+pub fn comments(conn: DbConn, user: Administrator) -> Template {
+    let context = CommentsContext {
+        header: "Club Coding",
+        user: user,
+        comments: get_all_comments(&conn),
+    };
+    Template::render("admin/comments", &context)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct HideComment {
+    hidden: bool,
+}
+
+#[post("/comments/hide/<uid>", format = "application/json", data = "<data>")]
+pub fn hide_comment(
+    conn: DbConn,
+    uid: String,
+    _user: Administrator,
+    data: Json<HideComment>,
+) -> Result<(), ()> {
+    match set_comment_hidden(&conn, &uid, data.0.hidden) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(()),
+    }
+}
+
+#[post("/comments/delete/<uid>")]
+pub fn delete_comment(conn: DbConn, uid: String, _user: Administrator) -> Result<(), ()> {
+    match get_comment(&conn, &uid) {
+        Some(comment) => match delete_comment_and_replies(&conn, comment.id) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(()),
+        },
+        None => Err(()),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![comments, hide_comment, delete_comment]
+}
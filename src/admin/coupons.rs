@@ -0,0 +1,224 @@
+use rocket_contrib::templates::Template;
+use admin::structs::Administrator;
+use admin::series::{get_all_seriesc, SerieC};
+use rocket::response::{Flash, Redirect};
+use rocket::request::{FlashMessage, Form};
+use club_coding::create_new_coupon;
+use database::DbConn;
+use chrono::{NaiveDate, NaiveDateTime};
+use coupons::{self, KINDS};
+use currency::{self, CURRENCIES, DEFAULT_CURRENCY};
+use rocket::Route;
+use std::collections::HashMap;
+
+#[derive(Serialize)]
+struct Coupon {
+    id: i64,
+    code: String,
+    kind: String,
+    value: i32,
+    currency: String,
+    expires: Option<NaiveDateTime>,
+    max_redemptions: Option<i32>,
+    times_redeemed: i32,
+    series: Vec<String>,
+    created: NaiveDateTime,
+}
+
+#[derive(Serialize)]
+struct CouponsContext<'a> {
+    header: &'a str,
+    user: Administrator,
+    coupons: Vec<Coupon>,
+    flash_name: String,
+    flash_msg: String,
+}
+
+#[derive(Serialize)]
+struct NewCouponContext<'a> {
+    header: &'a str,
+    user: Administrator,
+    kinds: &'a [&'static str],
+    currencies: &'a [&'static str],
+    default_currency: &'a str,
+    series: Vec<SerieC>,
+    flash_name: String,
+    flash_msg: String,
+}
+
+fn flash_parts(flash: Option<FlashMessage>) -> (String, String) {
+    match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    }
+}
+
+#[get("/coupons")]
+pub fn coupons_list(conn: DbConn, user: Administrator, flash: Option<FlashMessage>) -> Template {
+    let titles: HashMap<i64, String> = get_all_seriesc(&conn)
+        .into_iter()
+        .map(|serie| (serie.id, serie.name))
+        .collect();
+    let all = coupons::database::get_coupons(&conn)
+        .into_iter()
+        .map(|coupon| Coupon {
+            series: coupons::database::get_coupon_series(&conn, coupon.id)
+                .iter()
+                .filter_map(|sid| titles.get(sid).cloned())
+                .collect(),
+            id: coupon.id,
+            code: coupon.code,
+            kind: coupon.kind,
+            value: coupon.value,
+            currency: coupon.currency,
+            expires: coupon.expires,
+            max_redemptions: coupon.max_redemptions,
+            times_redeemed: coupon.times_redeemed,
+            created: coupon.created,
+        })
+        .collect();
+    let (name, msg) = flash_parts(flash);
+    let context = CouponsContext {
+        header: "Club Coding",
+        user: user,
+        coupons: all,
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Template::render("admin/coupons", &context)
+}
+
+#[get("/coupons/new")]
+pub fn new_coupon(conn: DbConn, user: Administrator, flash: Option<FlashMessage>) -> Template {
+    let (name, msg) = flash_parts(flash);
+    let context = NewCouponContext {
+        header: "Club Coding",
+        user: user,
+        kinds: &KINDS,
+        currencies: &CURRENCIES,
+        default_currency: DEFAULT_CURRENCY,
+        series: get_all_seriesc(&conn),
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Template::render("admin/new_coupon", &context)
+}
+
+/// Form for new coupons. The value is a percentage
+/// for percent coupons and cents in the currency
+/// for fixed ones.
+/// Expires is a date, the coupon can be used until
+/// the end of it. Series is a comma separated list
+/// of the ids of the series the coupon is restricted
+/// to, empty for all series.
+#[derive(FromForm)]
+pub struct NewCoupon {
+    code: String,
+    kind: String,
+    value: i32,
+    currency: String,
+    expires: Option<String>,
+    max_redemptions: Option<i32>,
+    series: String,
+}
+
+/// Reads the expiry date of the form.
+/// Returns Err if it isn't a date.
+fn parse_expires(expires: &Option<String>) -> Result<Option<NaiveDateTime>, ()> {
+    match *expires {
+        Some(ref date) if !date.trim().is_empty() => {
+            match NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+                Ok(date) => Ok(Some(date.and_hms(23, 59, 59))),
+                Err(_) => Err(()),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reads the comma separated series ids.
+/// Returns Err if one isn't a number.
+fn parse_series(series: &str) -> Result<Vec<i64>, ()> {
+    series
+        .split(',')
+        .map(|sid| sid.trim())
+        .filter(|sid| !sid.is_empty())
+        .map(|sid| sid.parse::<i64>().map_err(|_| ()))
+        .collect()
+}
+
+#[post("/coupons/new", data = "<coupon>")]
+pub fn insert_new_coupon(
+    conn: DbConn,
+    _user: Administrator,
+    coupon: Form<NewCoupon>,
+) -> Flash<Redirect> {
+    let new_coupon = coupon.into_inner();
+    let code = coupons::normalize_code(&new_coupon.code);
+    if !coupons::valid_code(&code) {
+        return Flash::error(
+            Redirect::to("/admin/coupons/new"),
+            "Codes can only have letters, numbers and dashes.",
+        );
+    }
+    if !coupons::valid_value(&new_coupon.kind, new_coupon.value) {
+        return Flash::error(
+            Redirect::to("/admin/coupons/new"),
+            "Percent coupons take off 1 to 100 percent and fixed coupons at least a cent.",
+        );
+    }
+    let coupon_currency = match currency::supported(&new_coupon.currency) {
+        Some(coupon_currency) => coupon_currency,
+        None => return Flash::error(Redirect::to("/admin/coupons/new"), "Invalid currency."),
+    };
+    if new_coupon.max_redemptions.map_or(false, |max| max < 1) {
+        return Flash::error(
+            Redirect::to("/admin/coupons/new"),
+            "Max redemptions has to be at least 1.",
+        );
+    }
+    let expires = match parse_expires(&new_coupon.expires) {
+        Ok(expires) => expires,
+        Err(_) => return Flash::error(Redirect::to("/admin/coupons/new"), "Invalid expiry date."),
+    };
+    let series = match parse_series(&new_coupon.series) {
+        Ok(series) => series,
+        Err(_) => return Flash::error(Redirect::to("/admin/coupons/new"), "Invalid series."),
+    };
+    match create_new_coupon(
+        &*conn,
+        &code,
+        &new_coupon.kind,
+        new_coupon.value,
+        expires,
+        new_coupon.max_redemptions,
+        coupon_currency,
+        &series,
+    ) {
+        Ok(_) => Flash::success(Redirect::to("/admin/coupons"), "Coupon created."),
+        Err(_) => Flash::error(
+            Redirect::to("/admin/coupons/new"),
+            "Could not create the coupon, the code might already be taken.",
+        ),
+    }
+}
+
+#[post("/coupons/expire/<id>")]
+pub fn expire_coupon(conn: DbConn, _user: Administrator, id: i64) -> Flash<Redirect> {
+    if coupons::database::expire_coupon(&conn, id) {
+        Flash::success(Redirect::to("/admin/coupons"), "Coupon expired.")
+    } else {
+        Flash::error(
+            Redirect::to("/admin/coupons"),
+            "Could not expire the coupon.",
+        )
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![coupons_list, new_coupon, insert_new_coupon, expire_coupon]
+}
@@ -2,7 +2,10 @@ use rocket::Route;
 use rand;
 use std;
 
+mod audit;
+mod coupons;
 mod series;
+mod comments;
 mod group;
 mod video;
 mod users;
@@ -48,5 +51,14 @@ pub fn endpoints() -> Vec<Route> {
     let mut video = video::endpoints();
     total.append(&mut video);
 
+    let mut comments = comments::endpoints();
+    total.append(&mut comments);
+
+    let mut audit = audit::endpoints();
+    total.append(&mut audit);
+
+    let mut coupons = coupons::endpoints();
+    total.append(&mut coupons);
+
     total
 }
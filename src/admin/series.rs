@@ -2,7 +2,8 @@ use rocket_contrib::templates::Template;
 use admin::structs::{Administrator, LoggedInContext};
 use rocket::response::Redirect;
 use club_coding::models::Series;
-use club_coding::create_new_series;
+use club_coding::{create_new_series, save_series_price};
+use currency::{self, CURRENCIES, DEFAULT_CURRENCY};
 use database::{DbConn, RedisConnection};
 use chrono::NaiveDateTime;
 use rocket_contrib::json::Json;
@@ -34,8 +35,8 @@ pub struct SeriesContext<'a> {
 
 #[derive(Deserialize, Serialize)]
 pub struct SerieC {
-    id: i64,
-    name: String,
+    pub id: i64,
+    pub name: String,
 }
 
 pub fn get_all_seriesc(connection: &DbConn) -> Vec<SerieC> {
@@ -148,6 +149,12 @@ fn get_serie(connection: &DbConn, uid: &str) -> Option<Series> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SeriePrice {
+    currency: String,
+    price: Option<i32>,
+}
+
 #[derive(Serialize)]
 pub struct EditSeries<'a> {
     header: &'a str,
@@ -156,11 +163,31 @@ pub struct EditSeries<'a> {
     title: String,
     description: String,
     price: i32,
+    default_currency: &'a str,
+    prices: Vec<SeriePrice>,
     published: bool,
     archived: bool,
     in_development: bool,
 }
 
+/// Gets the price of the series in every currency
+/// other than the default one. The price is None
+/// for currencies the series has no price in.
+fn get_serie_prices(connection: &DbConn, sid: i64) -> Vec<SeriePrice> {
+    let prices = currency::database::get_series_prices(connection, sid);
+    CURRENCIES
+        .iter()
+        .filter(|code| **code != DEFAULT_CURRENCY)
+        .map(|code| SeriePrice {
+            currency: code.to_string(),
+            price: prices
+                .iter()
+                .find(|&&(ref currency, _)| *currency == **code)
+                .map(|&(_, price)| price),
+        })
+        .collect()
+}
+
 #[get("/series/edit/<uuid>")]
 pub fn edit_series(conn: DbConn, uuid: String, user: Administrator) -> Option<Template> {
     match get_serie(&conn, &uuid) {
@@ -172,6 +199,8 @@ pub fn edit_series(conn: DbConn, uuid: String, user: Administrator) -> Option<Te
                 title: serie.title,
                 description: serie.description,
                 price: serie.price,
+                default_currency: DEFAULT_CURRENCY,
+                prices: get_serie_prices(&conn, serie.id),
                 published: serie.published,
                 archived: serie.archived,
                 in_development: serie.in_development,
@@ -222,6 +251,34 @@ pub fn update_serie(
     }
 }
 
+/// Sets the prices of the series in the other
+/// currencies. A currency without a price sells
+/// the series at the price in the default currency.
+#[post("/series/prices/<uid>", format = "application/json", data = "<data>")]
+pub fn update_serie_prices(
+    conn: DbConn,
+    uid: String,
+    _user: Administrator,
+    data: Json<Vec<SeriePrice>>,
+) -> Result<(), ()> {
+    let serie = get_serie(&conn, &uid).ok_or(())?;
+    for price in data.into_inner() {
+        let code = match currency::supported(&price.currency) {
+            Some(code) if code != DEFAULT_CURRENCY => code,
+            _ => return Err(()),
+        };
+        match price.price {
+            Some(amount) if amount > 0 => {
+                save_series_price(&*conn, serie.id, code, amount).map_err(|_| ())?
+            }
+            Some(_) => return Err(()),
+            None => currency::database::delete_series_price(&conn, serie.id, code)
+                .map_err(|_| ())?,
+        }
+    }
+    Ok(())
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
@@ -233,5 +290,6 @@ pub fn endpoints() -> Vec<Route> {
         insert_new_series,
         edit_series,
         update_serie,
+        update_serie_prices,
     ]
 }
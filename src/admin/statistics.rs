@@ -1,51 +1,506 @@
 use rocket_contrib::templates::Template;
 use users::get_users;
-use admin::structs::{Administrator, LoggedInContext};
+use admin::structs::Administrator;
 use series::database::get_series;
 use videos::database::get_videos;
 use rocket::Route;
-use database::DbConn;
+use database::{DbConn, RedisConnection};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use club_coding::models::{UsersStripeCharge, ViewJoin};
+use diesel::prelude::*;
+use redis::Commands;
+use rocket_contrib::json::Json;
+use std::collections::BTreeMap;
+
+/// Redis key for the cached dashboard statistics.
+static STATISTICS_KEY: &'static str = "admin:statistics";
+
+/// Number of seconds the dashboard statistics
+/// are cached in Redis.
+static STATISTICS_TTL: usize = 300;
+
+/// Statistics shown on the admin dashboard
+/// together with the value of the previous
+/// period so the template can show the change.
+#[derive(Serialize, Deserialize)]
+struct DashboardStatistics {
+    /// Number of views since midnight.
+    views_today: i64,
+    /// Number of views yesterday up to
+    /// the same time of day as now.
+    views_yesterday: i64,
+    /// Revenue in the current calendar month
+    /// minus refunds per currency, defined
+    /// by the currency * 100.
+    revenue_month: BTreeMap<String, i64>,
+    /// Revenue in the previous calendar month
+    /// minus refunds per currency, defined
+    /// by the currency * 100.
+    revenue_last_month: BTreeMap<String, i64>,
+    /// Number of distinct users that have
+    /// bought at least one series.
+    paying_users: usize,
+    /// Number of users that bought their
+    /// first series in the current
+    /// calendar month.
+    new_paying_users: usize,
+    /// Number of users that bought their
+    /// first series in the previous
+    /// calendar month.
+    new_paying_users_last_month: usize,
+}
+
+/// Returns the start of the current day
+/// and the start of the current calendar month.
+fn period_starts() -> (NaiveDateTime, NaiveDateTime) {
+    let today = Utc::now().naive_utc().date();
+    let start_of_month = NaiveDate::from_ymd(today.year(), today.month(), 1);
+    (today.and_hms(0, 0, 0), start_of_month.and_hms(0, 0, 0))
+}
+
+/// Returns the start of the calendar
+/// month before the month starting at
+/// start_of_month.
+fn previous_month_start(start_of_month: NaiveDateTime) -> NaiveDateTime {
+    let last_day_of_previous = start_of_month.date().pred();
+    NaiveDate::from_ymd(
+        last_day_of_previous.year(),
+        last_day_of_previous.month(),
+        1,
+    ).and_hms(0, 0, 0)
+}
+
+/// Counts the views created between
+/// from (inclusive) and to (exclusive).
+fn count_views(connection: &DbConn, from: NaiveDateTime, to: NaiveDateTime) -> i64 {
+    use club_coding::schema::users_views::dsl::*;
+
+    match users_views
+        .filter(created.ge(from))
+        .filter(created.lt(to))
+        .count()
+        .get_result::<i64>(&**connection)
+    {
+        Ok(views) => views,
+        Err(_) => 0,
+    }
+}
+
+/// Sums the paid charges created at Stripe
+/// between from (inclusive) and to (exclusive)
+/// with the refunded amount subtracted. Amounts
+/// in different currencies can't be added up
+/// so there is one sum per currency.
+fn sum_revenue(
+    connection: &DbConn,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> BTreeMap<String, i64> {
+    use club_coding::schema::users_stripe_charge::dsl::*;
+
+    let mut sums = BTreeMap::new();
+    if let Ok(charges) = users_stripe_charge
+        .filter(paid.eq(true))
+        .filter(created_at_stripe.ge(from.timestamp()))
+        .filter(created_at_stripe.lt(to.timestamp()))
+        .load::<UsersStripeCharge>(&**connection)
+    {
+        for charge in charges {
+            *sums.entry(charge.currency).or_insert(0) +=
+                (charge.amount - charge.amount_refunded) as i64;
+        }
+    }
+    sums
+}
+
+/// Gets when every paying user bought
+/// their first series.
+fn first_purchases(connection: &DbConn) -> Vec<NaiveDateTime> {
+    use club_coding::schema::users_series_access::dsl::*;
+
+    let mut firsts: BTreeMap<i64, NaiveDateTime> = BTreeMap::new();
+    if let Ok(purchases) = users_series_access
+        .filter(bought.eq(true))
+        .select((user_id, created))
+        .load::<(i64, NaiveDateTime)>(&**connection)
+    {
+        for (buyer, bought_at) in purchases {
+            let first = firsts.entry(buyer).or_insert(bought_at);
+            if bought_at < *first {
+                *first = bought_at;
+            }
+        }
+    }
+    firsts.into_iter().map(|(_, first)| first).collect()
+}
+
+/// Counts the first purchases made between
+/// from (inclusive) and to (exclusive).
+fn count_between(firsts: &[NaiveDateTime], from: NaiveDateTime, to: NaiveDateTime) -> usize {
+    firsts
+        .iter()
+        .filter(|first| **first >= from && **first < to)
+        .count()
+}
+
+/// Computes the dashboard statistics
+/// from the MySQL database.
+fn compute_statistics(connection: &DbConn) -> DashboardStatistics {
+    let (start_of_today, start_of_month) = period_starts();
+    let now = Utc::now().naive_utc();
+    let start_of_yesterday = start_of_today - Duration::days(1);
+    let start_of_last_month = previous_month_start(start_of_month);
+
+    let firsts = first_purchases(connection);
+
+    DashboardStatistics {
+        views_today: count_views(connection, start_of_today, now),
+        views_yesterday: count_views(connection, start_of_yesterday, now - Duration::days(1)),
+        revenue_month: sum_revenue(connection, start_of_month, now),
+        revenue_last_month: sum_revenue(connection, start_of_last_month, start_of_month),
+        paying_users: firsts.len(),
+        new_paying_users: count_between(&firsts, start_of_month, now),
+        new_paying_users_last_month: count_between(&firsts, start_of_last_month, start_of_month),
+    }
+}
+
+/// Gets the dashboard statistics from Redis
+/// if they are cached, otherwise computes
+/// them and caches them for STATISTICS_TTL
+/// seconds.
+fn get_statistics(mysql_conn: &DbConn, redis_conn: &RedisConnection) -> DashboardStatistics {
+    match redis_conn.get::<&str, String>(STATISTICS_KEY) {
+        Ok(result) => match serde_json::from_str(&result) {
+            Ok(statistics) => statistics,
+            Err(_) => compute_statistics(mysql_conn),
+        },
+        Err(_) => {
+            let statistics = compute_statistics(mysql_conn);
+            let json_string = match serde_json::to_string(&statistics) {
+                Ok(json_string) => json_string,
+                Err(_) => return statistics,
+            };
+            match redis_conn.set_ex::<&str, String, String>(
+                STATISTICS_KEY,
+                json_string,
+                STATISTICS_TTL,
+            ) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+            statistics
+        }
+    }
+}
+
+/// Revenue in one currency this month
+/// and the month before, for the
+/// comparison on the dashboard.
+#[derive(Serialize)]
+struct Revenue {
+    currency: String,
+    amount: i64,
+    last_amount: i64,
+}
+
+/// Pairs up the revenue of this month and the
+/// month before for every currency in either.
+fn compare_revenue(
+    month: &BTreeMap<String, i64>,
+    last_month: &BTreeMap<String, i64>,
+) -> Vec<Revenue> {
+    let currencies: BTreeMap<&String, ()> = month
+        .keys()
+        .chain(last_month.keys())
+        .map(|currency| (currency, ()))
+        .collect();
+    currencies
+        .keys()
+        .map(|currency| Revenue {
+            currency: currency.to_string(),
+            amount: *month.get(*currency).unwrap_or(&0),
+            last_amount: *last_month.get(*currency).unwrap_or(&0),
+        })
+        .collect()
+}
 
 #[derive(Serialize)]
 struct AdminContext<'a> {
     header: &'a str,
     user: Administrator,
-    views_today: usize,
+    views_today: i64,
+    views_yesterday: i64,
     videos_total: usize,
     series_total: usize,
-    revenue_month: u64,
+    revenue: Vec<Revenue>,
     paying_users: usize,
+    new_paying_users: usize,
+    new_paying_users_last_month: usize,
     total_users: usize,
 }
 
 #[get("/")]
-pub fn index(conn: DbConn, user: Administrator) -> Template {
+pub fn index(mysql_conn: DbConn, redis_conn: RedisConnection, user: Administrator) -> Template {
+    let statistics = get_statistics(&mysql_conn, &redis_conn);
     let context = AdminContext {
         header: "Club Coding",
         user: user,
-        views_today: 187232,
-        videos_total: get_videos(&conn).len(),
-        series_total: get_series(&conn).len(),
-        revenue_month: 102230,
-        paying_users: 123,
-        total_users: get_users(&conn).len(),
+        views_today: statistics.views_today,
+        views_yesterday: statistics.views_yesterday,
+        videos_total: get_videos(&mysql_conn).len(),
+        series_total: get_series(&mysql_conn).len(),
+        revenue: compare_revenue(&statistics.revenue_month, &statistics.revenue_last_month),
+        paying_users: statistics.paying_users,
+        new_paying_users: statistics.new_paying_users,
+        new_paying_users_last_month: statistics.new_paying_users_last_month,
+        total_users: get_users(&mysql_conn).len(),
     };
     Template::render("admin/index", &context)
 }
 
+/// Format used for dates in the
+/// views analytics query string.
+static DATE_FORMAT: &'static str = "%Y-%m-%d";
+
+/// Number of days shown on the views
+/// page if no range is selected.
+static DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// Largest number of days that
+/// can be shown on the views page.
+static MAX_RANGE_DAYS: i64 = 366;
+
+#[derive(Serialize)]
+struct ViewsContext<'a> {
+    header: &'a str,
+    user: Administrator,
+    from: String,
+    to: String,
+}
+
 #[get("/views")]
 pub fn views(user: Administrator) -> Template {
-    let context = LoggedInContext {
+    let (from, to) = parse_range(None, None);
+    let context = ViewsContext {
         header: "Club Coding",
         user: user,
+        from: from.format(DATE_FORMAT).to_string(),
+        to: to.format(DATE_FORMAT).to_string(),
     };
     Template::render("admin/views", &context)
 }
 
+/// View counts of a single video
+/// per bucket in the selected range.
+#[derive(Serialize)]
+pub struct VideoViews {
+    uuid: String,
+    title: String,
+    /// UUID of the series the video belongs to.
+    serie: String,
+    episode_number: i32,
+    counts: Vec<i64>,
+    total: i64,
+    /// Views of this episode relative to the
+    /// first watched episode of the series in
+    /// the range. Shows where viewers drop off.
+    retention: f64,
+}
+
+/// View counts of all videos in
+/// a series per bucket in the
+/// selected range.
+#[derive(Serialize)]
+pub struct SerieViews {
+    uuid: String,
+    title: String,
+    counts: Vec<i64>,
+    total: i64,
+}
+
+#[derive(Serialize)]
+pub struct ViewsData {
+    from: String,
+    to: String,
+    interval: String,
+    buckets: Vec<String>,
+    videos: Vec<VideoViews>,
+    series: Vec<SerieViews>,
+}
+
+/// Parses the from and to dates of the
+/// query string. Falls back to the last
+/// DEFAULT_RANGE_DAYS days if a date is
+/// missing or not valid. Ranges longer than
+/// MAX_RANGE_DAYS are cut to end at to.
+fn parse_range(from: Option<String>, to: Option<String>) -> (NaiveDate, NaiveDate) {
+    let today = Utc::now().naive_utc().date();
+    let to = to
+        .and_then(|to| NaiveDate::parse_from_str(&to, DATE_FORMAT).ok())
+        .unwrap_or(today);
+    let from = from
+        .and_then(|from| NaiveDate::parse_from_str(&from, DATE_FORMAT).ok())
+        .unwrap_or(to - Duration::days(DEFAULT_RANGE_DAYS));
+    let (from, to) = if from > to { (to, from) } else { (from, to) };
+    if to - from > Duration::days(MAX_RANGE_DAYS) {
+        (to - Duration::days(MAX_RANGE_DAYS), to)
+    } else {
+        (from, to)
+    }
+}
+
+/// Returns the first day of the bucket
+/// the date belongs to. Weeks start on
+/// mondays.
+fn bucket_start(date: NaiveDate, weekly: bool) -> NaiveDate {
+    if weekly {
+        date - Duration::days(date.weekday().num_days_from_monday() as i64)
+    } else {
+        date
+    }
+}
+
+/// Gets all views created between from
+/// and to (both inclusive) joined with
+/// the video and series they belong to.
+fn get_views_between(connection: &DbConn, from: NaiveDate, to: NaiveDate) -> Vec<ViewJoin> {
+    use club_coding::schema::{series, users_views, videos};
+
+    match users_views::table
+        .inner_join(videos::table.on(videos::id.eq(users_views::video_id)))
+        .inner_join(series::table.on(series::id.eq(videos::serie_id)))
+        .filter(users_views::created.ge(from.and_hms(0, 0, 0)))
+        .filter(users_views::created.lt(to.succ().and_hms(0, 0, 0)))
+        .select((
+            users_views::created,
+            videos::uuid,
+            videos::title,
+            videos::episode_number,
+            series::uuid,
+            series::title,
+        ))
+        .order((series::title.asc(), videos::episode_number.asc()))
+        .load::<ViewJoin>(&**connection)
+    {
+        Ok(views) => views,
+        Err(_) => vec![],
+    }
+}
+
+/// Groups the views between from and to
+/// into daily or weekly buckets per video
+/// and per series.
+fn get_views_data(connection: &DbConn, from: NaiveDate, to: NaiveDate, weekly: bool) -> ViewsData {
+    let step = if weekly { 7 } else { 1 };
+    let first = bucket_start(from, weekly);
+    let mut buckets: Vec<NaiveDate> = vec![];
+    let mut date = first;
+    while date <= to {
+        buckets.push(date);
+        date = date + Duration::days(step);
+    }
+
+    // Ordered by series title and episode number
+    // so the drop off can be read from top to bottom.
+    let mut videos: BTreeMap<(String, String, i32, String), VideoViews> = BTreeMap::new();
+    let mut series: BTreeMap<(String, String), SerieViews> = BTreeMap::new();
+
+    for view in get_views_between(connection, from, to) {
+        let ViewJoin {
+            created,
+            video_uuid,
+            video_title,
+            episode_number,
+            serie_uuid,
+            serie_title,
+        } = view;
+        let offset = (bucket_start(created.date(), weekly) - first).num_days() / step;
+        if offset < 0 || offset as usize >= buckets.len() {
+            continue;
+        }
+        let index = offset as usize;
+
+        let video = videos
+            .entry((
+                serie_title.clone(),
+                serie_uuid.clone(),
+                episode_number,
+                video_uuid.clone(),
+            ))
+            .or_insert(VideoViews {
+                uuid: video_uuid,
+                title: video_title,
+                serie: serie_uuid.clone(),
+                episode_number: episode_number,
+                counts: vec![0; buckets.len()],
+                total: 0,
+                retention: 0.0,
+            });
+        video.counts[index] += 1;
+        video.total += 1;
+
+        let serie = series
+            .entry((serie_title.clone(), serie_uuid.clone()))
+            .or_insert(SerieViews {
+                uuid: serie_uuid,
+                title: serie_title,
+                counts: vec![0; buckets.len()],
+                total: 0,
+            });
+        serie.counts[index] += 1;
+        serie.total += 1;
+    }
+
+    let mut videos: Vec<VideoViews> = videos.into_iter().map(|(_, video)| video).collect();
+    let mut current_serie = String::new();
+    let mut first_views = 0;
+    for video in &mut videos {
+        if video.serie != current_serie {
+            current_serie = video.serie.clone();
+            first_views = video.total;
+        }
+        if first_views > 0 {
+            video.retention = video.total as f64 / first_views as f64;
+        }
+    }
+
+    ViewsData {
+        from: from.format(DATE_FORMAT).to_string(),
+        to: to.format(DATE_FORMAT).to_string(),
+        interval: if weekly { "week" } else { "day" }.to_string(),
+        buckets: buckets
+            .iter()
+            .map(|bucket| bucket.format(DATE_FORMAT).to_string())
+            .collect(),
+        videos: videos,
+        series: series.into_iter().map(|(_, serie)| serie).collect(),
+    }
+}
+
+/// JSON Endpoint for the views page.
+/// Takes in an optional date range in
+/// the YYYY-MM-DD format and an optional
+/// interval, either day or week.
+/// Responds with the view counts per
+/// video and per series for every day
+/// or week in the range.
+#[get("/views/data?<from>&<to>&<interval>")]
+pub fn views_data(
+    conn: DbConn,
+    _user: Administrator,
+    from: Option<String>,
+    to: Option<String>,
+    interval: Option<String>,
+) -> Json<ViewsData> {
+    let (from, to) = parse_range(from, to);
+    let weekly = interval.map_or(false, |interval| interval == "week");
+    Json(get_views_data(&conn, from, to, weekly))
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
 /// instead we can keep all of the changes in here.
 pub fn endpoints() -> Vec<Route> {
-    routes![index, views]
+    routes![index, views, views_data]
 }
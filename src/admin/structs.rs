@@ -1,10 +1,12 @@
-use club_coding::models::{UsersAndSessions, UsersGroup};
+use club_coding::models::{UsersAndSessions, UsersGroup, UsersTwoFactor};
 use rocket::request::{self, FromRequest, Request};
 use rocket::Outcome;
 use database::{MySqlPool, RedisPool};
 use rocket::State;
 use redis::Commands;
 use diesel::prelude::*;
+use chrono::Utc;
+use sessions::SESSION_CACHE_TTL;
 
 /// Context for rendering tera templates
 /// for administrator endpoints.
@@ -22,8 +24,8 @@ pub struct LoggedInContext<'a> {
 /// that the administrator is logged in and an administrator.
 /// The difference between the user struct and the administrator
 /// struct is that the administrator struct requires the user
-/// is an administrator aswell, otherwise it's going to
-/// forward the request.
+/// is an administrator aswell and has enabled two-factor
+/// authentication, otherwise it's going to forward the request.
 #[derive(Serialize, Deserialize)]
 pub struct Administrator {
     /// The id of the administrator.
@@ -35,6 +37,10 @@ pub struct Administrator {
     /// A boolean representing if the user
     /// is an administrator or not.
     pub admin: bool,
+    /// A boolean representing if the user
+    /// has enabled two-factor authentication.
+    #[serde(default)]
+    pub two_factor: bool,
 }
 
 /// Request guard making sure that the user is logged in
@@ -54,18 +60,19 @@ impl<'a, 'r> FromRequest<'a, 'r> for Administrator {
                             Ok(user) => user,
                             Err(_) => return None,
                         };
-                        if user.admin {
+                        if user.admin && user.two_factor {
                             return Some(user);
                         }
                         return None;
                     }
                     Err(_) => match mysql_pool.get() {
                         Ok(connection) => {
-                            use club_coding::schema::{users, users_sessions};
+                            use club_coding::schema::{users, users_sessions, users_two_factor};
 
                             match users_sessions::table
                                 .inner_join(users::table.on(users::id.eq(users_sessions::user_id)))
                                 .filter(users_sessions::token.eq(cookie.value()))
+                                .filter(users_sessions::expires.gt(Utc::now().naive_utc()))
                                 .filter(users::verified.eq(true))
                                 .select((users::id, users::username, users::email))
                                 .first::<UsersAndSessions>(&*connection)
@@ -79,11 +86,21 @@ impl<'a, 'r> FromRequest<'a, 'r> for Administrator {
                                         .first::<UsersGroup>(&*connection)
                                     {
                                         Ok(_) => {
+                                            match users_two_factor::table
+                                                .filter(users_two_factor::user_id.eq(results.id))
+                                                .filter(users_two_factor::enabled.eq(true))
+                                                .first::<UsersTwoFactor>(&*connection)
+                                            {
+                                                Ok(_) => {}
+                                                Err(_) => return None,
+                                            }
+
                                             let user = Administrator {
                                                 id: results.id,
                                                 username: results.username,
                                                 email: results.email,
                                                 admin: true,
+                                                two_factor: true,
                                             };
 
                                             let json_string = match serde_json::to_string(&user) {
@@ -94,7 +111,7 @@ impl<'a, 'r> FromRequest<'a, 'r> for Administrator {
                                             match redis_conn.set_ex::<&str, String, String>(
                                                 cookie.value(),
                                                 json_string,
-                                                86400,
+                                                SESSION_CACHE_TTL,
                                             ) {
                                                 Ok(_) => {}
                                                 Err(_) => {}
@@ -1,5 +1,6 @@
 use rocket_contrib::templates::Template;
 use admin::structs::Administrator;
+use audit::{self, AuditEvent};
 use club_coding::models::{Users, UsersGroup, UsersSeriesAccess};
 use club_coding::{create_new_user_group, create_new_user_series_access};
 use database::DbConn;
@@ -12,6 +13,7 @@ use admin::series::SerieC;
 use admin::group::GroupC;
 use authentication::verify::send_verify_email;
 use rocket::Route;
+use sessions::ClientInfo;
 use structs::PostmarkToken;
 use rocket::State;
 use std::io::{Error, ErrorKind};
@@ -190,7 +192,8 @@ pub fn update_user(
     conn: DbConn,
     postmark_token: State<PostmarkToken>,
     uid: i64,
-    _user: Administrator,
+    admin: Administrator,
+    client: ClientInfo,
     data: Json<EditUser>,
 ) -> Result<(), ()> {
     use club_coding::schema::users::dsl::*;
@@ -277,6 +280,23 @@ pub fn update_user(
                 }
             }
 
+            let details = format!(
+                "username: {}, email: {}, groups: {:?}, series: {:?}, resend email: {}",
+                data.0.username,
+                data.0.email,
+                data.0.groups,
+                data.0.series,
+                data.0.force_resend_email
+            );
+            audit::record(
+                &conn,
+                AuditEvent::UserUpdatedByAdmin,
+                Some(uid),
+                Some(admin.id),
+                Some(&client),
+                Some(&details),
+            );
+
             match data.0.force_resend_email {
                 true => match resend_confirmation_email(&conn, &postmark_token.0, uid) {
                     Ok(_) => Ok(()),
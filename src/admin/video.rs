@@ -12,13 +12,21 @@ use rocket_contrib::json::Json;
 use rocket::Route;
 use rocket::response::Redirect;
 use rocket::request::Form;
+use videos::database::get_vote_totals;
+use comments::database::count_comments;
 
 #[derive(Serialize)]
 struct Video {
     uuid: String,
     name: String,
     views: u64,
-    comments: u64,
+    comments: i64,
+    likes: i64,
+    dislikes: i64,
+    /// Share of the votes that are likes,
+    /// between 0 and 1. None if the video
+    /// has no votes.
+    like_ratio: Option<f64>,
     serie: String,
     membership: bool,
     published: bool,
@@ -47,11 +55,20 @@ fn get_all_videos(connection: &DbConn) -> Vec<Video> {
                     Err(_) => "".to_string(),
                 };
 
+                let (likes, dislikes) = get_vote_totals(connection, video.id);
+                let like_ratio = match likes + dislikes {
+                    0 => None,
+                    total => Some(likes as f64 / total as f64),
+                };
+
                 ret.push(Video {
                     uuid: video.uuid,
                     name: video.title,
                     views: 0,
-                    comments: 0,
+                    comments: count_comments(connection, video.id),
+                    likes: likes,
+                    dislikes: dislikes,
+                    like_ratio: like_ratio,
                     serie: series_name,
                     membership: video.membership_only,
                     published: video.published,
@@ -0,0 +1,54 @@
+use club_coding::models::UsersAuditLog;
+use database::DbConn;
+use diesel::prelude::*;
+
+/// Number of events shown
+/// to users in settings.
+static USER_EVENTS_LIMIT: i64 = 100;
+
+/// Gets the latest events
+/// of the user, newest first.
+pub fn get_events_for_user(connection: &DbConn, uid: i64) -> Vec<UsersAuditLog> {
+    use club_coding::schema::users_audit_log::dsl::*;
+
+    match users_audit_log
+        .filter(user_id.eq(uid))
+        .order(id.desc())
+        .limit(USER_EVENTS_LIMIT)
+        .load::<UsersAuditLog>(&**connection)
+    {
+        Ok(events) => events,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the events matching the filters,
+/// newest first. Filters that are None
+/// match every event.
+pub fn get_events(
+    connection: &DbConn,
+    event_name: Option<&str>,
+    uid: Option<i64>,
+    offset: i64,
+    count: i64,
+) -> Vec<UsersAuditLog> {
+    use club_coding::schema::users_audit_log::dsl::*;
+
+    let mut query = users_audit_log.into_boxed();
+    if let Some(event_name) = event_name {
+        query = query.filter(event.eq(event_name));
+    }
+    if let Some(uid) = uid {
+        query = query.filter(user_id.eq(uid));
+    }
+
+    match query
+        .order(id.desc())
+        .offset(offset)
+        .limit(count)
+        .load::<UsersAuditLog>(&**connection)
+    {
+        Ok(events) => events,
+        Err(_) => vec![],
+    }
+}
@@ -0,0 +1,209 @@
+pub mod database;
+
+use chrono::NaiveDateTime;
+use club_coding::create_new_user_audit_log;
+use database::DbConn;
+use rocket::Route;
+use rocket::response::Redirect;
+use rocket_contrib::templates::Template;
+use sessions::ClientInfo;
+use users::User;
+
+#[cfg(test)]
+mod tests;
+
+/// The events written to the audit log.
+/// The log is append-only, events are
+/// never updated or removed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditEvent {
+    /// The user logged in.
+    Login,
+    /// Someone failed to log in
+    /// to the account.
+    FailedLogin,
+    /// The account was locked after
+    /// too many failed logins.
+    AccountLocked,
+    /// The user changed the password
+    /// from settings.
+    PasswordChanged,
+    /// The user set a new password
+    /// with a recovery link.
+    PasswordRecovered,
+    /// The user added a card.
+    CardAdded,
+    /// The user updated the card.
+    CardUpdated,
+    /// The user deleted the card.
+    CardDeleted,
+    /// An administrator edited the user.
+    UserUpdatedByAdmin,
+}
+
+/// Every event, in the order
+/// they are listed in filters.
+pub static ALL_EVENTS: [AuditEvent; 9] = [
+    AuditEvent::Login,
+    AuditEvent::FailedLogin,
+    AuditEvent::AccountLocked,
+    AuditEvent::PasswordChanged,
+    AuditEvent::PasswordRecovered,
+    AuditEvent::CardAdded,
+    AuditEvent::CardUpdated,
+    AuditEvent::CardDeleted,
+    AuditEvent::UserUpdatedByAdmin,
+];
+
+impl AuditEvent {
+    /// The name stored in the database.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AuditEvent::Login => "login",
+            AuditEvent::FailedLogin => "failed_login",
+            AuditEvent::AccountLocked => "account_locked",
+            AuditEvent::PasswordChanged => "password_changed",
+            AuditEvent::PasswordRecovered => "password_recovered",
+            AuditEvent::CardAdded => "card_added",
+            AuditEvent::CardUpdated => "card_updated",
+            AuditEvent::CardDeleted => "card_deleted",
+            AuditEvent::UserUpdatedByAdmin => "user_updated_by_admin",
+        }
+    }
+
+    /// The description shown to users.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            AuditEvent::Login => "Logged in",
+            AuditEvent::FailedLogin => "Failed login",
+            AuditEvent::AccountLocked => "Account locked",
+            AuditEvent::PasswordChanged => "Password changed",
+            AuditEvent::PasswordRecovered => "Password recovered",
+            AuditEvent::CardAdded => "Card added",
+            AuditEvent::CardUpdated => "Card updated",
+            AuditEvent::CardDeleted => "Card deleted",
+            AuditEvent::UserUpdatedByAdmin => "Account edited by an administrator",
+        }
+    }
+
+    /// Gets the event from the
+    /// name stored in the database.
+    pub fn from_name(name: &str) -> Option<AuditEvent> {
+        ALL_EVENTS
+            .iter()
+            .find(|event| event.name() == name)
+            .cloned()
+    }
+}
+
+/// Writes an event to the audit log. The actor
+/// is the administrator that did it, if it
+/// wasn't the user. Failing to write the log
+/// should never stop the action itself so
+/// errors are ignored.
+pub fn record(
+    conn: &DbConn,
+    event: AuditEvent,
+    user_id: Option<i64>,
+    actor_id: Option<i64>,
+    client: Option<&ClientInfo>,
+    details: Option<&str>,
+) {
+    match create_new_user_audit_log(
+        &**conn,
+        user_id,
+        actor_id,
+        event.name(),
+        client.map(|client| client.ip.as_str()),
+        client.map(|client| client.user_agent.as_str()),
+        details,
+    ) {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+}
+
+/// An event on the
+/// security page.
+#[derive(Serialize)]
+struct PublicEvent {
+    /// Description of the event.
+    description: String,
+    /// Extra information about the
+    /// event, for example the method
+    /// used to log in.
+    details: Option<String>,
+    /// The IP address of the client.
+    ip: Option<String>,
+    /// The user agent of the client.
+    user_agent: Option<String>,
+    /// Boolean of whether an
+    /// administrator did it.
+    by_admin: bool,
+    /// When it happened.
+    created: NaiveDateTime,
+}
+
+/// Context for rendering tera templates
+/// for the security settings endpoint.
+#[derive(Serialize)]
+struct SecurityContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// The latest events of the user.
+    events: Vec<PublicEvent>,
+}
+
+/// GET Endpoint for the page showing the
+/// security events of your account.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// Responds with the Security Template
+/// in the settings folder.
+#[get("/settings/security")]
+fn security_page(conn: DbConn, user: User) -> Template {
+    let events = database::get_events_for_user(&conn, user.id)
+        .into_iter()
+        .map(|event| PublicEvent {
+            description: match AuditEvent::from_name(&event.event) {
+                Some(audit_event) => audit_event.description().to_string(),
+                None => event.event,
+            },
+            details: event.details,
+            ip: event.ip,
+            user_agent: event.user_agent,
+            by_admin: event.actor_id.is_some(),
+            created: event.created,
+        })
+        .collect();
+    let context = SecurityContext {
+        header: "Security",
+        user: user,
+        events: events,
+    };
+    Template::render("settings/security", &context)
+}
+
+/// GET Endpoint for the page showing the
+/// security events of your account.
+/// This endpoint will kick in if the user
+/// is not logged in and will redirect the
+/// user to the index.
+#[get("/settings/security", rank = 2)]
+fn security_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![security_page, security_page_nouser]
+}
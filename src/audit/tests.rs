@@ -0,0 +1,24 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+    use audit::{AuditEvent, ALL_EVENTS};
+
+    #[test]
+    fn security_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/security").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
+
+    #[test]
+    fn event_names_round_trip() {
+        for event in ALL_EVENTS.iter() {
+            assert_eq!(AuditEvent::from_name(event.name()), Some(*event));
+        }
+        assert_eq!(AuditEvent::from_name("unknown"), None);
+    }
+}
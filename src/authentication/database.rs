@@ -1,34 +1,71 @@
+use chrono::{Duration, Utc};
 use database::DbConn;
 use club_coding::models::{Users, UsersRecoverEmail, UsersVerifyEmail};
 use std::io::{Error, ErrorKind};
 use diesel::prelude::*;
 
-/// Gets the password hash of a user from
-/// the username of the user.
-pub fn get_password_hash_from_username(connection: &DbConn, name: &str) -> Result<String, Error> {
+/// Gets a user from either the username
+/// or the email of the user. Logins that
+/// contain an @ are first matched as emails
+/// without regard to case, then as usernames.
+pub fn get_user_by_login(connection: &DbConn, login: &str) -> Option<Users> {
     use club_coding::schema::users::dsl::*;
 
+    if login.contains('@') {
+        if let Ok(user) = users
+            .filter(email.eq(login.trim().to_lowercase()))
+            .first::<Users>(&**connection)
+        {
+            return Some(user);
+        }
+    }
     match users
-        .filter(username.eq(name))
+        .filter(username.eq(login))
         .first::<Users>(&**connection)
     {
-        Ok(results) => Ok(results.password),
-        Err(_) => Err(Error::new(ErrorKind::Other, "No user found")),
+        Ok(user) => Some(user),
+        Err(_) => None,
     }
 }
 
-/// Gets the user id of a user from
-/// the username of the user.
-pub fn get_user_id_from_username(connection: &DbConn, name: &str) -> Result<i64, Error> {
+/// Gets the user from the id of the user.
+pub fn get_user(connection: &DbConn, uid: i64) -> Option<Users> {
+    use club_coding::schema::users::dsl::*;
+
+    match users.find(uid).first::<Users>(&**connection) {
+        Ok(user) => Some(user),
+        Err(_) => None,
+    }
+}
+
+/// Updates the password hash of the user.
+pub fn update_password_hash(
+    connection: &DbConn,
+    uid: i64,
+    hashed_password: &str,
+) -> Result<(), Error> {
+    use club_coding::schema::users::dsl::*;
+
+    match diesel::update(users.find(uid))
+        .set(password.eq(hashed_password))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update password.")),
+    }
+}
+
+/// Checks if an email is already
+/// used by another account.
+pub fn email_exists(connection: &DbConn, name: &str) -> bool {
     use club_coding::schema::users::dsl::*;
 
     match users
-        .filter(username.eq(name))
-        .filter(verified.eq(true))
+        .filter(email.eq(name.trim().to_lowercase()))
         .first::<Users>(&**connection)
     {
-        Ok(result) => Ok(result.id),
-        Err(_) => Err(Error::new(ErrorKind::Other, "No user found")),
+        Ok(_) => true,
+        Err(_) => false,
     }
 }
 
@@ -38,7 +75,7 @@ pub fn get_user_id_from_email(connection: &DbConn, name: &str) -> Option<i64> {
     use club_coding::schema::users::dsl::*;
 
     match users
-        .filter(email.eq(name))
+        .filter(email.eq(name.trim().to_lowercase()))
         .filter(verified.eq(true))
         .first::<Users>(&**connection)
     {
@@ -48,7 +85,8 @@ pub fn get_user_id_from_email(connection: &DbConn, name: &str) -> Option<i64> {
 }
 
 /// Gets the UsersVerifyEmail struct
-/// from the verification token
+/// from the verification token. Tokens
+/// for changing email are not included.
 pub fn get_verify_email_by_token(
     connection: &DbConn,
     uuid_token: &str,
@@ -57,6 +95,7 @@ pub fn get_verify_email_by_token(
 
     match users_verify_email
         .filter(token.eq(uuid_token))
+        .filter(new_email.is_null())
         .first::<UsersVerifyEmail>(&**connection)
     {
         Ok(result) => Some(result),
@@ -138,3 +177,80 @@ pub fn invalidate_token_and_update_password(
         Err(_) => return Err(Error::new(ErrorKind::Other, "Could not verify user.")),
     }
 }
+
+/// Gets the user id of an unverified
+/// user from the email of the user.
+pub fn get_unverified_user_id_from_email(connection: &DbConn, name: &str) -> Option<i64> {
+    use club_coding::schema::users::dsl::*;
+
+    match users
+        .filter(email.eq(name.trim().to_lowercase()))
+        .filter(verified.eq(false))
+        .first::<Users>(&**connection)
+    {
+        Ok(result) => Some(result.id),
+        Err(_) => None,
+    }
+}
+
+/// Invalidates every unused verification
+/// token of the user. Used when a new
+/// verification email is sent.
+pub fn invalidate_verify_tokens(connection: &DbConn, uid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_verify_email::dsl::*;
+
+    match diesel::update(
+        users_verify_email
+            .filter(user_id.eq(uid))
+            .filter(used.eq(false))
+            .filter(new_email.is_null()),
+    )
+        .set(used.eq(true))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not update verification tokens.",
+        )),
+    }
+}
+
+/// Deletes the verify email and recover
+/// account tokens that are older than
+/// the given number of hours.
+pub fn delete_expired_tokens(
+    connection: &MysqlConnection,
+    verify_hours: i64,
+    recover_hours: i64,
+) -> Result<(), Error> {
+    use club_coding::schema::{users_recover_email, users_verify_email};
+
+    let now = Utc::now().naive_utc();
+
+    match diesel::delete(
+        users_verify_email::table
+            .filter(users_verify_email::created.lt(now - Duration::hours(verify_hours))),
+    ).execute(connection)
+    {
+        Ok(_) => {}
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not delete verification tokens.",
+            ))
+        }
+    }
+
+    match diesel::delete(
+        users_recover_email::table
+            .filter(users_recover_email::created.lt(now - Duration::hours(recover_hours))),
+    ).execute(connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not delete recovery tokens.",
+        )),
+    }
+}
@@ -1,20 +1,28 @@
+use audit::{self, AuditEvent};
 use authentication;
-use bcrypt::verify;
-use club_coding::create_new_user_session;
+use authentication::rate_limit;
+use authentication::verify::VerifyEmail;
 use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
-use database::DbConn;
+use database::{DbConn, RedisConnection};
+use email::{EmailBody, PostmarkClient};
+use oauth::client::OAuthProviders;
+use passwords::{self, BcryptCost};
 use rocket::http::{Cookie, Cookies};
 use rocket::request::{FlashMessage, Form};
 use rocket::response::{Flash, Redirect};
 use rocket::{Route, State};
 use rocket_contrib::templates::Template;
-use time::Duration;
+use sessions::{self, ClientInfo};
+use std::io::{Error, ErrorKind};
+use structs::PostmarkToken;
+use two_factor;
 use users::User as UserStruct;
 
 /// Struct for parsing login forms
 #[derive(FromForm)]
 struct User {
-    /// The username for the user
+    /// The username or the email
+    /// of the user
     username: String,
     /// The password of the user
     password: String,
@@ -51,6 +59,27 @@ pub struct LoginContext<'a> {
     pub flash_msg: String,
 }
 
+/// Context for the login page. Same as
+/// the LoginContext with the providers
+/// that can be used to sign in.
+#[derive(Serialize)]
+struct LoginPageContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// CSRF Token. Used as a hidden
+    /// input in the form.
+    csrf: String,
+    /// Flash name if the request is redirected
+    /// with one.
+    flash_name: String,
+    /// Flash message if the request is redirected
+    /// with one.
+    flash_msg: String,
+    /// Names of the OAuth2 providers.
+    providers: Vec<String>,
+}
+
 /// GET Endpoint for the login page.
 /// This endpoint will kick in
 /// if the user is not logged in.
@@ -59,23 +88,146 @@ pub struct LoginContext<'a> {
 /// Responds with the Login Template
 /// in the authentication folder.
 #[get("/login", rank = 2)]
-fn login_page(token: CsrfToken, flash: Option<FlashMessage>) -> Template {
+fn login_page(
+    token: CsrfToken,
+    providers: State<OAuthProviders>,
+    flash: Option<FlashMessage>,
+) -> Template {
     let (name, msg) = match flash {
         Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
         None => ("".to_string(), "".to_string()),
     };
-    let context = LoginContext {
+    let context = LoginPageContext {
         header: "Login Page!",
         csrf: token.value(),
         flash_name: name,
         flash_msg: msg,
+        providers: providers.names(),
     };
     Template::render("authentication/login", &context)
 }
 
+/// Function to send an email with a link
+/// to unlock the account when it has been
+/// locked after too many failed logins.
+fn send_unlock_mail(postmark_token: &str, token: &str, email: &str) -> Result<(), Error> {
+    let tera = compile_templates!("templates/emails/**/*");
+    let verify = VerifyEmail { token: token };
+    match tera.render("unlock_account.html.tera", &verify) {
+        Ok(html_body) => {
+            let body = EmailBody {
+                from: "axel@clubcoding.com".to_string(),
+                to: email.to_string(),
+                subject: Some("Your account has been locked".to_string()),
+                html_body: Some(html_body),
+                cc: None,
+                bcc: None,
+                tag: None,
+                text_body: None,
+                reply_to: None,
+                headers: None,
+                track_opens: None,
+                track_links: None,
+            };
+            let postmark_client = PostmarkClient::new(postmark_token);
+            postmark_client.send_email(&body)?;
+            Ok(())
+        }
+        Err(_) => Err(Error::new(ErrorKind::Other, "couldn't render template")),
+    }
+}
+
+/// Counts a failed login for the username.
+/// Locks the username after too many failed
+/// logins in a row and emails the user a
+/// link to unlock it if the account exists.
+/// Both are written to the audit log with
+/// the details. Used for both the password
+/// and the two-factor code.
+/// Returns true if the username got locked.
+pub fn count_failed_login(
+    conn: &DbConn,
+    redis_conn: &RedisConnection,
+    postmark_token: &str,
+    client: &ClientInfo,
+    username: &str,
+    account: Option<(i64, &str)>,
+    details: &str,
+) -> bool {
+    let user_id = account.map(|(user_id, _)| user_id);
+    audit::record(
+        conn,
+        AuditEvent::FailedLogin,
+        user_id,
+        None,
+        Some(client),
+        Some(details),
+    );
+
+    let failures = rate_limit::increment(
+        redis_conn,
+        &rate_limit::login_user_key(username),
+        rate_limit::LOGIN_WINDOW,
+    );
+    if failures < rate_limit::MAX_FAILED_LOGINS {
+        return false;
+    }
+    if let Some(token) = rate_limit::lock(redis_conn, username) {
+        audit::record(
+            conn,
+            AuditEvent::AccountLocked,
+            user_id,
+            None,
+            Some(client),
+            Some(details),
+        );
+        if let Some((_, email)) = account {
+            match send_unlock_mail(postmark_token, &token, email) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+        }
+    }
+    true
+}
+
+/// Counts a failed login for the username
+/// and responds with the message to show.
+/// The same message is returned whether the
+/// account exists or not so it doesn't reveal
+/// which usernames are taken.
+fn failed_login(
+    conn: &DbConn,
+    redis_conn: &RedisConnection,
+    postmark_token: &str,
+    client: &ClientInfo,
+    username: &str,
+    account: Option<(i64, &str)>,
+) -> Flash<Redirect> {
+    let details = format!("username: {}", username);
+    if count_failed_login(
+        conn,
+        redis_conn,
+        postmark_token,
+        client,
+        username,
+        account,
+        &details,
+    ) {
+        return Flash::error(
+            Redirect::to("/login"),
+            "Too many failed attempts. Please try again later.",
+        );
+    }
+    Flash::error(Redirect::to("/login"), "Invalid credentials.")
+}
+
 /// POST Endpoint for the page to login.
 /// It requires all of the parameters in the
 /// User struct to be submitted as a form.
+/// Logins are rate limited by IP address and
+/// the username is locked after too many
+/// failed attempts.
 /// If everything is successful, it will set
 /// a Session Token and redirect the user to
 /// the index. Otherwise it will redirect
@@ -85,7 +237,11 @@ fn login_page(token: CsrfToken, flash: Option<FlashMessage>) -> Template {
 fn login(
     conn: DbConn,
     csrf_secret_key: State<CSRFSecretToken>,
+    postmark_token: State<PostmarkToken>,
+    bcrypt_cost: State<BcryptCost>,
+    redis_conn: RedisConnection,
     csrf_cookie: CsrfCookie,
+    client: ClientInfo,
     mut cookies: Cookies,
     user: Form<User>,
 ) -> Result<Flash<Redirect>, Flash<Redirect>> {
@@ -94,35 +250,119 @@ fn login(
         return Err(Flash::error(Redirect::to("/login"), "CSRF Failed."));
     }
 
-    let password_hash: String = match authentication::database::get_password_hash_from_username(
-        &conn,
-        &input_data.username,
-    ) {
-        Ok(password_hash) => password_hash,
-        Err(_) => return Err(Flash::error(Redirect::to("/login"), "No user found")),
+    let attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::login_ip_key(&client.ip),
+        rate_limit::LOGIN_WINDOW,
+    );
+    if attempts > rate_limit::LOGIN_ATTEMPTS_PER_IP {
+        return Err(Flash::error(
+            Redirect::to("/login"),
+            "Too many login attempts. Please try again later.",
+        ));
+    }
+
+    let user = authentication::database::get_user_by_login(&conn, &input_data.username);
+
+    // Failed logins are counted per account so using
+    // the email instead of the username gives no more
+    // attempts. Unknown logins are counted as typed.
+    let lockout_name = match user {
+        Some(ref user) => user.username.clone(),
+        None => input_data.username.clone(),
     };
 
-    let passwords_match: bool = match verify(&input_data.password, &password_hash) {
-        Ok(passwords_match) => passwords_match,
-        Err(_) => return Err(Flash::error(Redirect::to("/login"), "An error occurred")),
+    if rate_limit::is_locked(&redis_conn, &lockout_name) {
+        return Err(Flash::error(
+            Redirect::to("/login"),
+            "Too many failed attempts. Please try again later.",
+        ));
+    }
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            // Takes as long as a wrong password so the
+            // time doesn't reveal if the account exists.
+            let _ = passwords::verify_password(
+                &input_data.password,
+                &passwords::dummy_hash(bcrypt_cost.0),
+            );
+            return Err(failed_login(
+                &conn,
+                &redis_conn,
+                &postmark_token.0,
+                &client,
+                &lockout_name,
+                None,
+            ))
+        }
     };
 
+    let passwords_match: bool =
+        match passwords::verify_password(&input_data.password, &user.password) {
+            Ok(passwords_match) => passwords_match,
+            Err(_) => return Err(Flash::error(Redirect::to("/login"), "An error occurred")),
+        };
+
     if !passwords_match {
-        return Err(Flash::error(Redirect::to("/login"), "Password incorrect"));
+        return Err(failed_login(
+            &conn,
+            &redis_conn,
+            &postmark_token.0,
+            &client,
+            &lockout_name,
+            Some((user.id, &user.email)),
+        ));
+    }
+
+    rate_limit::clear(&redis_conn, &rate_limit::login_user_key(&lockout_name));
+
+    // Hashes made before the cost was raised are upgraded
+    // now that we know the password. Logging in should not
+    // fail if it doesn't work, it is tried again next time.
+    if passwords::needs_rehash(&user.password, bcrypt_cost.0) {
+        let rehashed = passwords::hash_password(&input_data.password, bcrypt_cost.0);
+        if let Ok(hashed_password) = rehashed {
+            match authentication::database::update_password_hash(&conn, user.id, &hashed_password)
+            {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+        }
     }
 
-    let user_id =
-        match authentication::database::get_user_id_from_username(&conn, &input_data.username) {
-            Ok(user_id) => user_id,
-            Err(_) => return Err(Flash::error(Redirect::to("/login"), "User not verified")),
+    if !user.verified {
+        return Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "Your email has not been verified yet. Check your inbox for the verification email or send a new one below.",
+        ));
+    }
+    let user_id = user.id;
+
+    if two_factor::database::is_enabled(&conn, user_id) {
+        return match two_factor::begin_login(&redis_conn, &mut cookies, user_id) {
+            Ok(_) => Ok(Flash::success(
+                Redirect::to("/login/two_factor"),
+                "Enter the code from your authenticator app.",
+            )),
+            Err(_) => Err(Flash::error(
+                Redirect::to("/login"),
+                "An error occured, please try again later.",
+            )),
         };
+    }
 
-    let session_token = authentication::generate_token(64);
-    match create_new_user_session(&*conn, user_id, &session_token) {
+    match sessions::start_session(&conn, &client, &mut cookies, user_id) {
         Ok(_) => {
-            let mut c = Cookie::new("session_token", session_token);
-            c.set_max_age(Duration::hours(24));
-            cookies.add_private(c);
+            audit::record(
+                &conn,
+                AuditEvent::Login,
+                Some(user_id),
+                None,
+                Some(&client),
+                Some("password"),
+            );
             Ok(Flash::success(Redirect::to("/"), "You're now logged in."))
         }
         Err(_) => Err(Flash::error(
@@ -132,16 +372,47 @@ fn login(
     }
 }
 
+/// GET Endpoint to logout. Deletes the
+/// session from the database and from
+/// the Redis cache, removes the cookie
+/// and redirects the user to the index.
 #[get("/logout")]
-fn logout(mut cookies: Cookies) -> Redirect {
+fn logout(conn: DbConn, redis_conn: RedisConnection, mut cookies: Cookies) -> Redirect {
+    if let Some(cookie) = cookies.get_private("session_token") {
+        match sessions::database::delete_session(&conn, &redis_conn, cookie.value()) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
     cookies.remove_private(Cookie::named("session_token"));
     Redirect::to("/")
 }
 
+/// GET Endpoint to unlock an account
+/// that has been locked after too many
+/// failed logins. The token is sent to
+/// the user by email when the account
+/// is locked. Redirects to the login page
+/// with an appropriate message.
+#[get("/unlock/<token>")]
+fn unlock(redis_conn: RedisConnection, token: String) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if rate_limit::unlock(&redis_conn, &token) {
+        Ok(Flash::success(
+            Redirect::to("/login"),
+            "Your account has been unlocked.",
+        ))
+    } else {
+        Err(Flash::error(
+            Redirect::to("/login"),
+            "The link is invalid or has expired.",
+        ))
+    }
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
 /// instead we can keep all of the changes in here.
 pub fn endpoints() -> Vec<Route> {
-    routes![login_page, login_page_loggedin, login, logout]
+    routes![login_page, login_page_loggedin, login, logout, unlock]
 }
@@ -0,0 +1,361 @@
+use audit::{self, AuditEvent};
+use authentication;
+use authentication::rate_limit;
+use authentication::verify::VerifyEmail;
+use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
+use database::{DbConn, RedisConnection};
+use email::{EmailBody, PostmarkClient};
+use redis::Commands;
+use rocket::http::Cookies;
+use rocket::request::{FlashMessage, Form};
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_contrib::templates::Template;
+use sessions::{self, ClientInfo};
+use std::io::{Error, ErrorKind};
+use structs::{EmailRegex, PostmarkToken};
+use two_factor;
+use users::User as UserStruct;
+
+/// Number of seconds a sign-in
+/// link can be used for.
+static MAGIC_LINK_LIFETIME: usize = 900;
+
+/// Number of sign-in links that can be
+/// requested from one IP address per window.
+static MAGIC_LINK_ATTEMPTS_PER_IP: i64 = 5;
+
+/// Number of sign-in links that can be
+/// requested for one email per window.
+static MAGIC_LINK_ATTEMPTS_PER_EMAIL: i64 = 3;
+
+/// Number of seconds the sign-in link
+/// requests are counted for.
+static MAGIC_LINK_WINDOW: usize = 3600;
+
+/// Redis key storing the user id
+/// of the sign-in link token.
+fn magic_link_key(token: &str) -> String {
+    format!("magic_link:{}", token)
+}
+
+/// Function to send an email with
+/// a link to sign in to the user.
+fn send_magic_link_mail(postmark_token: &str, token: &str, email: &str) -> Result<(), Error> {
+    let tera = compile_templates!("templates/emails/**/*");
+    let verify = VerifyEmail { token: token };
+    match tera.render("magic_link.html.tera", &verify) {
+        Ok(html_body) => {
+            let body = EmailBody {
+                from: "axel@clubcoding.com".to_string(),
+                to: email.to_string(),
+                subject: Some("Your sign-in link".to_string()),
+                html_body: Some(html_body),
+                cc: None,
+                bcc: None,
+                tag: None,
+                text_body: None,
+                reply_to: None,
+                headers: None,
+                track_opens: None,
+                track_links: None,
+            };
+            let postmark_client = PostmarkClient::new(postmark_token);
+            postmark_client.send_email(&body)?;
+            Ok(())
+        }
+        Err(_) => Err(Error::new(ErrorKind::Other, "couldn't render template")),
+    }
+}
+
+/// Generates a random token.
+/// Stores the token in Redis for
+/// MAGIC_LINK_LIFETIME seconds.
+/// Sends a sign-in email to the
+/// user with that token.
+fn creates_and_sends_magic_link(
+    redis_conn: &RedisConnection,
+    postmark_token: &str,
+    user_id: i64,
+    email: &str,
+) -> Result<(), Error> {
+    let token = authentication::generate_token(30);
+    match redis_conn.set_ex::<String, i64, String>(
+        magic_link_key(&token),
+        user_id,
+        MAGIC_LINK_LIFETIME,
+    ) {
+        Ok(_) => send_magic_link_mail(postmark_token, &token, email),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not store sign-in link.")),
+    }
+}
+
+/// Checks if the token exists without
+/// removing it, so the link can be
+/// confirmed before it's used.
+fn magic_link_exists(redis_conn: &RedisConnection, token: &str) -> bool {
+    match redis_conn.exists::<String, bool>(magic_link_key(token)) {
+        Ok(exists) => exists,
+        Err(_) => false,
+    }
+}
+
+/// Gets the user id of the token and removes
+/// the token so the link can only be used once.
+/// Returns None if the token is invalid
+/// or has expired.
+fn take_magic_link(redis_conn: &RedisConnection, token: &str) -> Option<i64> {
+    let key = magic_link_key(token);
+    let user_id = match redis_conn.get::<&str, Option<i64>>(&key) {
+        Ok(Some(user_id)) => user_id,
+        _ => return None,
+    };
+    match redis_conn.del::<&str, i64>(&key) {
+        // Only the request that removed the token may use it.
+        Ok(1) => Some(user_id),
+        _ => None,
+    }
+}
+
+/// POST Endpoint to request a sign-in link.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// logged in it redirect the
+/// user to the index, otherwise
+/// it forwards the request.
+#[post("/login/link")]
+fn send_magic_link_loggedin(_userid: UserStruct) -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for parsing
+/// sign-in link forms
+#[derive(FromForm)]
+struct MagicLink {
+    /// The email of the user
+    email: String,
+    /// CSRF Token from the form
+    csrf: String,
+}
+
+/// POST Endpoint to request a sign-in link.
+/// This endpoint will kick in if the user
+/// is not logged in. It requires all of the
+/// parameters in the MagicLink struct to be
+/// submitted as a form.
+/// Checks if the CSRF matches and the email
+/// is valid. Requests are rate limited by IP
+/// address and by email. The same message is
+/// shown whether the email belongs to an
+/// account or not.
+#[post("/login/link", data = "<user>", rank = 2)]
+fn send_magic_link(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    client: ClientInfo,
+    email_regex: State<EmailRegex>,
+    postmark_token: State<PostmarkToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    user: Form<MagicLink>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let input: MagicLink = user.into_inner();
+    let email = input.email.trim().to_lowercase();
+    if !email_regex.0.is_match(&email) {
+        return Err(Flash::error(Redirect::to("/login"), "Email is not valid."));
+    }
+    if !csrf_matches(csrf_secret_key.0, &input.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(Redirect::to("/login"), "CSRF Failed."));
+    }
+
+    let ip_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::magic_link_ip_key(&client.ip),
+        MAGIC_LINK_WINDOW,
+    );
+    let email_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::magic_link_email_key(&email),
+        MAGIC_LINK_WINDOW,
+    );
+    if ip_attempts > MAGIC_LINK_ATTEMPTS_PER_IP || email_attempts > MAGIC_LINK_ATTEMPTS_PER_EMAIL {
+        return Err(Flash::error(
+            Redirect::to("/login"),
+            "Too many attempts. Please try again later.",
+        ));
+    }
+
+    let user_id: i64 = match authentication::database::get_user_id_from_email(&conn, &email) {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(Flash::success(
+                Redirect::to("/login"),
+                "Email sent. Please check your inbox for the sign-in link.",
+            ))
+        }
+    };
+
+    match creates_and_sends_magic_link(&redis_conn, &postmark_token.0, user_id, &email) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/login"),
+            "Email sent. Please check your inbox for the sign-in link.",
+        )),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// GET Endpoint for the sign-in link.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// logged in it redirect the
+/// user to the index, otherwise
+/// it forwards the request.
+#[get("/login/link/<_token>")]
+fn magic_link_login_loggedin(_token: String, _userid: UserStruct) -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint for the sign-in link.
+/// This endpoint will kick in if the user
+/// is not logged in. Checks if the token
+/// is valid without using it and responds
+/// with a page to confirm the sign-in, so
+/// that links opened by mail scanners
+/// don't use up the token.
+#[get("/login/link/<token>", rank = 2)]
+fn magic_link_page(
+    redis_conn: RedisConnection,
+    csrf_token: CsrfToken,
+    flash: Option<FlashMessage>,
+    token: String,
+) -> Result<Template, Flash<Redirect>> {
+    if !magic_link_exists(&redis_conn, &token) {
+        return Err(Flash::error(
+            Redirect::to("/login"),
+            "The link is invalid or has expired.",
+        ));
+    }
+
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
+
+    let context = authentication::login::LoginContext {
+        header: "Sign in",
+        csrf: csrf_token.value(),
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Ok(Template::render("authentication/magic_link", &context))
+}
+
+/// POST Endpoint for the sign-in link.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// logged in it redirect the
+/// user to the index, otherwise
+/// it forwards the request.
+#[post("/login/link/<_token>")]
+fn magic_link_login_loggedin_post(_token: String, _userid: UserStruct) -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for parsing
+/// sign-in confirmation forms
+#[derive(FromForm)]
+struct ConfirmMagicLink {
+    /// CSRF Token from the form
+    csrf: String,
+}
+
+/// POST Endpoint for the sign-in link.
+/// This endpoint will kick in if the user
+/// is not logged in. Checks if the CSRF
+/// matches and the token is valid and
+/// removes it. Starts a session the same
+/// way as logging in with a password,
+/// asking for the two-factor code first if
+/// the user has it enabled. Redirects with
+/// an appropriate message.
+#[post("/login/link/<token>", data = "<confirm>", rank = 2)]
+fn magic_link_login(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    client: ClientInfo,
+    mut cookies: Cookies,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    token: String,
+    confirm: Form<ConfirmMagicLink>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let input: ConfirmMagicLink = confirm.into_inner();
+    if !csrf_matches(csrf_secret_key.0, &input.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to(format!("/login/link/{}", token)),
+            "CSRF Failed.",
+        ));
+    }
+
+    let user_id = match take_magic_link(&redis_conn, &token) {
+        Some(user_id) => user_id,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/login"),
+                "The link is invalid or has expired.",
+            ))
+        }
+    };
+
+    if two_factor::database::is_enabled(&conn, user_id) {
+        return match two_factor::begin_login(&redis_conn, &mut cookies, user_id) {
+            Ok(_) => Ok(Flash::success(
+                Redirect::to("/login/two_factor"),
+                "Enter the code from your authenticator app.",
+            )),
+            Err(_) => Err(Flash::error(
+                Redirect::to("/login"),
+                "An error occured, please try again later.",
+            )),
+        };
+    }
+
+    match sessions::start_session(&conn, &client, &mut cookies, user_id) {
+        Ok(_) => {
+            audit::record(
+                &conn,
+                AuditEvent::Login,
+                Some(user_id),
+                None,
+                Some(&client),
+                Some("sign-in link"),
+            );
+            Ok(Flash::success(Redirect::to("/"), "You're now logged in."))
+        }
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![
+        send_magic_link_loggedin,
+        send_magic_link,
+        magic_link_login_loggedin,
+        magic_link_page,
+        magic_link_login_loggedin_post,
+        magic_link_login
+    ]
+}
@@ -1,8 +1,16 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use database::MySqlPool;
+use rocket::fairing::AdHoc;
 use rocket::Route;
+use std::thread;
+use std::time::Duration as StdDuration;
+use structs::TokenLifetime;
 
-mod database;
+pub mod database;
 mod register;
-mod login;
+pub mod login;
+mod magic_link;
+mod rate_limit;
 mod recover;
 pub mod verify;
 
@@ -16,6 +24,51 @@ pub fn generate_token(length: u8) -> String {
     return strings.join("");
 }
 
+/// Number of seconds between each
+/// removal of expired tokens.
+static TOKEN_CLEANUP_INTERVAL: u64 = 3600;
+
+/// Number of hours an expired token is
+/// kept before it's removed, so that the
+/// link can still show that it has expired.
+static TOKEN_GRACE_HOURS: i64 = 7 * 24;
+
+/// Checks if a token created at the
+/// given time is older than its lifetime.
+pub fn token_expired(created: NaiveDateTime, lifetime_hours: i64) -> bool {
+    created + Duration::hours(lifetime_hours) < Utc::now().naive_utc()
+}
+
+/// Returns a AdHoc Fairing that starts a job
+/// when the server launches. The job removes
+/// expired verify email and recover account
+/// tokens once every hour, after they have
+/// been expired for the grace period.
+pub fn token_cleanup_fairing() -> AdHoc {
+    AdHoc::on_launch("TokenCleanup", |rocket| {
+        let pool = match rocket.state::<MySqlPool>() {
+            Some(pool) => pool.clone(),
+            None => return,
+        };
+        let (verify_hours, recover_hours) = match rocket.state::<TokenLifetime>() {
+            Some(lifetime) => (
+                lifetime.verify_hours + TOKEN_GRACE_HOURS,
+                lifetime.recover_hours + TOKEN_GRACE_HOURS,
+            ),
+            None => return,
+        };
+        thread::spawn(move || loop {
+            if let Ok(connection) = pool.get() {
+                match database::delete_expired_tokens(&*connection, verify_hours, recover_hours) {
+                    Ok(_) => {}
+                    Err(_) => {}
+                }
+            }
+            thread::sleep(StdDuration::from_secs(TOKEN_CLEANUP_INTERVAL));
+        });
+    })
+}
+
 /// Assembles all of the endpoints of the authentication
 /// endpoints. The upside of assembling all of the endpoints
 /// here is that we don't have to update the main function
@@ -29,6 +82,9 @@ pub fn endpoints() -> Vec<Route> {
     let mut login = login::endpoints();
     total.append(&mut login);
 
+    let mut magic_link = magic_link::endpoints();
+    total.append(&mut magic_link);
+
     let mut recover = recover::endpoints();
     total.append(&mut recover);
 
@@ -0,0 +1,183 @@
+use authentication;
+use database::RedisConnection;
+use redis::Commands;
+
+/// Number of login attempts allowed
+/// from one IP address per window.
+pub static LOGIN_ATTEMPTS_PER_IP: i64 = 20;
+
+/// Number of seconds the login
+/// attempts are counted for.
+pub static LOGIN_WINDOW: usize = 900;
+
+/// Number of failed logins in a row
+/// before the account is locked.
+pub static MAX_FAILED_LOGINS: i64 = 5;
+
+/// Number of seconds an account
+/// stays locked.
+pub static LOCKOUT_SECONDS: usize = 900;
+
+/// Number of recovery emails that can be
+/// requested from one IP address per window.
+pub static RECOVER_ATTEMPTS_PER_IP: i64 = 5;
+
+/// Number of recovery emails that can be
+/// requested for one email per window.
+pub static RECOVER_ATTEMPTS_PER_EMAIL: i64 = 3;
+
+/// Number of seconds the recovery
+/// requests are counted for.
+pub static RECOVER_WINDOW: usize = 3600;
+
+/// Number of email changes a user
+/// can request per window.
+pub static EMAIL_CHANGE_ATTEMPTS_PER_USER: i64 = 5;
+
+/// Number of two-factor codes that can
+/// be checked for one user per window.
+pub static TWO_FACTOR_ATTEMPTS_PER_USER: i64 = 10;
+
+/// Number of seconds the two-factor
+/// codes are counted for.
+pub static TWO_FACTOR_WINDOW: usize = 900;
+
+/// Redis key counting the login
+/// attempts from an IP address.
+pub fn login_ip_key(ip: &str) -> String {
+    format!("rate_limit:login:ip:{}", ip)
+}
+
+/// Redis key counting the failed logins
+/// of a username. Usernames are lowercased
+/// so changing the case does not give
+/// more attempts.
+pub fn login_user_key(username: &str) -> String {
+    format!("rate_limit:login:user:{}", username.trim().to_lowercase())
+}
+
+/// Redis key counting the recovery
+/// requests from an IP address.
+pub fn recover_ip_key(ip: &str) -> String {
+    format!("rate_limit:recover:ip:{}", ip)
+}
+
+/// Redis key counting the recovery
+/// requests for an email.
+pub fn recover_email_key(email: &str) -> String {
+    format!("rate_limit:recover:email:{}", email.trim().to_lowercase())
+}
+
+/// Redis key counting the verification
+/// emails requested from an IP address.
+pub fn verify_ip_key(ip: &str) -> String {
+    format!("rate_limit:verify:ip:{}", ip)
+}
+
+/// Redis key counting the verification
+/// emails requested for an email.
+pub fn verify_email_key(email: &str) -> String {
+    format!("rate_limit:verify:email:{}", email.trim().to_lowercase())
+}
+
+/// Redis key counting the sign-in links
+/// requested from an IP address.
+pub fn magic_link_ip_key(ip: &str) -> String {
+    format!("rate_limit:magic_link:ip:{}", ip)
+}
+
+/// Redis key counting the sign-in links
+/// requested for an email.
+pub fn magic_link_email_key(email: &str) -> String {
+    format!("rate_limit:magic_link:email:{}", email.trim().to_lowercase())
+}
+
+/// Redis key counting the email
+/// changes requested by a user.
+pub fn email_change_user_key(user_id: i64) -> String {
+    format!("rate_limit:email_change:user:{}", user_id)
+}
+
+/// Redis key counting the two-factor
+/// codes checked for a user.
+pub fn two_factor_user_key(user_id: i64) -> String {
+    format!("rate_limit:two_factor:user:{}", user_id)
+}
+
+/// Redis key that is set while
+/// the username is locked.
+fn lockout_key(username: &str) -> String {
+    format!("lockout:{}", username.trim().to_lowercase())
+}
+
+/// Increments the counter of the key and
+/// returns the new value. The counter is
+/// removed after window seconds from the
+/// first increment.
+/// If Redis is unavailable it returns 0
+/// so users can still login.
+pub fn increment(redis_conn: &RedisConnection, key: &str, window: usize) -> i64 {
+    let value = match redis_conn.incr::<&str, i64, i64>(key, 1) {
+        Ok(value) => value,
+        Err(_) => return 0,
+    };
+    if value == 1 {
+        match redis_conn.expire::<&str, i64>(key, window) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
+    value
+}
+
+/// Removes the counter.
+pub fn clear(redis_conn: &RedisConnection, key: &str) {
+    match redis_conn.del::<&str, i64>(key) {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+}
+
+/// Checks if the username is locked.
+pub fn is_locked(redis_conn: &RedisConnection, username: &str) -> bool {
+    match redis_conn.exists::<&str, bool>(&lockout_key(username)) {
+        Ok(locked) => locked,
+        Err(_) => false,
+    }
+}
+
+/// Locks the username for LOCKOUT_SECONDS.
+/// Returns a token that unlocks the username
+/// again, or None if it could not be stored.
+pub fn lock(redis_conn: &RedisConnection, username: &str) -> Option<String> {
+    let username = username.trim().to_lowercase();
+    let token = authentication::generate_token(30);
+    match redis_conn.set_ex::<String, &str, String>(lockout_key(&username), "1", LOCKOUT_SECONDS)
+    {
+        Ok(_) => {}
+        Err(_) => return None,
+    }
+    match redis_conn.set_ex::<String, &str, String>(
+        format!("unlock:{}", token),
+        &username,
+        LOCKOUT_SECONDS,
+    ) {
+        Ok(_) => Some(token),
+        Err(_) => None,
+    }
+}
+
+/// Unlocks the username belonging to the
+/// unlock token and resets its failed logins.
+/// Returns true if the token was valid.
+pub fn unlock(redis_conn: &RedisConnection, token: &str) -> bool {
+    let key = format!("unlock:{}", token);
+    let username = match redis_conn.get::<&str, Option<String>>(&key) {
+        Ok(Some(username)) => username,
+        _ => return false,
+    };
+    clear(redis_conn, &key);
+    clear(redis_conn, &lockout_key(&username));
+    clear(redis_conn, &login_user_key(&username));
+    true
+}
@@ -1,17 +1,21 @@
+use audit::{self, AuditEvent};
 use authentication;
+use authentication::rate_limit;
 use authentication::verify::VerifyEmail;
-use bcrypt::{hash, DEFAULT_COST};
 use club_coding::create_new_users_recover_email;
 use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
-use database::DbConn;
+use database::{DbConn, RedisConnection};
 use email::{EmailBody, PostmarkClient};
+use passwords::{self, BcryptCost, PasswordPolicy};
 use rocket::request::{FlashMessage, Form};
 use rocket::response::{Flash, Redirect};
 use rocket::{Route, State};
 use rocket_contrib::templates::Template;
+use sessions::{self, ClientInfo};
 use std::io::{Error, ErrorKind};
 use structs::EmailRegex;
 use structs::PostmarkToken;
+use structs::TokenLifetime;
 use users::User as UserStruct;
 
 /// GET Endpoint for the recover email
@@ -132,13 +136,18 @@ fn creates_and_sends_recover_email(
 /// in the RecoverAccount struct to
 /// be submitted as a form.
 /// Checks if the CSRF matches and
-/// the email is valid. If everything
-/// succeeds it redirects to the index
-/// otherwise it redirects to the index
+/// the email is valid. Requests are rate
+/// limited by IP address and by email.
+/// The same message is shown whether the
+/// email belongs to an account or not. If
+/// everything succeeds it redirects to the
+/// index otherwise it redirects to the index
 /// and gives an appropriate message.
 #[post("/recover/email", data = "<user>", rank = 2)]
 fn send_recover_email(
     conn: DbConn,
+    redis_conn: RedisConnection,
+    client: ClientInfo,
     email_regex: State<EmailRegex>,
     postmark_token: State<PostmarkToken>,
     csrf_secret_key: State<CSRFSecretToken>,
@@ -159,12 +168,31 @@ fn send_recover_email(
         ));
     }
 
+    let ip_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::recover_ip_key(&client.ip),
+        rate_limit::RECOVER_WINDOW,
+    );
+    let email_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::recover_email_key(&input.email),
+        rate_limit::RECOVER_WINDOW,
+    );
+    if ip_attempts > rate_limit::RECOVER_ATTEMPTS_PER_IP
+        || email_attempts > rate_limit::RECOVER_ATTEMPTS_PER_EMAIL
+    {
+        return Err(Flash::error(
+            Redirect::to("/recover/email"),
+            "Too many attempts. Please try again later.",
+        ));
+    }
+
     let user_id: i64 = match authentication::database::get_user_id_from_email(&conn, &input.email) {
         Some(user_id) => user_id,
         None => {
-            return Err(Flash::error(
-                Redirect::to("/recover/email"),
-                "Email not found.",
+            return Ok(Flash::success(
+                Redirect::to("/"),
+                "Email sent. Please check your inbox.",
             ))
         }
     };
@@ -201,10 +229,13 @@ fn update_password_loggedin_page(_uuid: String, _userid: UserStruct) -> Redirect
 /// it responds with the Recover Email
 /// Template in the authentication folder.
 /// Otherwise it redirects to the index with
-/// an appropriate message.
+/// an appropriate message. If the link has
+/// expired it redirects to the page to send
+/// a new one.
 #[get("/email/recover/<uuid>", rank = 2)]
 fn update_password_page(
     conn: DbConn,
+    lifetime: State<TokenLifetime>,
     csrf_token: CsrfToken,
     flash: Option<FlashMessage>,
     uuid: String,
@@ -218,6 +249,13 @@ fn update_password_page(
         return Err(Flash::error(Redirect::to("/"), "Link already used."));
     }
 
+    if authentication::token_expired(result.created, lifetime.recover_hours) {
+        return Err(Flash::error(
+            Redirect::to("/recover/email"),
+            "The link has expired. Enter your email and we'll send you a new one.",
+        ));
+    }
+
     let (name, msg) = match flash {
         Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
         None => ("".to_string(), "".to_string()),
@@ -267,14 +305,20 @@ struct UpdatePassword {
 /// Checks if the CSRF matches and
 /// the email is valid. If everything
 /// succeeds it updates the password in
-/// the database and redirects to the
+/// the database, logs the user out of
+/// every session and redirects to the
 /// index otherwise it redirects to
 /// the index or the current UUID page
 /// and gives an appropriate message.
 #[post("/email/recover/<uuid>", data = "<user>", rank = 2)]
 fn update_password(
     conn: DbConn,
+    redis_conn: RedisConnection,
+    lifetime: State<TokenLifetime>,
+    bcrypt_cost: State<BcryptCost>,
+    password_policy: State<PasswordPolicy>,
     csrf_secret_key: State<CSRFSecretToken>,
+    client: ClientInfo,
     uuid: String,
     csrf_cookie: CsrfCookie,
     user: Form<UpdatePassword>,
@@ -296,6 +340,13 @@ fn update_password(
         return Err(Flash::error(Redirect::to("/"), "Link already used."));
     }
 
+    if authentication::token_expired(result.created, lifetime.recover_hours) {
+        return Err(Flash::error(
+            Redirect::to("/recover/email"),
+            "The link has expired. Enter your email and we'll send you a new one.",
+        ));
+    }
+
     if !csrf_matches(csrf_secret_key.0, &input.csrf, &csrf_cookie.value()) {
         return Err(Flash::error(
             Redirect::to(format!("/email/recover/{}", uuid)),
@@ -303,7 +354,18 @@ fn update_password(
         ));
     }
 
-    let hashed_password = match hash(&input.password, DEFAULT_COST) {
+    let account = match authentication::database::get_user(&conn, result.user_id) {
+        Some(account) => account,
+        None => return Err(Flash::error(Redirect::to("/"), "Link incorrect.")),
+    };
+    if let Err(error) = password_policy.check(&input.password, &account.username, &account.email) {
+        return Err(Flash::error(
+            Redirect::to(format!("/email/recover/{}", uuid)),
+            error.message(),
+        ));
+    }
+
+    let hashed_password = match passwords::hash_password(&input.password, bcrypt_cost.0) {
         Ok(hashed_password) => hashed_password,
         Err(_) => {
             return Err(Flash::error(
@@ -319,10 +381,29 @@ fn update_password(
         result.user_id,
         &hashed_password,
     ) {
-        Ok(_) => Ok(Flash::success(
-            Redirect::to("/"),
-            "Password updated, please sign in.",
-        )),
+        Ok(_) => {
+            match sessions::database::revoke_other_sessions(
+                &conn,
+                &redis_conn,
+                result.user_id,
+                None,
+            ) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+            audit::record(
+                &conn,
+                AuditEvent::PasswordRecovered,
+                Some(result.user_id),
+                None,
+                Some(&client),
+                None,
+            );
+            Ok(Flash::success(
+                Redirect::to("/"),
+                "Password updated, please sign in.",
+            ))
+        }
         Err(_) => Err(Flash::error(
             Redirect::to(format!("/email/recover/{}", uuid)),
             "An error occured, please try again later.",
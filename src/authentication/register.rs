@@ -1,8 +1,8 @@
-use authentication::{login, verify};
-use bcrypt::{hash, DEFAULT_COST};
+use authentication::{database, login, verify};
 use club_coding::create_new_user;
 use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
 use database::DbConn;
+use passwords::{self, BcryptCost, PasswordPolicy};
 use rocket::request::{FlashMessage, Form};
 use rocket::response::{Flash, Redirect};
 use rocket::{Route, State};
@@ -71,11 +71,9 @@ fn hash_and_create_user(
     username: &str,
     email: &str,
     password: &str,
+    cost: u32,
 ) -> Result<(), Error> {
-    let hashed_password: String = match hash(password, DEFAULT_COST) {
-        Ok(hashed_password) => hashed_password,
-        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not hash password.")),
-    };
+    let hashed_password: String = passwords::hash_password(password, cost)?;
 
     let new_user = match create_new_user(&**connection, username, &hashed_password, email) {
         Ok(new_user) => new_user,
@@ -103,15 +101,25 @@ fn register_user(
     conn: DbConn,
     email_regex: State<EmailRegex>,
     postmark_token: State<PostmarkToken>,
+    bcrypt_cost: State<BcryptCost>,
+    password_policy: State<PasswordPolicy>,
     csrf_secret_key: State<CSRFSecretToken>,
     csrf_cookie: CsrfCookie,
     user: Form<UserRegistration>,
 ) -> Result<Flash<Redirect>, Flash<Redirect>> {
     let input: UserRegistration = user.into_inner();
-    if !email_regex.0.is_match(&input.email) {
+    let email = input.email.trim().to_lowercase();
+    if !email_regex.0.is_match(&email) {
         return Err(Flash::error(Redirect::to("/signup"), "Email is not valid."));
     }
 
+    if input.username.contains('@') {
+        return Err(Flash::error(
+            Redirect::to("/signup"),
+            "Username can't contain @.",
+        ));
+    }
+
     if !(input.password == input.confirm_password) {
         return Err(Flash::error(
             Redirect::to("/signup"),
@@ -123,12 +131,24 @@ fn register_user(
         return Err(Flash::error(Redirect::to("/signup"), "CSRF Failed."));
     }
 
+    if let Err(error) = password_policy.check(&input.password, &input.username, &email) {
+        return Err(Flash::error(Redirect::to("/signup"), error.message()));
+    }
+
+    if database::email_exists(&conn, &email) {
+        return Err(Flash::error(
+            Redirect::to("/signup"),
+            "Email is already in use.",
+        ));
+    }
+
     match hash_and_create_user(
         &conn,
         &postmark_token.0,
         &input.username,
-        &input.email,
+        &email,
         &input.password,
+        bcrypt_cost.0,
     ) {
         Ok(_) => Ok(Flash::success(
             Redirect::to("/"),
@@ -4,6 +4,9 @@ mod test {
     use rocket::local::Client;
     use rocket::http::Status;
     use rocket::http::ContentType;
+    use authentication::rate_limit::{login_user_key, recover_email_key};
+    use authentication::token_expired;
+    use chrono::{Duration, Utc};
 
     #[test]
     fn authentication() {
@@ -13,4 +16,39 @@ mod test {
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(response.content_type(), Some(ContentType::HTML));
     }
+
+    #[test]
+    fn rate_limit_keys_ignore_case() {
+        assert_eq!(login_user_key("Axel"), login_user_key(" axel "));
+        assert_eq!(
+            recover_email_key("Axel@ClubCoding.com"),
+            recover_email_key("axel@clubcoding.com")
+        );
+    }
+
+    #[test]
+    fn tokens_expire_after_lifetime() {
+        let now = Utc::now().naive_utc();
+
+        assert!(!token_expired(now - Duration::hours(1), 2));
+        assert!(token_expired(now - Duration::hours(3), 2));
+    }
+
+    #[test]
+    fn resend_verify_email_page() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/email/verify").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+    }
+
+    #[test]
+    fn invalid_magic_link() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/login/link/invalid").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/login"));
+    }
 }
@@ -1,11 +1,17 @@
+use rocket::request::{FlashMessage, Form};
 use rocket::response::{Flash, Redirect};
 use club_coding::create_new_users_verify_email;
-use database::DbConn;
-use rocket::Route;
+use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
+use database::{DbConn, RedisConnection};
+use rocket::{Route, State};
+use rocket_contrib::templates::Template;
+use sessions::ClientInfo;
+use structs::{EmailRegex, PostmarkToken, TokenLifetime};
 use users::User as UserStruct;
 use email::{EmailBody, PostmarkClient};
 use std::io::{Error, ErrorKind};
 use authentication;
+use authentication::rate_limit;
 use diesel::prelude::*;
 
 /// Struct for emails, not used
@@ -29,7 +35,7 @@ pub fn send_verify_email(
     email: &str,
 ) -> Result<(), Error> {
     let token = authentication::generate_token(30);
-    create_new_users_verify_email(connection, user_id, &token)?;
+    create_new_users_verify_email(connection, user_id, &token, None)?;
     let tera = compile_templates!("templates/emails/**/*");
     let verify = VerifyEmail { token: &token };
     match tera.render("verify_account.html.tera", &verify) {
@@ -78,14 +84,25 @@ fn verify_email_loggedin(_uuid: String, _userid: UserStruct) -> Redirect {
 /// works it redirects to the index and
 /// otherwise it redirects to the index
 /// with an appropriate error message.
+/// If the link has expired it redirects
+/// to the page to send a new one.
 #[get("/email/verify/<uuid>", rank = 2)]
-fn verify_email(conn: DbConn, uuid: String) -> Result<Flash<Redirect>, Flash<Redirect>> {
+fn verify_email(
+    conn: DbConn,
+    lifetime: State<TokenLifetime>,
+    uuid: String,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
     let result = match authentication::database::get_verify_email_by_token(&conn, &uuid) {
         Some(result) => result,
         None => return Err(Flash::error(Redirect::to("/"), "Link incorrect.")),
     };
     if result.used {
         Err(Flash::error(Redirect::to("/"), "Link already used."))
+    } else if authentication::token_expired(result.created, lifetime.verify_hours) {
+        Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "The link has expired. Enter your email and we'll send you a new one.",
+        ))
     } else {
         match authentication::database::invalidate_token_and_verify_user(
             &conn,
@@ -104,10 +121,152 @@ fn verify_email(conn: DbConn, uuid: String) -> Result<Flash<Redirect>, Flash<Red
     }
 }
 
+/// GET Endpoint for the page to send a
+/// new verification email. Endpoints checks
+/// if the user is logged in by using the
+/// user request guard. If the user is logged
+/// in it redirect the user to the index,
+/// otherwise it forwards the request.
+#[get("/email/verify")]
+fn resend_verify_email_loggedin_page(_userid: UserStruct) -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint for the page to send a
+/// new verification email. This endpoint
+/// will kick in if the user is not logged in.
+/// Takes in an optional FlashMessage
+/// incase there is one. Responds with
+/// the Resend Verify Template in the
+/// authentication folder.
+#[get("/email/verify", rank = 2)]
+fn resend_verify_email_page(csrf_token: CsrfToken, flash: Option<FlashMessage>) -> Template {
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
+    let context = authentication::login::LoginContext {
+        header: "Verify email",
+        csrf: csrf_token.value(),
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Template::render("authentication/resend_verify", &context)
+}
+
+/// POST Endpoint to send a new verification
+/// email. Endpoints checks if the user is
+/// logged in by using the user request guard.
+/// If the user is logged in it redirect the
+/// user to the index, otherwise it forwards
+/// the request.
+#[post("/email/verify")]
+fn resend_verify_email_loggedin(_userid: UserStruct) -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for parsing resend
+/// verification email forms
+#[derive(FromForm)]
+struct ResendVerifyEmail {
+    /// The email of the user
+    email: String,
+    /// CSRF Token from the form
+    csrf: String,
+}
+
+/// POST Endpoint to send a new verification
+/// email. This endpoint will kick in if the
+/// user is not logged in.
+/// It requires all of the parameters in the
+/// ResendVerifyEmail struct to be submitted
+/// as a form. Requests are rate limited by
+/// IP address and by email. Earlier links
+/// stop working when a new one is sent.
+/// The same message is shown whether the email
+/// belongs to an unverified account or not.
+#[post("/email/verify", data = "<user>", rank = 2)]
+fn resend_verify_email(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    client: ClientInfo,
+    email_regex: State<EmailRegex>,
+    postmark_token: State<PostmarkToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    user: Form<ResendVerifyEmail>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let input: ResendVerifyEmail = user.into_inner();
+    if !email_regex.0.is_match(&input.email) {
+        return Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "Email is not valid.",
+        ));
+    }
+    if !csrf_matches(csrf_secret_key.0, &input.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "CSRF Doesn't match.",
+        ));
+    }
+
+    let ip_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::verify_ip_key(&client.ip),
+        rate_limit::RECOVER_WINDOW,
+    );
+    let email_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::verify_email_key(&input.email),
+        rate_limit::RECOVER_WINDOW,
+    );
+    if ip_attempts > rate_limit::RECOVER_ATTEMPTS_PER_IP
+        || email_attempts > rate_limit::RECOVER_ATTEMPTS_PER_EMAIL
+    {
+        return Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "Too many attempts. Please try again later.",
+        ));
+    }
+
+    let user_id = match authentication::database::get_unverified_user_id_from_email(
+        &conn,
+        &input.email,
+    ) {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(Flash::success(
+                Redirect::to("/"),
+                "Email sent. Please check your inbox.",
+            ))
+        }
+    };
+
+    match authentication::database::invalidate_verify_tokens(&conn, user_id)
+        .and_then(|_| send_verify_email(&conn, &postmark_token.0, user_id, &input.email))
+    {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/"),
+            "Email sent. Please check your inbox.",
+        )),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/email/verify"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
 /// instead we can keep all of the changes in here.
 pub fn endpoints() -> Vec<Route> {
-    routes![verify_email_loggedin, verify_email,]
+    routes![
+        verify_email_loggedin,
+        verify_email,
+        resend_verify_email_loggedin_page,
+        resend_verify_email_page,
+        resend_verify_email_loggedin,
+        resend_verify_email,
+    ]
 }
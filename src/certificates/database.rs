@@ -0,0 +1,111 @@
+use chrono::NaiveDateTime;
+use club_coding::models::{CertificateJoin, UsersCertificates};
+use database::DbConn;
+use diesel;
+use diesel::prelude::*;
+
+/// Gets a certificate specified by the UUID
+/// together with the username of the user
+/// and the title of the series. Returns
+/// either Some certificate or None if the
+/// certificate does not exist.
+pub fn get_certificate(connection: &DbConn, uid: &str) -> Option<CertificateJoin> {
+    use club_coding::schema::{series, users, users_certificates};
+
+    match users_certificates::table
+        .inner_join(users::table.on(users::id.eq(users_certificates::user_id)))
+        .inner_join(series::table.on(series::id.eq(users_certificates::serie_id)))
+        .filter(users_certificates::uuid.eq(uid))
+        .select((
+            users_certificates::uuid,
+            users_certificates::user_id,
+            users_certificates::serie_id,
+            users::username,
+            series::uuid,
+            series::title,
+            users_certificates::created,
+        ))
+        .first::<CertificateJoin>(&**connection)
+    {
+        Ok(certificate) => Some(certificate),
+        Err(_) => None,
+    }
+}
+
+/// Gets the certificate of the user for the
+/// series specified by the series id. Returns
+/// either Some certificate or None if the user
+/// has not been issued a certificate yet.
+pub fn get_user_certificate(
+    connection: &DbConn,
+    uid: i64,
+    sid: i64,
+) -> Option<UsersCertificates> {
+    use club_coding::schema::users_certificates::dsl::*;
+
+    match users_certificates
+        .filter(user_id.eq(uid))
+        .filter(serie_id.eq(sid))
+        .first::<UsersCertificates>(&**connection)
+    {
+        Ok(certificate) => Some(certificate),
+        Err(_) => None,
+    }
+}
+
+/// Checks if the user has completed every
+/// published episode in the series specified
+/// by the series id. A series without any
+/// published episodes can not be completed.
+pub fn has_completed_serie(connection: &DbConn, uid: i64, sid: i64) -> bool {
+    use club_coding::schema::{users_views, videos};
+
+    let episodes: Vec<i64> = match videos::table
+        .filter(videos::serie_id.eq(sid))
+        .filter(videos::published.eq(true))
+        .filter(videos::archived.eq(false))
+        .select(videos::id)
+        .load::<i64>(&**connection)
+    {
+        Ok(episodes) => episodes,
+        Err(_) => return false,
+    };
+    if episodes.is_empty() {
+        return false;
+    }
+
+    match users_views::table
+        .filter(users_views::user_id.eq(uid))
+        .filter(users_views::completed.eq(true))
+        .filter(users_views::video_id.eq_any(&episodes))
+        .count()
+        .get_result::<i64>(&**connection)
+    {
+        Ok(completed) => completed as usize == episodes.len(),
+        Err(_) => false,
+    }
+}
+
+/// Gets when the user completed the series
+/// specified by the series id, which is the
+/// time of the last completed view of a
+/// published episode in the series. Returns
+/// None if the user has not completed any
+/// episode in the series.
+pub fn get_completion_date(connection: &DbConn, uid: i64, sid: i64) -> Option<NaiveDateTime> {
+    use club_coding::schema::{users_views, videos};
+
+    match users_views::table
+        .inner_join(videos::table.on(videos::id.eq(users_views::video_id)))
+        .filter(users_views::user_id.eq(uid))
+        .filter(users_views::completed.eq(true))
+        .filter(videos::serie_id.eq(sid))
+        .filter(videos::published.eq(true))
+        .filter(videos::archived.eq(false))
+        .select(diesel::dsl::max(users_views::updated))
+        .first::<Option<NaiveDateTime>>(&**connection)
+    {
+        Ok(completed) => completed,
+        Err(_) => None,
+    }
+}
@@ -0,0 +1,255 @@
+pub mod database;
+
+use authentication;
+use chrono::NaiveDateTime;
+use club_coding::create_new_user_certificate;
+use club_coding::models::CertificateJoin;
+use database::DbConn;
+use rocket::Route;
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+use rocket::response::{Flash, Redirect};
+use rocket_contrib::templates::Template;
+use series;
+use users::User;
+
+#[cfg(test)]
+mod tests;
+
+/// Format of the completion date
+/// shown on certificates.
+static DATE_FORMAT: &'static str = "%B %-d, %Y";
+
+/// Context for rendering tera templates
+/// for the logged in verification endpoint.
+#[derive(Serialize)]
+struct VerifyContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// UUID of the certificate.
+    uuid: String,
+    /// Boolean of whether the certificate
+    /// is genuine or not.
+    valid: bool,
+    /// Username of the user the certificate
+    /// was issued to.
+    username: String,
+    /// Title of the completed series.
+    serie_title: String,
+    /// When the series was completed.
+    completed: String,
+}
+
+/// Context for rendering tera templates
+/// for the not logged in verification endpoint.
+#[derive(Serialize)]
+struct VerifyNoLoginContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// UUID of the certificate.
+    uuid: String,
+    /// Boolean of whether the certificate
+    /// is genuine or not.
+    valid: bool,
+    /// Username of the user the certificate
+    /// was issued to.
+    username: String,
+    /// Title of the completed series.
+    serie_title: String,
+    /// When the series was completed.
+    completed: String,
+}
+
+/// Escapes the characters that have a
+/// special meaning in XML so user input
+/// can be put in the SVG.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Gets when the series of the certificate
+/// was completed. Falls back to when the
+/// certificate was issued if the views of
+/// the series can't be found.
+fn completion_date(conn: &DbConn, certificate: &CertificateJoin) -> NaiveDateTime {
+    database::get_completion_date(conn, certificate.user_id, certificate.serie_id)
+        .unwrap_or(certificate.created)
+}
+
+/// Renders the certificate as an SVG with
+/// the username, the title of the series,
+/// the completion date and the URL where
+/// the certificate can be verified.
+fn render_svg(certificate: &CertificateJoin, completed: &NaiveDateTime) -> String {
+    format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="1123" height="794" viewBox="0 0 1123 794">
+  <rect width="1123" height="794" fill="#ffffff"/>
+  <rect x="24" y="24" width="1075" height="746" fill="none" stroke="#2c3e50" stroke-width="6"/>
+  <text x="561.5" y="170" font-family="Helvetica, Arial, sans-serif" font-size="56" text-anchor="middle" fill="#2c3e50">Certificate of Completion</text>
+  <text x="561.5" y="260" font-family="Helvetica, Arial, sans-serif" font-size="24" text-anchor="middle" fill="#7f8c8d">This certifies that</text>
+  <text x="561.5" y="340" font-family="Helvetica, Arial, sans-serif" font-size="48" text-anchor="middle" fill="#2c3e50">{username}</text>
+  <text x="561.5" y="420" font-family="Helvetica, Arial, sans-serif" font-size="24" text-anchor="middle" fill="#7f8c8d">has completed the series</text>
+  <text x="561.5" y="490" font-family="Helvetica, Arial, sans-serif" font-size="40" text-anchor="middle" fill="#2c3e50">{title}</text>
+  <text x="561.5" y="580" font-family="Helvetica, Arial, sans-serif" font-size="24" text-anchor="middle" fill="#7f8c8d">on {date}</text>
+  <text x="561.5" y="720" font-family="Helvetica, Arial, sans-serif" font-size="16" text-anchor="middle" fill="#95a5a6">Club Coding - Verify at https://clubcoding.com/certificates/{uuid}</text>
+</svg>
+"##,
+        username = escape_xml(&certificate.username),
+        title = escape_xml(&certificate.serie_title),
+        date = completed.format(DATE_FORMAT),
+        uuid = escape_xml(&certificate.uuid),
+    )
+}
+
+/// GET Endpoint to get the certificate of
+/// a series. Endpoints checks if the user
+/// is logged in by using the user request
+/// guard. If the user is not logged in it
+/// forwards the request.
+/// The certificate is issued the first time
+/// the endpoint is called after the user has
+/// completed every published episode in the
+/// series. Redirects to the certificate if
+/// the user has completed the series and
+/// otherwise back to the series with an
+/// error message.
+#[get("/claim/<uuid>")]
+fn claim(conn: DbConn, user: User, uuid: String) -> Result<Redirect, Flash<Redirect>> {
+    let serie = match series::database::get_serie(&conn, &uuid) {
+        Some(serie) => serie,
+        None => return Err(Flash::error(Redirect::to("/"), "Series not found.")),
+    };
+    if let Some(certificate) = database::get_user_certificate(&conn, user.id, serie.id) {
+        return Ok(Redirect::to(format!(
+            "/certificates/download/{}",
+            certificate.uuid
+        )));
+    }
+    if !database::has_completed_serie(&conn, user.id, serie.id) {
+        return Err(Flash::error(
+            Redirect::to(format!("/series/{}", uuid)),
+            "Complete every episode in the series to get a certificate.",
+        ));
+    }
+
+    let certificate_uuid = authentication::generate_token(24);
+    match create_new_user_certificate(&conn, &certificate_uuid, user.id, serie.id) {
+        Ok(_) => Ok(Redirect::to(format!(
+            "/certificates/download/{}",
+            certificate_uuid
+        ))),
+        Err(_) => Err(Flash::error(
+            Redirect::to(format!("/series/{}", uuid)),
+            "Could not issue certificate.",
+        )),
+    }
+}
+
+/// GET Endpoint to download a certificate.
+/// Responds with the certificate as an SVG
+/// or None if the certificate does not exist.
+#[get("/download/<uuid>")]
+fn download(conn: DbConn, uuid: String) -> Option<Content<String>> {
+    match database::get_certificate(&conn, &uuid) {
+        Some(certificate) => {
+            let completed = completion_date(&conn, &certificate);
+            Some(Content(
+                ContentType::SVG,
+                render_svg(&certificate, &completed),
+            ))
+        }
+        None => None,
+    }
+}
+
+/// GET Endpoint for the page to verify
+/// a certificate. Endpoints checks if
+/// the user is logged in by using the
+/// user request guard. If the user is
+/// not logged in it forwards the request.
+/// Responds with the Verify Template
+/// in the certificates folder that shows
+/// whether the certificate is genuine.
+#[get("/<uuid>")]
+fn verify(conn: DbConn, user: User, uuid: String) -> Template {
+    let context = match database::get_certificate(&conn, &uuid) {
+        Some(certificate) => {
+            let completed = completion_date(&conn, &certificate);
+            VerifyContext {
+                header: "Certificate",
+                user: user,
+                uuid: uuid,
+                valid: true,
+                username: certificate.username,
+                serie_title: certificate.serie_title,
+                completed: completed.format(DATE_FORMAT).to_string(),
+            }
+        }
+        None => VerifyContext {
+            header: "Certificate",
+            user: user,
+            uuid: uuid,
+            valid: false,
+            username: "".to_string(),
+            serie_title: "".to_string(),
+            completed: "".to_string(),
+        },
+    };
+    Template::render("certificates/verify", &context)
+}
+
+/// GET Endpoint for the page to verify
+/// a certificate. This endpoint will kick
+/// in if the user is not logged in.
+/// Responds with the Verify No Login
+/// Template in the certificates folder.
+#[get("/<uuid>", rank = 2)]
+fn verify_nologin(conn: DbConn, uuid: String) -> Template {
+    let context = match database::get_certificate(&conn, &uuid) {
+        Some(certificate) => {
+            let completed = completion_date(&conn, &certificate);
+            VerifyNoLoginContext {
+                header: "Certificate",
+                uuid: uuid,
+                valid: true,
+                username: certificate.username,
+                serie_title: certificate.serie_title,
+                completed: completed.format(DATE_FORMAT).to_string(),
+            }
+        }
+        None => VerifyNoLoginContext {
+            header: "Certificate",
+            uuid: uuid,
+            valid: false,
+            username: "".to_string(),
+            serie_title: "".to_string(),
+            completed: "".to_string(),
+        },
+    };
+    Template::render("certificates/verify_nologin", &context)
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![claim, download, verify, verify_nologin]
+}
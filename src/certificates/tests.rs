@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+    use rocket::http::ContentType;
+
+    #[test]
+    fn verify_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client
+            .get("/certificates/6A0C2E3B9D58F1B4E2A7C0D9F83B51E6C4A2D7F09B3E8C15")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+    }
+
+    #[test]
+    fn download_not_found() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client
+            .get("/certificates/download/6A0C2E3B9D58F1B4E2A7C0D9F83B51E6C4A2D7F09B3E8C15")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
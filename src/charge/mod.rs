@@ -1,4 +1,5 @@
 pub mod customer;
+use audit::{self, AuditEvent};
 use charge::customer::charge;
 use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
 use database::DbConn;
@@ -7,6 +8,7 @@ use rocket::request::Form;
 use rocket::response::{Flash, Redirect};
 use rocket::{Route, State};
 use rocket_contrib::templates::Template;
+use sessions::ClientInfo;
 use structs::{PostmarkToken, StripeToken};
 use users::User;
 
@@ -184,6 +186,7 @@ pub struct Stripe {
 fn add_card(
     conn: DbConn,
     user: User,
+    client: ClientInfo,
     csrf_cookie: CsrfCookie,
     stripe_token: State<StripeToken>,
     postmark: State<PostmarkToken>,
@@ -202,10 +205,20 @@ fn add_card(
         &user.email,
         user.id,
     ) {
-        Ok(()) => Ok(Flash::success(
-            Redirect::to("/"),
-            "Card added. Welcome to the club!",
-        )),
+        Ok(()) => {
+            audit::record(
+                &conn,
+                AuditEvent::CardAdded,
+                Some(user.id),
+                None,
+                Some(&client),
+                None,
+            );
+            Ok(Flash::success(
+                Redirect::to("/"),
+                "Card added. Welcome to the club!",
+            ))
+        }
         _ => Err(Flash::error(
             Redirect::to("/card/add"),
             "An error occured, please try again later.",
@@ -223,20 +236,22 @@ fn add_card(
 /// Stripe struct submitted as a form.
 /// If everything is successful, it will add
 /// a card to the user and redirect the user
-/// to the watch video page for the UUID.
+/// back to the watch video page for the UUID
+/// to buy the series.
 /// Otherwise it will redirect the user back
 /// to the card add page.
 #[post("/card/add/<uuid>", data = "<form_data>")]
 fn add_card_uuid(
     conn: DbConn,
     user: User,
+    client: ClientInfo,
     csrf_cookie: CsrfCookie,
     stripe_token: State<StripeToken>,
     postmark: State<PostmarkToken>,
     csrf_secret_key: State<CSRFSecretToken>,
     form_data: Form<Stripe>,
     uuid: String,
-) -> Result<Redirect, Flash<Redirect>> {
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
     let data = form_data.into_inner();
     if !csrf_matches(csrf_secret_key.0, &data.csrf, &csrf_cookie.value()) {
         return Err(Flash::error(
@@ -252,7 +267,20 @@ fn add_card_uuid(
         &user.email,
         user.id,
     ) {
-        Ok(()) => Ok(Redirect::to(format!("/watch/{}/buy/fiat", uuid))),
+        Ok(()) => {
+            audit::record(
+                &conn,
+                AuditEvent::CardAdded,
+                Some(user.id),
+                None,
+                Some(&client),
+                None,
+            );
+            Ok(Flash::success(
+                Redirect::to(format!("/watch/{}", uuid)),
+                "Card added. You can now buy the series.",
+            ))
+        }
         _ => Err(Flash::error(
             Redirect::to(format!("/card/add/{}", uuid)),
             "An error occured, please try again later.",
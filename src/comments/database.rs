@@ -0,0 +1,163 @@
+use club_coding::models::{AdminCommentJoin, CommentJoin, UsersVideosComments};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel::prelude::*;
+
+/// Gets all of the comments on a video
+/// together with the username of the
+/// author. Ordered by their creation
+/// date in an ascending order.
+pub fn get_comments(connection: &DbConn, vid: i64) -> Vec<CommentJoin> {
+    use club_coding::schema::{users, users_videos_comments};
+
+    match users_videos_comments::table
+        .inner_join(users::table.on(users::id.eq(users_videos_comments::user_id)))
+        .filter(users_videos_comments::video_id.eq(vid))
+        .order(users_videos_comments::created.asc())
+        .select((
+            users_videos_comments::id,
+            users_videos_comments::uuid,
+            users_videos_comments::user_id,
+            users::username,
+            users_videos_comments::parent_id,
+            users_videos_comments::body,
+            users_videos_comments::hidden,
+            users_videos_comments::deleted,
+            users_videos_comments::created,
+            users_videos_comments::updated,
+        ))
+        .load::<CommentJoin>(&**connection)
+    {
+        Ok(comments) => comments,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets a comment specified by the UUID.
+/// Returns either Some comment or None
+/// if the comment does not exist.
+pub fn get_comment(connection: &DbConn, uid: &str) -> Option<UsersVideosComments> {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    match users_videos_comments
+        .filter(uuid.eq(uid))
+        .first::<UsersVideosComments>(&**connection)
+    {
+        Ok(comment) => Some(comment),
+        Err(_) => None,
+    }
+}
+
+/// Updates the body of the comment
+/// specified by the comment id.
+pub fn update_comment_body(connection: &DbConn, cid: i64, new_body: &str) -> Result<(), Error> {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    match diesel::update(users_videos_comments.find(cid))
+        .set(body.eq(new_body))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update comment.")),
+    }
+}
+
+/// Marks the comment specified by the
+/// comment id as deleted and removes
+/// the body. The row is kept so the
+/// replies to it still have a parent.
+pub fn mark_comment_deleted(connection: &DbConn, cid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    match diesel::update(users_videos_comments.find(cid))
+        .set((deleted.eq(true), body.eq("")))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete comment.")),
+    }
+}
+
+/// Hides or shows the comment
+/// specified by the UUID.
+pub fn set_comment_hidden(connection: &DbConn, uid: &str, hide: bool) -> Result<(), Error> {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    match diesel::update(users_videos_comments.filter(uuid.eq(uid)))
+        .set(hidden.eq(hide))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update comment.")),
+    }
+}
+
+/// Deletes the comment specified by the
+/// comment id and all of the replies to
+/// it from the database.
+pub fn delete_comment_and_replies(connection: &DbConn, cid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    let replies: Vec<i64> = match users_videos_comments
+        .filter(parent_id.eq(cid))
+        .select(id)
+        .load::<i64>(&**connection)
+    {
+        Ok(replies) => replies,
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not load replies.")),
+    };
+
+    for reply in replies {
+        delete_comment_and_replies(connection, reply)?;
+    }
+
+    match diesel::delete(users_videos_comments.find(cid)).execute(&**connection) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete comment.")),
+    }
+}
+
+/// Counts the comments on a video
+/// that have not been deleted.
+pub fn count_comments(connection: &DbConn, vid: i64) -> i64 {
+    use club_coding::schema::users_videos_comments::dsl::*;
+
+    match users_videos_comments
+        .filter(video_id.eq(vid))
+        .filter(deleted.eq(false))
+        .count()
+        .get_result::<i64>(&**connection)
+    {
+        Ok(comments) => comments,
+        Err(_) => 0,
+    }
+}
+
+/// Gets the newest comments on all videos
+/// together with the author and the video.
+/// Ordered by their creation date in a
+/// descending order.
+pub fn get_newest_comments(connection: &DbConn, limit: i64) -> Vec<AdminCommentJoin> {
+    use club_coding::schema::{users, users_videos_comments, videos};
+
+    match users_videos_comments::table
+        .inner_join(users::table.on(users::id.eq(users_videos_comments::user_id)))
+        .inner_join(videos::table.on(videos::id.eq(users_videos_comments::video_id)))
+        .order(users_videos_comments::created.desc())
+        .limit(limit)
+        .select((
+            users_videos_comments::uuid,
+            users::username,
+            videos::uuid,
+            videos::title,
+            users_videos_comments::body,
+            users_videos_comments::hidden,
+            users_videos_comments::deleted,
+            users_videos_comments::created,
+        ))
+        .load::<AdminCommentJoin>(&**connection)
+    {
+        Ok(comments) => comments,
+        Err(_) => vec![],
+    }
+}
@@ -0,0 +1,255 @@
+pub mod database;
+
+use authentication;
+use chrono::NaiveDateTime;
+use club_coding::create_new_user_video_comment;
+use club_coding::models::CommentJoin;
+use database::DbConn;
+use rocket::Route;
+use rocket_contrib::json::Json;
+use users::User;
+use videos;
+
+#[cfg(test)]
+mod tests;
+
+/// Maximum number of characters
+/// allowed in a comment.
+static MAX_COMMENT_LENGTH: usize = 5000;
+
+#[derive(Serialize)]
+pub struct PublicComment {
+    /// UUID of the comment.
+    uuid: String,
+    /// Username of the author.
+    username: String,
+    /// The text of the comment. Empty
+    /// if the comment has been deleted.
+    body: String,
+    /// Boolean of whether the comment
+    /// was written by the logged in user.
+    own: bool,
+    /// Boolean of whether the comment
+    /// has been deleted by the author.
+    deleted: bool,
+    /// When the comment was written.
+    created: NaiveDateTime,
+    /// When the comment was last edited.
+    updated: NaiveDateTime,
+    /// The replies to the comment.
+    replies: Vec<PublicComment>,
+}
+
+/// Builds the thread of replies to the
+/// comment specified by the parent id, or
+/// the top level comments if it is None.
+/// Hidden comments are left out together
+/// with their replies and deleted comments
+/// are only kept if they have replies.
+fn build_thread(
+    comments: &[CommentJoin],
+    parent: Option<i64>,
+    uid: Option<i64>,
+) -> Vec<PublicComment> {
+    let mut thread: Vec<PublicComment> = vec![];
+    for comment in comments {
+        if comment.parent_id != parent || comment.hidden {
+            continue;
+        }
+        let replies = build_thread(comments, Some(comment.id), uid);
+        if comment.deleted && replies.is_empty() {
+            continue;
+        }
+        thread.push(PublicComment {
+            uuid: comment.uuid.clone(),
+            username: if comment.deleted {
+                "".to_string()
+            } else {
+                comment.username.clone()
+            },
+            body: comment.body.clone(),
+            own: !comment.deleted && Some(comment.user_id) == uid,
+            deleted: comment.deleted,
+            created: comment.created,
+            updated: comment.updated,
+            replies: replies,
+        });
+    }
+    thread
+}
+
+/// Gets the comment threads of a video.
+/// The user id is used to mark which
+/// comments the logged in user can edit.
+pub fn get_video_comments(connection: &DbConn, vid: i64, uid: Option<i64>) -> Vec<PublicComment> {
+    let comments = database::get_comments(connection, vid);
+    build_thread(&comments, None, uid)
+}
+
+/// Checks that the body of a comment is
+/// not empty and not too long. Returns
+/// the trimmed body if it is valid.
+fn validate_body(body: &str) -> Option<&str> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_COMMENT_LENGTH {
+        None
+    } else {
+        Some(body)
+    }
+}
+
+/// GET Endpoint for the comments on a
+/// video. Endpoints checks if the user
+/// is logged in by using the user request
+/// guard. If the user is not logged in it
+/// forwards the request.
+/// Responds with the comment threads
+/// in a JSON format.
+#[get("/watch/<uuid>/comments")]
+fn comments(conn: DbConn, user: User, uuid: String) -> Option<Json<Vec<PublicComment>>> {
+    match videos::database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => Some(Json(get_video_comments(&conn, video.id, Some(user.id)))),
+        Err(_video_not_found) => None,
+    }
+}
+
+/// GET Endpoint for the comments on a
+/// video. This endpoint will kick in
+/// if the user is not logged in.
+/// Responds with the comment threads
+/// in a JSON format, or no comments if
+/// the video is only for members.
+#[get("/watch/<uuid>/comments", rank = 2)]
+fn comments_nologin(conn: DbConn, uuid: String) -> Option<Json<Vec<PublicComment>>> {
+    match videos::database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(ref video) if video.membership_only => Some(Json(vec![])),
+        Ok(video) => Some(Json(get_video_comments(&conn, video.id, None))),
+        Err(_video_not_found) => None,
+    }
+}
+
+/// Struct for posting a new
+/// comment on a video.
+#[derive(Deserialize)]
+struct NewComment {
+    /// The text of the comment.
+    body: String,
+    /// UUID of the comment that is
+    /// replied to, if it is a reply.
+    parent: Option<String>,
+}
+
+/// POST Endpoint to comment on a video.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// It requires the parameters in the
+/// NewComment struct in a JSON format.
+/// The user must have access to the video.
+/// If everything is successful, it responds
+/// with the updated comment threads in a
+/// JSON format.
+#[post("/watch/<uuid>/comments", format = "application/json", data = "<data>")]
+fn post_comment(
+    conn: DbConn,
+    user: User,
+    uuid: String,
+    data: Json<NewComment>,
+) -> Result<Json<Vec<PublicComment>>, ()> {
+    let video = match videos::database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => video,
+        Err(_video_not_found) => return Err(()),
+    };
+    if video.membership_only && !videos::database::user_has_access(&conn, video.serie_id, user.id) {
+        return Err(());
+    }
+    let body = match validate_body(&data.0.body) {
+        Some(body) => body,
+        None => return Err(()),
+    };
+    let parent_id = match data.0.parent {
+        Some(ref parent) => match database::get_comment(&conn, parent) {
+            Some(ref parent) if parent.video_id == video.id && !parent.deleted => Some(parent.id),
+            _ => return Err(()),
+        },
+        None => None,
+    };
+
+    let comment_uuid = authentication::generate_token(24);
+    match create_new_user_video_comment(&conn, &comment_uuid, user.id, video.id, parent_id, body) {
+        Ok(_) => Ok(Json(get_video_comments(&conn, video.id, Some(user.id)))),
+        Err(_) => Err(()),
+    }
+}
+
+/// Struct for editing a comment.
+#[derive(Deserialize)]
+struct EditComment {
+    /// The new text of the comment.
+    body: String,
+}
+
+/// POST Endpoint to edit a comment.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// It requires the parameter in the
+/// EditComment struct in a JSON format.
+/// Users can only edit their own comments.
+#[post("/comments/<uuid>/edit", format = "application/json", data = "<data>")]
+fn edit_comment(conn: DbConn, user: User, uuid: String, data: Json<EditComment>) -> Result<(), ()> {
+    match database::get_comment(&conn, &uuid) {
+        Some(comment) => {
+            if comment.user_id != user.id || comment.deleted {
+                return Err(());
+            }
+            match validate_body(&data.0.body) {
+                Some(body) => match database::update_comment_body(&conn, comment.id, body) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(()),
+                },
+                None => Err(()),
+            }
+        }
+        None => Err(()),
+    }
+}
+
+/// POST Endpoint to delete a comment.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// Users can only delete their own comments.
+/// The replies to the comment are kept.
+#[post("/comments/<uuid>/delete")]
+fn delete_comment(conn: DbConn, user: User, uuid: String) -> Result<(), ()> {
+    match database::get_comment(&conn, &uuid) {
+        Some(comment) => {
+            if comment.user_id != user.id {
+                return Err(());
+            }
+            match database::mark_comment_deleted(&conn, comment.id) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(()),
+            }
+        }
+        None => Err(()),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![
+        comments,
+        comments_nologin,
+        post_comment,
+        edit_comment,
+        delete_comment
+    ]
+}
@@ -0,0 +1,149 @@
+use club_coding::create_new_coupon_redemption;
+use club_coding::models::{Coupons, CouponsRedemptions, CouponsSeries};
+use chrono::Utc;
+use database::DbConn;
+use diesel;
+use diesel::Connection;
+use diesel::prelude::*;
+
+/// Gets the coupon with the code, if any.
+/// The code has to be normalized.
+pub fn get_coupon_by_code(connection: &DbConn, name: &str) -> Option<Coupons> {
+    use club_coding::schema::coupons::dsl::*;
+
+    match coupons.filter(code.eq(name)).first::<Coupons>(&**connection) {
+        Ok(coupon) => Some(coupon),
+        Err(_) => None,
+    }
+}
+
+/// Gets every coupon, newest first.
+pub fn get_coupons(connection: &DbConn) -> Vec<Coupons> {
+    use club_coding::schema::coupons::dsl::*;
+
+    match coupons.order(id.desc()).load::<Coupons>(&**connection) {
+        Ok(result) => result,
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the ids of the series the coupon is
+/// restricted to. Empty if it can be used
+/// for all series.
+pub fn get_coupon_series(connection: &DbConn, cid: i64) -> Vec<i64> {
+    use club_coding::schema::coupons_series::dsl::*;
+
+    match coupons_series
+        .filter(coupon_id.eq(cid))
+        .load::<CouponsSeries>(&**connection)
+    {
+        Ok(result) => result.into_iter().map(|row| row.series_id).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Checks if the user has
+/// redeemed the coupon.
+pub fn has_redeemed(connection: &DbConn, cid: i64, uid: i64) -> bool {
+    use club_coding::schema::coupons_redemptions::dsl::*;
+
+    match coupons_redemptions
+        .filter(coupon_id.eq(cid))
+        .filter(user_id.eq(uid))
+        .first::<CouponsRedemptions>(&**connection)
+    {
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Redeems the coupon for the user buying the
+/// series before the card is charged. Counts the
+/// redemption if the coupon has any left and
+/// inserts the redemption of the user, which is
+/// unique per coupon and user, in one transaction.
+/// Two purchases can't both take the last one and
+/// one user can't use the coupon twice at the
+/// same time. Returns false if either fails.
+pub fn reserve_redemption(
+    connection: &DbConn,
+    cid: i64,
+    uid: i64,
+    sid: i64,
+    discount: i32,
+) -> bool {
+    use club_coding::schema::coupons::dsl::*;
+
+    let reserved = connection.transaction::<_, diesel::result::Error, _>(|| {
+        let updated = diesel::update(
+            coupons.filter(id.eq(cid)).filter(
+                max_redemptions
+                    .is_null()
+                    .or(times_redeemed.nullable().lt(max_redemptions)),
+            ),
+        ).set(times_redeemed.eq(times_redeemed + 1))
+            .execute(&**connection)?;
+        if updated != 1 {
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+        create_new_coupon_redemption(&**connection, cid, uid, sid, None, discount)
+            .map_err(|_| diesel::result::Error::RollbackTransaction)
+    });
+    reserved.is_ok()
+}
+
+/// Sets the charge of the redemption
+/// once the card has been charged.
+pub fn set_redemption_charge(
+    connection: &DbConn,
+    cid: i64,
+    uid: i64,
+    charge: &str,
+) -> Result<(), diesel::result::Error> {
+    use club_coding::schema::coupons_redemptions::dsl::*;
+
+    diesel::update(
+        coupons_redemptions
+            .filter(coupon_id.eq(cid))
+            .filter(user_id.eq(uid)),
+    ).set(charge_uuid.eq(Some(charge)))
+        .execute(&**connection)
+        .map(|_| ())
+}
+
+/// Gives back a redemption reserved
+/// for a purchase that failed.
+pub fn release_redemption(connection: &DbConn, cid: i64, uid: i64) {
+    use club_coding::schema::{coupons, coupons_redemptions};
+
+    let _ = connection.transaction::<_, diesel::result::Error, _>(|| {
+        let deleted = diesel::delete(
+            coupons_redemptions::table
+                .filter(coupons_redemptions::coupon_id.eq(cid))
+                .filter(coupons_redemptions::user_id.eq(uid)),
+        ).execute(&**connection)?;
+        if deleted == 1 {
+            diesel::update(
+                coupons::table
+                    .filter(coupons::id.eq(cid))
+                    .filter(coupons::times_redeemed.gt(0)),
+            ).set(coupons::times_redeemed.eq(coupons::times_redeemed - 1))
+                .execute(&**connection)?;
+        }
+        Ok(())
+    });
+}
+
+/// Expires the coupon right away
+/// so it can't be used anymore.
+pub fn expire_coupon(connection: &DbConn, cid: i64) -> bool {
+    use club_coding::schema::coupons::dsl::*;
+
+    match diesel::update(coupons.find(cid))
+        .set(expires.eq(Some(Utc::now().naive_utc())))
+        .execute(&**connection)
+    {
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
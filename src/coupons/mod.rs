@@ -0,0 +1,162 @@
+pub mod database;
+
+use chrono::{NaiveDateTime, Utc};
+use club_coding::models::Coupons;
+use currency;
+use database::DbConn;
+
+#[cfg(test)]
+mod tests;
+
+/// Kinds of coupons. Percent coupons take
+/// a percentage off the price and fixed
+/// coupons take an amount in cents off. Fixed
+/// coupons can only be used in their currency.
+pub static KINDS: [&'static str; 2] = ["percent", "fixed"];
+
+/// Reasons a coupon can't be used.
+#[derive(Debug, PartialEq)]
+pub enum CouponError {
+    /// No coupon has the code.
+    NotFound,
+    /// The coupon has expired.
+    Expired,
+    /// The coupon has been redeemed
+    /// the maximum number of times.
+    UsedUp,
+    /// The coupon can't be used
+    /// for the series.
+    WrongSeries,
+    /// The coupon takes an amount off
+    /// in another currency than the
+    /// one the user pays in.
+    WrongCurrency,
+    /// The user has already
+    /// redeemed the coupon.
+    AlreadyRedeemed,
+}
+
+impl CouponError {
+    /// Message shown to the user.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            CouponError::NotFound => "The coupon code is not valid.",
+            CouponError::Expired => "The coupon has expired.",
+            CouponError::UsedUp => "The coupon has been used up.",
+            CouponError::WrongSeries => "The coupon can't be used for this series.",
+            CouponError::WrongCurrency => "The coupon can't be used in your currency.",
+            CouponError::AlreadyRedeemed => "You have already used this coupon.",
+        }
+    }
+}
+
+/// Codes are stored in upper case so
+/// they can be typed in any case.
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Checks that the kind is known and the value
+/// makes sense for it. Percent coupons take off
+/// 1 to 100 percent, fixed coupons at least a cent.
+pub fn valid_value(kind: &str, value: i32) -> bool {
+    match kind {
+        "percent" => value > 0 && value <= 100,
+        "fixed" => value > 0,
+        _ => false,
+    }
+}
+
+/// Codes can only have letters, numbers
+/// and dashes so they are easy to type.
+pub fn valid_code(code: &str) -> bool {
+    !code.is_empty() && code.len() <= 32
+        && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// The amount in cents the coupon takes
+/// off the price. Never more than the price.
+pub fn discount(price: i32, kind: &str, value: i32) -> i32 {
+    let discount = match kind {
+        "percent" => (price as i64 * value as i64 / 100) as i32,
+        _ => value,
+    };
+    discount.max(0).min(price)
+}
+
+/// The price in the currency after the discount,
+/// raised to the minimum charge of the currency
+/// unless it is free.
+pub fn discounted_price(price: i32, discount: i32, currency: &str) -> i32 {
+    let discounted = price - discount;
+    let minimum = currency::minimum_charge(currency);
+    if discounted > 0 && discounted < minimum {
+        minimum
+    } else {
+        discounted
+    }
+}
+
+/// Checks if a coupon of the kind in the
+/// coupon currency can be used when paying
+/// in the currency. Percent coupons can be
+/// used in any currency.
+pub fn usable_in(kind: &str, coupon_currency: &str, currency: &str) -> bool {
+    kind == "percent" || coupon_currency == currency
+}
+
+/// Checks if the coupon can be used for the
+/// series in the currency at the time now.
+/// Coupons without any series can be used
+/// for all series.
+pub fn check(
+    coupon: &Coupons,
+    coupon_series: &[i64],
+    series_id: i64,
+    currency: &str,
+    now: NaiveDateTime,
+) -> Result<(), CouponError> {
+    if coupon.expires.map_or(false, |expires| expires <= now) {
+        return Err(CouponError::Expired);
+    }
+    if coupon
+        .max_redemptions
+        .map_or(false, |max| coupon.times_redeemed >= max)
+    {
+        return Err(CouponError::UsedUp);
+    }
+    if !coupon_series.is_empty() && !coupon_series.contains(&series_id) {
+        return Err(CouponError::WrongSeries);
+    }
+    if !usable_in(&coupon.kind, &coupon.currency, currency) {
+        return Err(CouponError::WrongCurrency);
+    }
+    Ok(())
+}
+
+/// Gets the coupon with the code if the user
+/// can use it to buy the series in the currency.
+pub fn find_coupon(
+    conn: &DbConn,
+    code: &str,
+    user_id: i64,
+    series_id: i64,
+    currency: &str,
+) -> Result<Coupons, CouponError> {
+    let coupon = match database::get_coupon_by_code(conn, &normalize_code(code)) {
+        Some(coupon) => coupon,
+        None => return Err(CouponError::NotFound),
+    };
+    let coupon_series = database::get_coupon_series(conn, coupon.id);
+    check(
+        &coupon,
+        &coupon_series,
+        series_id,
+        currency,
+        Utc::now().naive_utc(),
+    )?;
+    if database::has_redeemed(conn, coupon.id, user_id) {
+        return Err(CouponError::AlreadyRedeemed);
+    }
+    Ok(coupon)
+}
@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use club_coding::models::Coupons;
+    use coupons::{check, discount, discounted_price, normalize_code, usable_in, valid_code,
+                  valid_value, CouponError};
+
+    fn coupon(kind: &str, max_redemptions: Option<i32>, times_redeemed: i32) -> Coupons {
+        let created = NaiveDate::from_ymd(2018, 8, 1).and_hms(12, 0, 0);
+        Coupons {
+            id: 1,
+            code: "LAUNCH".to_string(),
+            kind: kind.to_string(),
+            value: 20,
+            expires: Some(NaiveDate::from_ymd(2018, 9, 1).and_hms(23, 59, 59)),
+            max_redemptions: max_redemptions,
+            times_redeemed: times_redeemed,
+            created: created,
+            updated: created,
+            currency: "usd".to_string(),
+        }
+    }
+
+    #[test]
+    fn discounts() {
+        assert_eq!(discount(1500, "percent", 20), 300);
+        assert_eq!(discount(1500, "percent", 100), 1500);
+        assert_eq!(discount(1500, "fixed", 500), 500);
+        assert_eq!(discount(1500, "fixed", 2000), 1500);
+        assert_eq!(discount(1500, "fixed", -100), 0);
+    }
+
+    #[test]
+    fn discounted_prices() {
+        assert_eq!(discounted_price(1500, 300, "usd"), 1200);
+        assert_eq!(discounted_price(1500, 1500, "usd"), 0);
+        assert_eq!(discounted_price(1500, 1480, "usd"), 50);
+        assert_eq!(discounted_price(13900, 13700, "sek"), 300);
+        assert_eq!(discounted_price(1500, 1480, "gbp"), 30);
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(normalize_code("  launch-2018 "), "LAUNCH-2018");
+        assert!(valid_code("LAUNCH-2018"));
+        assert!(!valid_code(""));
+        assert!(!valid_code("LAUNCH 2018"));
+        assert!(valid_value("percent", 100));
+        assert!(!valid_value("percent", 101));
+        assert!(!valid_value("fixed", 0));
+        assert!(!valid_value("free", 10));
+    }
+
+    #[test]
+    fn checks() {
+        let now = NaiveDate::from_ymd(2018, 8, 5).and_hms(12, 0, 0);
+        let later = NaiveDate::from_ymd(2018, 9, 2).and_hms(0, 0, 0);
+
+        assert_eq!(check(&coupon("percent", None, 0), &[], 3, "usd", now), Ok(()));
+        assert_eq!(
+            check(&coupon("percent", Some(10), 9), &[3, 4], 3, "sek", now),
+            Ok(())
+        );
+        assert_eq!(
+            check(&coupon("percent", None, 0), &[], 3, "usd", later),
+            Err(CouponError::Expired)
+        );
+        assert_eq!(
+            check(&coupon("percent", Some(10), 10), &[], 3, "usd", now),
+            Err(CouponError::UsedUp)
+        );
+        assert_eq!(
+            check(&coupon("percent", None, 0), &[4], 3, "usd", now),
+            Err(CouponError::WrongSeries)
+        );
+        assert_eq!(check(&coupon("fixed", None, 0), &[], 3, "usd", now), Ok(()));
+        assert_eq!(
+            check(&coupon("fixed", None, 0), &[], 3, "sek", now),
+            Err(CouponError::WrongCurrency)
+        );
+    }
+
+    #[test]
+    fn currencies() {
+        assert!(usable_in("percent", "usd", "eur"));
+        assert!(usable_in("fixed", "eur", "eur"));
+        assert!(!usable_in("fixed", "usd", "sek"));
+    }
+}
@@ -0,0 +1,84 @@
+use club_coding::models::{SeriesPrices, UsersStripeCard};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel;
+use diesel::prelude::*;
+
+/// Gets the prices the series has in other
+/// currencies than the default one, as
+/// pairs of the currency and the price.
+pub fn get_series_prices(connection: &DbConn, sid: i64) -> Vec<(String, i32)> {
+    use club_coding::schema::series_prices::dsl::*;
+
+    match series_prices
+        .filter(series_id.eq(sid))
+        .order(currency.asc())
+        .load::<SeriesPrices>(&**connection)
+    {
+        Ok(result) => result
+            .into_iter()
+            .map(|row| (row.currency, row.price))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Removes the price of the series in the
+/// currency, so it is sold at the price in
+/// the default currency instead.
+pub fn delete_series_price(connection: &DbConn, sid: i64, code: &str) -> Result<(), Error> {
+    use club_coding::schema::series_prices::dsl::*;
+
+    match diesel::delete(
+        series_prices
+            .filter(series_id.eq(sid))
+            .filter(currency.eq(code)),
+    ).execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete price.")),
+    }
+}
+
+/// Gets the currency the user has
+/// chosen in the settings, if any.
+pub fn get_user_currency(connection: &DbConn, uid: i64) -> Option<String> {
+    use club_coding::schema::users::dsl::*;
+
+    match users
+        .find(uid)
+        .select(currency)
+        .first::<Option<String>>(&**connection)
+    {
+        Ok(result) => result,
+        Err(_) => None,
+    }
+}
+
+/// Sets the currency the user wants to pay
+/// in. None picks it from the card instead.
+pub fn set_user_currency(connection: &DbConn, uid: i64, code: Option<&str>) -> Result<(), Error> {
+    use club_coding::schema::users::dsl::*;
+
+    match diesel::update(users.find(uid))
+        .set(currency.eq(code))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update currency.")),
+    }
+}
+
+/// Gets the country of the
+/// card of the user, if any.
+pub fn get_card_country(connection: &DbConn, uid: i64) -> Option<String> {
+    use club_coding::schema::users_stripe_card::dsl::*;
+
+    match users_stripe_card
+        .filter(user_id.eq(uid))
+        .first::<UsersStripeCard>(&**connection)
+    {
+        Ok(card) => Some(card.country),
+        Err(_) => None,
+    }
+}
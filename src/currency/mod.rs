@@ -0,0 +1,102 @@
+pub mod database;
+
+use database::DbConn;
+
+#[cfg(test)]
+mod tests;
+
+/// Currencies series can be priced and
+/// charged in, as lower case ISO codes
+/// the way Stripe wants them.
+pub static CURRENCIES: [&'static str; 4] = ["usd", "eur", "gbp", "sek"];
+
+/// Currency of the price stored on the
+/// series itself. Used when the series
+/// has no price in the user's currency.
+pub static DEFAULT_CURRENCY: &'static str = "usd";
+
+/// Countries paying with euro, as the
+/// two letter codes Stripe gives cards.
+static EURO_COUNTRIES: [&'static str; 19] = [
+    "AT", "BE", "CY", "DE", "EE", "ES", "FI", "FR", "GR", "IE", "IT", "LT", "LU", "LV", "MT",
+    "NL", "PT", "SI", "SK",
+];
+
+/// Smallest amount in cents Stripe
+/// can charge in the currency.
+pub fn minimum_charge(currency: &str) -> i32 {
+    match currency {
+        "gbp" => 30,
+        "sek" => 300,
+        _ => 50,
+    }
+}
+
+/// Gets the supported currency with the
+/// code, in any case. None if it isn't
+/// supported.
+pub fn supported(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_lowercase();
+    CURRENCIES.iter().find(|currency| **currency == code).cloned()
+}
+
+/// The currency used in the country
+/// of a card, or the default currency
+/// if we don't charge in it.
+pub fn currency_for_country(country: &str) -> &'static str {
+    match country {
+        "GB" => "gbp",
+        "SE" => "sek",
+        country if EURO_COUNTRIES.contains(&country) => "eur",
+        _ => DEFAULT_CURRENCY,
+    }
+}
+
+/// Picks the currency of a user. The currency the
+/// user chose in the settings comes first, then the
+/// currency of the country of the card.
+pub fn default_currency(preference: Option<&str>, card_country: Option<&str>) -> &'static str {
+    match preference.and_then(supported) {
+        Some(currency) => currency,
+        None => card_country.map_or(DEFAULT_CURRENCY, currency_for_country),
+    }
+}
+
+/// Picks the price of the series in the currency
+/// out of the prices set for it. Falls back to the
+/// price of the series in the default currency.
+/// Returns the currency and the price.
+pub fn pick_price(
+    price: i32,
+    prices: &[(String, i32)],
+    currency: &'static str,
+) -> (&'static str, i32) {
+    if currency == DEFAULT_CURRENCY {
+        return (DEFAULT_CURRENCY, price);
+    }
+    match prices.iter().find(|&&(ref code, _)| code == currency) {
+        Some(&(_, price)) => (currency, price),
+        None => (DEFAULT_CURRENCY, price),
+    }
+}
+
+/// Gets the currency the user pays in.
+pub fn user_currency(conn: &DbConn, user_id: i64) -> &'static str {
+    let preference = database::get_user_currency(conn, user_id);
+    let country = database::get_card_country(conn, user_id);
+    default_currency(
+        preference.as_ref().map(|currency| currency.as_str()),
+        country.as_ref().map(|country| country.as_str()),
+    )
+}
+
+/// Gets the price of the series in the currency
+/// the user pays in, given the price stored on
+/// the series. Returns the currency and the price.
+pub fn user_price(conn: &DbConn, series_id: i64, price: i32, user_id: i64) -> (&'static str, i32) {
+    pick_price(
+        price,
+        &database::get_series_prices(conn, series_id),
+        user_currency(conn, user_id),
+    )
+}
@@ -0,0 +1,46 @@
+#[cfg(test)]
+mod test {
+    use currency::{currency_for_country, default_currency, minimum_charge, pick_price,
+                   supported};
+
+    #[test]
+    fn supported_currencies() {
+        assert_eq!(supported("EUR"), Some("eur"));
+        assert_eq!(supported(" sek "), Some("sek"));
+        assert_eq!(supported("btc"), None);
+    }
+
+    #[test]
+    fn card_countries() {
+        assert_eq!(currency_for_country("DE"), "eur");
+        assert_eq!(currency_for_country("GB"), "gbp");
+        assert_eq!(currency_for_country("SE"), "sek");
+        assert_eq!(currency_for_country("US"), "usd");
+        assert_eq!(currency_for_country("JP"), "usd");
+    }
+
+    #[test]
+    fn default_currencies() {
+        assert_eq!(default_currency(Some("gbp"), Some("SE")), "gbp");
+        assert_eq!(default_currency(None, Some("SE")), "sek");
+        assert_eq!(default_currency(Some("btc"), Some("FR")), "eur");
+        assert_eq!(default_currency(None, None), "usd");
+    }
+
+    #[test]
+    fn minimum_charges() {
+        assert_eq!(minimum_charge("usd"), 50);
+        assert_eq!(minimum_charge("eur"), 50);
+        assert_eq!(minimum_charge("gbp"), 30);
+        assert_eq!(minimum_charge("sek"), 300);
+    }
+
+    #[test]
+    fn prices() {
+        let prices = vec![("eur".to_string(), 1300), ("sek".to_string(), 13900)];
+        assert_eq!(pick_price(1500, &prices, "eur"), ("eur", 1300));
+        assert_eq!(pick_price(1500, &prices, "sek"), ("sek", 13900));
+        assert_eq!(pick_price(1500, &prices, "gbp"), ("usd", 1500));
+        assert_eq!(pick_price(1500, &prices, "usd"), ("usd", 1500));
+    }
+}
@@ -6,15 +6,101 @@ extern crate rocket;
 pub mod models;
 pub mod schema;
 
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use std::io::{Error, ErrorKind};
 
-use self::models::{NewGroup, NewNewsletterSubscriber, NewRequestNetworkHash, NewRequestNetworkPayment, NewSerie, NewUser,
-                   NewUserGroup, NewUserRecoverEmail, NewUserSeriesAccess, NewUserSession,
+use self::models::{NewCoupon, NewCouponRedemption, NewCouponSeries, NewGroup, NewMembership, NewNewsletterSubscriber, NewRequestNetworkHash, NewRequestNetworkPayment, NewSerie, NewSeriesPrice, NewUser,
+                   NewUserAuditLog, NewUserCertificate, NewUserGroup, NewUserIdentity, NewUserRecoverEmail, NewUserSeriesAccess, NewUserSession,
                    NewUserStripeCard, NewUserStripeCharge, NewUserStripeCustomer,
-                   NewUserStripeToken, NewUserVerifyEmail, NewUserView, NewVideo,
+                   NewUserStripeToken, NewUserTwoFactor, NewUserTwoFactorRecovery,
+                   NewUserVerifyEmail, NewUserVideoComment, NewUserVideoVote,
+                   NewUserView, NewVideo,
                    RequestNetworkPayments, Users};
 
+/// Creates the coupon and the series it
+/// is restricted to in one transaction.
+/// Returns the id of the new coupon.
+pub fn create_new_coupon(
+    conn: &MysqlConnection,
+    code: &str,
+    kind: &str,
+    value: i32,
+    expires: Option<NaiveDateTime>,
+    max_redemptions: Option<i32>,
+    currency: &str,
+    series_ids: &[i64],
+) -> Result<i64, Error> {
+    use schema::{coupons, coupons_series};
+
+    let new_coupon = NewCoupon {
+        code: code,
+        kind: kind,
+        value: value,
+        expires: expires,
+        max_redemptions: max_redemptions,
+        currency: currency,
+    };
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::insert_into(coupons::table)
+            .values(&new_coupon)
+            .execute(conn)?;
+        let coupon_id = coupons::table
+            .filter(coupons::code.eq(code))
+            .select(coupons::id)
+            .first::<i64>(conn)?;
+        let new_coupons_series: Vec<NewCouponSeries> = series_ids
+            .iter()
+            .map(|series_id| NewCouponSeries {
+                coupon_id: coupon_id,
+                series_id: *series_id,
+            })
+            .collect();
+        if !new_coupons_series.is_empty() {
+            diesel::insert_into(coupons_series::table)
+                .values(&new_coupons_series)
+                .execute(conn)?;
+        }
+        Ok(coupon_id)
+    });
+
+    match result {
+        Ok(coupon_id) => Ok(coupon_id),
+        Err(_) => Err(Error::new(ErrorKind::Other, "No coupons table found")),
+    }
+}
+
+pub fn create_new_coupon_redemption(
+    conn: &MysqlConnection,
+    coupon_id: i64,
+    user_id: i64,
+    series_id: i64,
+    charge_uuid: Option<&str>,
+    discount: i32,
+) -> Result<(), Error> {
+    use schema::coupons_redemptions;
+
+    let new_redemption = NewCouponRedemption {
+        coupon_id: coupon_id,
+        user_id: user_id,
+        series_id: series_id,
+        charge_uuid: charge_uuid,
+        discount: discount,
+    };
+
+    match diesel::insert_into(coupons_redemptions::table)
+        .values(&new_redemption)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No coupons redemptions table found",
+        )),
+    }
+}
+
 pub fn create_new_group(conn: &MysqlConnection, uuid: &str, name: &str) -> Result<(), Error> {
     use schema::groups;
 
@@ -32,6 +118,38 @@ pub fn create_new_group(conn: &MysqlConnection, uuid: &str, name: &str) -> Resul
     }
 }
 
+pub fn create_new_membership(
+    conn: &MysqlConnection,
+    user_id: i64,
+    plan: &str,
+    stripe_subscription_id: &str,
+    status: &str,
+    cancel_at_period_end: bool,
+    current_period_end: i64,
+) -> Result<(), Error> {
+    use schema::memberships;
+
+    let new_membership = NewMembership {
+        user_id: user_id,
+        plan: plan,
+        stripe_subscription_id: stripe_subscription_id,
+        status: status,
+        cancel_at_period_end: cancel_at_period_end,
+        current_period_end: current_period_end,
+    };
+
+    match diesel::insert_into(memberships::table)
+        .values(&new_membership)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No memberships table found",
+        )),
+    }
+}
+
 pub fn create_new_newsletter_subscriber(conn: &MysqlConnection, email: &str) -> Result<(), Error> {
     use schema::newsletter_subscribers;
 
@@ -146,16 +264,47 @@ pub fn create_new_series(
     }
 }
 
+/// Sets the price of the series in the currency,
+/// replacing the price it had in it before.
+pub fn save_series_price(
+    conn: &MysqlConnection,
+    series_id: i64,
+    currency: &str,
+    price: i32,
+) -> Result<(), Error> {
+    use schema::series_prices;
+
+    let new_price = NewSeriesPrice {
+        series_id: series_id,
+        currency: currency,
+        price: price,
+    };
+
+    match diesel::replace_into(series_prices::table)
+        .values(&new_price)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "No series prices table found")),
+    }
+}
+
 pub fn create_new_user_session(
     conn: &MysqlConnection,
     user_id: i64,
     token: &str,
+    ip: &str,
+    user_agent: &str,
+    expires: NaiveDateTime,
 ) -> Result<(), Error> {
     use schema::users_sessions;
 
     let new_user_session = NewUserSession {
         user_id: user_id,
         token: token,
+        ip: ip,
+        user_agent: user_agent,
+        expires: expires,
     };
 
     match diesel::insert_into(users_sessions::table)
@@ -196,6 +345,38 @@ pub fn create_new_user(
     }
 }
 
+pub fn create_new_user_audit_log(
+    conn: &MysqlConnection,
+    user_id: Option<i64>,
+    actor_id: Option<i64>,
+    event: &str,
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+    details: Option<&str>,
+) -> Result<(), Error> {
+    use schema::users_audit_log;
+
+    let new_user_audit_log = NewUserAuditLog {
+        user_id: user_id,
+        actor_id: actor_id,
+        event: event,
+        ip: ip,
+        user_agent: user_agent,
+        details: details,
+    };
+
+    match diesel::insert_into(users_audit_log::table)
+        .values(&new_user_audit_log)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users audit log table found",
+        )),
+    }
+}
+
 pub fn create_new_user_group(
     conn: &MysqlConnection,
     user_id: i64,
@@ -355,6 +536,7 @@ pub fn insert_new_users_stripe_charge(
     source_transfer: Option<&str>,
     statement_descriptor: Option<&str>,
     status: &str,
+    currency: &str,
 ) -> Result<(), Error> {
     use schema::users_stripe_charge;
 
@@ -381,6 +563,7 @@ pub fn insert_new_users_stripe_charge(
         source_transfer: source_transfer,
         statement_descriptor: statement_descriptor,
         status: status,
+        currency: currency,
     };
 
     match diesel::insert_into(users_stripe_charge::table)
@@ -475,12 +658,14 @@ pub fn create_new_users_verify_email(
     conn: &MysqlConnection,
     user_id: i64,
     token: &str,
+    new_email: Option<&str>,
 ) -> Result<(), Error> {
     use schema::users_verify_email;
 
     let new_user_verify_email = NewUserVerifyEmail {
         user_id: user_id,
         token: token,
+        new_email: new_email,
     };
 
     match diesel::insert_into(users_verify_email::table)
@@ -495,6 +680,164 @@ pub fn create_new_users_verify_email(
     }
 }
 
+pub fn create_new_user_certificate(
+    conn: &MysqlConnection,
+    uuid: &str,
+    user_id: i64,
+    serie_id: i64,
+) -> Result<(), Error> {
+    use schema::users_certificates;
+
+    let new_user_certificate = NewUserCertificate {
+        uuid: uuid,
+        user_id: user_id,
+        serie_id: serie_id,
+    };
+
+    match diesel::insert_into(users_certificates::table)
+        .values(&new_user_certificate)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users certificates table found",
+        )),
+    }
+}
+
+pub fn create_new_user_identity(
+    conn: &MysqlConnection,
+    user_id: i64,
+    provider: &str,
+    provider_user_id: &str,
+    provider_username: Option<&str>,
+) -> Result<(), Error> {
+    use schema::users_identities;
+
+    let new_user_identity = NewUserIdentity {
+        user_id: user_id,
+        provider: provider,
+        provider_user_id: provider_user_id,
+        provider_username: provider_username,
+    };
+
+    match diesel::insert_into(users_identities::table)
+        .values(&new_user_identity)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users identities table found",
+        )),
+    }
+}
+
+pub fn create_new_user_two_factor(
+    conn: &MysqlConnection,
+    user_id: i64,
+    secret: &str,
+) -> Result<(), Error> {
+    use schema::users_two_factor;
+
+    let new_user_two_factor = NewUserTwoFactor {
+        user_id: user_id,
+        secret: secret,
+    };
+
+    match diesel::insert_into(users_two_factor::table)
+        .values(&new_user_two_factor)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users two factor table found",
+        )),
+    }
+}
+
+pub fn create_new_user_two_factor_recovery(
+    conn: &MysqlConnection,
+    user_id: i64,
+    code: &str,
+) -> Result<(), Error> {
+    use schema::users_two_factor_recovery;
+
+    let new_user_two_factor_recovery = NewUserTwoFactorRecovery {
+        user_id: user_id,
+        code: code,
+    };
+
+    match diesel::insert_into(users_two_factor_recovery::table)
+        .values(&new_user_two_factor_recovery)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users two factor recovery table found",
+        )),
+    }
+}
+
+pub fn create_new_user_video_comment(
+    conn: &MysqlConnection,
+    uuid: &str,
+    user_id: i64,
+    video_id: i64,
+    parent_id: Option<i64>,
+    body: &str,
+) -> Result<(), Error> {
+    use schema::users_videos_comments;
+
+    let new_user_video_comment = NewUserVideoComment {
+        uuid: uuid,
+        user_id: user_id,
+        video_id: video_id,
+        parent_id: parent_id,
+        body: body,
+    };
+
+    match diesel::insert_into(users_videos_comments::table)
+        .values(&new_user_video_comment)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users videos comments table found",
+        )),
+    }
+}
+
+pub fn create_new_user_video_vote(
+    conn: &MysqlConnection,
+    user_id: i64,
+    video_id: i64,
+    is_like: bool,
+) -> Result<(), Error> {
+    use schema::users_videos_votes;
+
+    let new_user_video_vote = NewUserVideoVote {
+        user_id: user_id,
+        video_id: video_id,
+        is_like: is_like,
+    };
+
+    match diesel::insert_into(users_videos_votes::table)
+        .values(&new_user_video_vote)
+        .execute(conn)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "No users videos votes table found",
+        )),
+    }
+}
+
 pub fn create_new_user_view(
     conn: &MysqlConnection,
     user_id: i64,
@@ -6,6 +6,7 @@ extern crate csrf;
 extern crate data_encoding;
 extern crate diesel;
 extern crate futures;
+extern crate hmac;
 extern crate hyper_tls;
 extern crate r2d2;
 extern crate r2d2_redis;
@@ -15,6 +16,8 @@ extern crate regex;
 extern crate rocket_contrib;
 extern crate serde;
 extern crate serde_json;
+extern crate sha1;
+extern crate sha2;
 extern crate stripe;
 extern crate time;
 extern crate tokio_core;
@@ -27,21 +30,32 @@ extern crate reqwest;
 
 #[macro_use] extern crate serde_derive;
 
+mod account;
 mod admin;
+mod audit;
 mod authentication;
+mod certificates;
 mod charge;
+mod comments;
+mod coupons;
+mod currency;
 mod custom_csrf;
 mod database;
 mod email;
 mod errors;
+mod oauth;
 mod pages;
+mod passwords;
 mod payment;
 mod request_network;
 mod series;
+mod sessions;
 mod settings;
 mod structs;
+mod two_factor;
 mod users;
 mod videos;
+mod webhooks;
 
 pub fn website() -> rocket::Rocket {
     rocket::ignite()
@@ -50,16 +64,30 @@ pub fn website() -> rocket::Rocket {
         .mount("/", pages::endpoints())
         .mount("/", videos::endpoints())
         .mount("/", charge::endpoints())
+        .mount("/", comments::endpoints())
+        .mount("/", sessions::endpoints())
+        .mount("/", account::endpoints())
+        .mount("/", two_factor::endpoints())
+        .mount("/", oauth::endpoints())
+        .mount("/", audit::endpoints())
+        .mount("/", webhooks::endpoints())
         .mount("/settings/payment", payment::endpoints())
         .mount("/series", series::endpoints())
+        .mount("/certificates", certificates::endpoints())
         .mount("/admin", admin::endpoints())
         .attach(rocket_contrib::templates::Template::fairing())
         .attach(custom_csrf::csrf_secret_key_fairing())
         .attach(database::mysql_fairing())
         .attach(database::redis_fairing())
         .attach(structs::stripe_token_fairing())
+        .attach(payment::membership::membership_plans_fairing())
         .attach(structs::postmark_token_fairing())
         .attach(structs::email_regex_fairing())
+        .attach(structs::token_lifetime_fairing())
+        .attach(passwords::bcrypt_cost_fairing())
+        .attach(passwords::password_policy_fairing())
+        .attach(oauth::client::oauth_providers_fairing())
+        .attach(authentication::token_cleanup_fairing())
         .register(errors::endpoints())
 }
 
@@ -3,6 +3,66 @@ extern crate chrono;
 use schema::*;
 use self::chrono::NaiveDateTime;
 
+#[derive(Queryable, Clone)]
+pub struct Coupons {
+    pub id: i64,
+    pub code: String,
+    pub kind: String,
+    pub value: i32,
+    pub expires: Option<NaiveDateTime>,
+    pub max_redemptions: Option<i32>,
+    pub times_redeemed: i32,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub currency: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "coupons"]
+pub struct NewCoupon<'a> {
+    pub code: &'a str,
+    pub kind: &'a str,
+    pub value: i32,
+    pub expires: Option<NaiveDateTime>,
+    pub max_redemptions: Option<i32>,
+    pub currency: &'a str,
+}
+
+#[derive(Queryable)]
+pub struct CouponsRedemptions {
+    pub id: i64,
+    pub coupon_id: i64,
+    pub user_id: i64,
+    pub series_id: i64,
+    pub charge_uuid: Option<String>,
+    pub discount: i32,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "coupons_redemptions"]
+pub struct NewCouponRedemption<'a> {
+    pub coupon_id: i64,
+    pub user_id: i64,
+    pub series_id: i64,
+    pub charge_uuid: Option<&'a str>,
+    pub discount: i32,
+}
+
+#[derive(Queryable)]
+pub struct CouponsSeries {
+    pub id: i64,
+    pub coupon_id: i64,
+    pub series_id: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "coupons_series"]
+pub struct NewCouponSeries {
+    pub coupon_id: i64,
+    pub series_id: i64,
+}
+
 #[derive(Queryable, Clone)]
 pub struct Groups {
     pub id: i64,
@@ -19,6 +79,30 @@ pub struct NewGroup<'a> {
     pub name: &'a str,
 }
 
+#[derive(Queryable)]
+pub struct Memberships {
+    pub id: i64,
+    pub user_id: i64,
+    pub plan: String,
+    pub stripe_subscription_id: String,
+    pub status: String,
+    pub cancel_at_period_end: bool,
+    pub current_period_end: i64,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "memberships"]
+pub struct NewMembership<'a> {
+    pub user_id: i64,
+    pub plan: &'a str,
+    pub stripe_subscription_id: &'a str,
+    pub status: &'a str,
+    pub cancel_at_period_end: bool,
+    pub current_period_end: i64,
+}
+
 #[derive(Queryable, Clone)]
 pub struct NewsletterSubscribers {
     pub id: i64,
@@ -102,6 +186,24 @@ pub struct NewSerie<'a> {
     pub archived: bool,
 }
 
+#[derive(Queryable, Clone)]
+pub struct SeriesPrices {
+    pub id: i64,
+    pub series_id: i64,
+    pub currency: String,
+    pub price: i32,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "series_prices"]
+pub struct NewSeriesPrice<'a> {
+    pub series_id: i64,
+    pub currency: &'a str,
+    pub price: i32,
+}
+
 #[derive(Queryable, Clone)]
 pub struct Users {
     pub id: i64,
@@ -111,6 +213,7 @@ pub struct Users {
     pub verified: bool,
     pub created: NaiveDateTime,
     pub updated: NaiveDateTime,
+    pub currency: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -121,6 +224,58 @@ pub struct NewUser<'a> {
     pub email: &'a str,
 }
 
+#[derive(Queryable)]
+pub struct UsersAuditLog {
+    pub id: i64,
+    pub user_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub event: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<String>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_audit_log"]
+pub struct NewUserAuditLog<'a> {
+    pub user_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub event: &'a str,
+    pub ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub details: Option<&'a str>,
+}
+
+#[derive(Queryable)]
+pub struct UsersCertificates {
+    pub id: i64,
+    pub uuid: String,
+    pub user_id: i64,
+    pub serie_id: i64,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_certificates"]
+pub struct NewUserCertificate<'a> {
+    pub uuid: &'a str,
+    pub user_id: i64,
+    pub serie_id: i64,
+}
+
+#[derive(Queryable)]
+pub struct CertificateJoin {
+    pub uuid: String,
+    pub user_id: i64,
+    pub serie_id: i64,
+    pub username: String,
+    pub serie_uuid: String,
+    pub serie_title: String,
+    pub created: NaiveDateTime,
+}
+
 #[derive(Queryable, Clone)]
 pub struct UsersGroup {
     pub id: i64,
@@ -251,6 +406,8 @@ pub struct UsersStripeCharge {
     pub source_transfer: Option<String>,
     pub statement_descriptor: Option<String>,
     pub status: String,
+    pub currency: String,
+    pub last_event_at: i64,
 }
 
 #[derive(Insertable)]
@@ -278,6 +435,7 @@ pub struct NewUserStripeCharge<'a> {
     pub source_transfer: Option<&'a str>,
     pub statement_descriptor: Option<&'a str>,
     pub status: &'a str,
+    pub currency: &'a str,
 }
 
 #[derive(Queryable, Clone)]
@@ -347,6 +505,10 @@ pub struct UsersSessions {
     pub token: String,
     pub created: NaiveDateTime,
     pub updated: NaiveDateTime,
+    pub ip: String,
+    pub user_agent: String,
+    pub last_seen: NaiveDateTime,
+    pub expires: NaiveDateTime,
 }
 
 #[derive(Queryable)]
@@ -361,6 +523,64 @@ pub struct UsersAndSessions {
 pub struct NewUserSession<'a> {
     pub user_id: i64,
     pub token: &'a str,
+    pub ip: &'a str,
+    pub user_agent: &'a str,
+    pub expires: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct UsersIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub provider_username: Option<String>,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_identities"]
+pub struct NewUserIdentity<'a> {
+    pub user_id: i64,
+    pub provider: &'a str,
+    pub provider_user_id: &'a str,
+    pub provider_username: Option<&'a str>,
+}
+
+#[derive(Queryable)]
+pub struct UsersTwoFactor {
+    pub id: i64,
+    pub user_id: i64,
+    pub secret: String,
+    pub enabled: bool,
+    pub last_step: i64,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_two_factor"]
+pub struct NewUserTwoFactor<'a> {
+    pub user_id: i64,
+    pub secret: &'a str,
+}
+
+#[derive(Queryable)]
+pub struct UsersTwoFactorRecovery {
+    pub id: i64,
+    pub user_id: i64,
+    pub code: String,
+    pub used: bool,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_two_factor_recovery"]
+pub struct NewUserTwoFactorRecovery<'a> {
+    pub user_id: i64,
+    pub code: &'a str,
 }
 
 #[derive(Queryable)]
@@ -371,6 +591,7 @@ pub struct UsersVerifyEmail {
     pub used: bool,
     pub created: NaiveDateTime,
     pub updated: NaiveDateTime,
+    pub new_email: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -378,6 +599,49 @@ pub struct UsersVerifyEmail {
 pub struct NewUserVerifyEmail<'a> {
     pub user_id: i64,
     pub token: &'a str,
+    pub new_email: Option<&'a str>,
+}
+
+#[derive(Queryable)]
+pub struct UsersVideosComments {
+    pub id: i64,
+    pub uuid: String,
+    pub user_id: i64,
+    pub video_id: i64,
+    pub parent_id: Option<i64>,
+    pub body: String,
+    pub hidden: bool,
+    pub deleted: bool,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_videos_comments"]
+pub struct NewUserVideoComment<'a> {
+    pub uuid: &'a str,
+    pub user_id: i64,
+    pub video_id: i64,
+    pub parent_id: Option<i64>,
+    pub body: &'a str,
+}
+
+#[derive(Queryable)]
+pub struct UsersVideosVotes {
+    pub id: i64,
+    pub user_id: i64,
+    pub video_id: i64,
+    pub is_like: bool,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users_videos_votes"]
+pub struct NewUserVideoVote {
+    pub user_id: i64,
+    pub video_id: i64,
+    pub is_like: bool,
 }
 
 #[derive(Queryable)]
@@ -387,6 +651,9 @@ pub struct UsersViews {
     pub video_id: i64,
     pub created: NaiveDateTime,
     pub updated: NaiveDateTime,
+    pub seconds: i32,
+    pub duration: i32,
+    pub completed: bool,
 }
 
 #[derive(Insertable)]
@@ -441,3 +708,117 @@ pub struct VideoJoin {
     pub price: i32,
     pub in_development: bool,
 }
+
+#[derive(Queryable)]
+pub struct ViewJoin {
+    pub created: NaiveDateTime,
+    pub video_uuid: String,
+    pub video_title: String,
+    pub episode_number: i32,
+    pub serie_uuid: String,
+    pub serie_title: String,
+}
+
+#[derive(Queryable)]
+pub struct CommentJoin {
+    pub id: i64,
+    pub uuid: String,
+    pub user_id: i64,
+    pub username: String,
+    pub parent_id: Option<i64>,
+    pub body: String,
+    pub hidden: bool,
+    pub deleted: bool,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct AdminCommentJoin {
+    pub uuid: String,
+    pub username: String,
+    pub video_uuid: String,
+    pub video_title: String,
+    pub body: String,
+    pub hidden: bool,
+    pub deleted: bool,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct SessionExport {
+    pub ip: String,
+    pub user_agent: String,
+    pub created: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    pub expires: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct ViewExport {
+    pub video: String,
+    pub seconds: i32,
+    pub duration: i32,
+    pub completed: bool,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct VoteExport {
+    pub video: String,
+    pub is_like: bool,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct CommentExport {
+    pub video: String,
+    pub body: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct SeriesAccessExport {
+    pub serie: String,
+    pub bought: bool,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct CertificateExport {
+    pub uuid: String,
+    pub serie: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Queryable)]
+pub struct ChargeExport {
+    pub uuid: String,
+    pub serie: String,
+    pub amount: i32,
+    pub currency: String,
+    pub amount_refunded: i32,
+    pub refunded: bool,
+    pub status: String,
+    pub created_at_stripe: i64,
+}
+
+#[derive(Queryable)]
+pub struct CardExport {
+    pub brand: String,
+    pub country: String,
+    pub last4: String,
+    pub exp_month: i32,
+    pub exp_year: i32,
+    pub name: Option<String>,
+}
+
+#[derive(Queryable)]
+pub struct AuditEventExport {
+    pub event: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Option<String>,
+    pub created: NaiveDateTime,
+}
@@ -0,0 +1,321 @@
+use reqwest::header::{ACCEPT, AUTHORIZATION, USER_AGENT};
+use reqwest::Url;
+use rocket::fairing::AdHoc;
+use serde_json::Value;
+use std::io::{Error, ErrorKind};
+
+/// User agent sent to the providers.
+/// GitHub rejects requests without one.
+static CLIENT_USER_AGENT: &'static str = "club-coding";
+
+/// Endpoints and credentials of
+/// an OAuth2 provider.
+pub struct OAuthProvider {
+    /// Name of the provider used in
+    /// URLs and stored with identities.
+    pub name: String,
+    /// Client id of the application
+    /// registered at the provider.
+    pub client_id: String,
+    /// Client secret of the application
+    /// registered at the provider.
+    pub client_secret: String,
+    /// URL the user is sent to
+    /// for authorizing the application.
+    pub authorize_url: String,
+    /// URL the code is exchanged
+    /// for an access token at.
+    pub token_url: String,
+    /// URL returning the
+    /// user of the token.
+    pub user_url: String,
+    /// URL returning the emails of the
+    /// user, for providers that don't
+    /// include verified emails in the user.
+    pub emails_url: Option<String>,
+    /// Scopes asked for.
+    pub scope: String,
+}
+
+/// Struct for the configured OAuth2
+/// providers. Used in the endpoints
+/// to sign in with a provider.
+pub struct OAuthProviders {
+    /// URL of the site the providers
+    /// redirect back to, for example
+    /// https://clubcoding.com
+    pub redirect_base: String,
+    /// The configured providers.
+    pub providers: Vec<OAuthProvider>,
+}
+
+impl OAuthProviders {
+    /// Gets the provider with the name.
+    pub fn get(&self, name: &str) -> Option<&OAuthProvider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    /// The names of all of the providers.
+    pub fn names(&self) -> Vec<String> {
+        self.providers
+            .iter()
+            .map(|provider| provider.name.clone())
+            .collect()
+    }
+
+    /// The URL the provider redirects
+    /// back to after authorizing.
+    pub fn redirect_uri(&self, provider: &OAuthProvider) -> String {
+        format!(
+            "{}/login/oauth/{}/callback",
+            self.redirect_base.trim_right_matches('/'),
+            provider.name
+        )
+    }
+}
+
+/// Default endpoints and scopes for the providers
+/// we know about. Other providers need every endpoint
+/// to be set in Rocket.toml File.
+fn default_setting(name: &str, field: &str) -> Option<&'static str> {
+    match (name, field) {
+        ("github", "authorize_url") => Some("https://github.com/login/oauth/authorize"),
+        ("github", "token_url") => Some("https://github.com/login/oauth/access_token"),
+        ("github", "user_url") => Some("https://api.github.com/user"),
+        ("github", "emails_url") => Some("https://api.github.com/user/emails"),
+        ("github", "scope") => Some("read:user user:email"),
+        _ => None,
+    }
+}
+
+/// Returns a AdHoc Fairing with the OAuth2 Providers.
+/// The providers are listed in oauth_providers and each
+/// one is configured with oauth_<name>_client_id and
+/// oauth_<name>_client_secret. The endpoints can be
+/// changed with oauth_<name>_authorize_url, _token_url,
+/// _user_url and _emails_url, for example to test against
+/// a local server. Providers without a client id are
+/// skipped. Will panic if an endpoint is missing for a
+/// provider without defaults.
+pub fn oauth_providers_fairing() -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("OAuthProviders", |rocket| {
+        let config = rocket.config().clone();
+
+        let redirect_base = config
+            .get_str("oauth_redirect_base")
+            .unwrap_or("https://clubcoding.com")
+            .to_string();
+
+        let names: Vec<String> = match config.get_slice("oauth_providers") {
+            Ok(names) => names
+                .iter()
+                .filter_map(|name| name.as_str().map(|name| name.to_string()))
+                .collect(),
+            Err(_) => vec![],
+        };
+
+        let mut providers = vec![];
+        for name in names {
+            let key = |field: &str| format!("oauth_{}_{}", name, field);
+            let client_id = match config.get_str(&key("client_id")) {
+                Ok(client_id) if !client_id.is_empty() => client_id.to_string(),
+                _ => continue,
+            };
+            let client_secret = config
+                .get_str(&key("client_secret"))
+                .expect("oauth client_secret not specified")
+                .to_string();
+
+            let setting = |field: &str| match config.get_str(&key(field)) {
+                Ok(value) => Some(value.to_string()),
+                Err(_) => default_setting(&name, field).map(|value| value.to_string()),
+            };
+
+            providers.push(OAuthProvider {
+                authorize_url: setting("authorize_url").expect("oauth authorize_url not specified"),
+                token_url: setting("token_url").expect("oauth token_url not specified"),
+                user_url: setting("user_url").expect("oauth user_url not specified"),
+                emails_url: setting("emails_url"),
+                scope: setting("scope").unwrap_or("".to_string()),
+                name: name.clone(),
+                client_id: client_id,
+                client_secret: client_secret,
+            });
+        }
+
+        Ok(rocket.manage(OAuthProviders {
+            redirect_base: redirect_base,
+            providers: providers,
+        }))
+    })
+}
+
+/// The user at the provider.
+#[derive(Debug, PartialEq)]
+pub struct ProviderUser {
+    /// Id of the user at the provider.
+    pub id: String,
+    /// Username at the provider, if any.
+    pub username: Option<String>,
+    /// An email the provider
+    /// has verified, if any.
+    pub email: Option<String>,
+}
+
+/// Builds the URL the user is sent to
+/// for authorizing the application.
+pub fn authorize_url(
+    provider: &OAuthProvider,
+    redirect_uri: &str,
+    state: &str,
+) -> Result<String, Error> {
+    match Url::parse_with_params(
+        &provider.authorize_url,
+        &[
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", provider.scope.as_str()),
+            ("state", state),
+            ("response_type", "code"),
+        ],
+    ) {
+        Ok(url) => Ok(url.into_string()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Invalid authorize URL.")),
+    }
+}
+
+/// Sends the request and parses
+/// the response as JSON.
+fn send_json(request: reqwest::RequestBuilder) -> Result<Value, Error> {
+    let mut res = match request
+        .header(ACCEPT, "application/json")
+        .header(USER_AGENT, CLIENT_USER_AGENT)
+        .send()
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not connect to server at URL.",
+            ))
+        }
+    };
+
+    if !res.status().is_success() {
+        return Err(Error::new(ErrorKind::Other, "Provider returned an error."));
+    }
+
+    let text = match res.text() {
+        Ok(text) => text,
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not read response.")),
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not serialize response.",
+        )),
+    }
+}
+
+/// Exchanges the code from the provider
+/// for an access token.
+pub fn exchange_code(
+    provider: &OAuthProvider,
+    redirect_uri: &str,
+    code: &str,
+) -> Result<String, Error> {
+    let client = reqwest::Client::new();
+    let value = send_json(client.post(&provider.token_url).form(&[
+        ("client_id", provider.client_id.as_str()),
+        ("client_secret", provider.client_secret.as_str()),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("grant_type", "authorization_code"),
+    ]))?;
+
+    match value.get("access_token").and_then(|token| token.as_str()) {
+        Some(token) => Ok(token.to_string()),
+        None => Err(Error::new(ErrorKind::Other, "No access token in response.")),
+    }
+}
+
+/// Gets the user of the access token.
+/// The email is taken from the emails
+/// endpoint if the provider has one,
+/// otherwise from the user if the
+/// provider says it is verified.
+pub fn get_user(provider: &OAuthProvider, access_token: &str) -> Result<ProviderUser, Error> {
+    let client = reqwest::Client::new();
+    let authorization = format!("Bearer {}", access_token);
+
+    let user = send_json(
+        client
+            .get(&provider.user_url)
+            .header(AUTHORIZATION, authorization.as_str()),
+    )?;
+
+    let emails = match provider.emails_url {
+        Some(ref emails_url) => Some(send_json(
+            client
+                .get(emails_url)
+                .header(AUTHORIZATION, authorization.as_str()),
+        )?),
+        None => None,
+    };
+
+    match parse_user(&user, emails.as_ref()) {
+        Some(user) => Ok(user),
+        None => Err(Error::new(ErrorKind::Other, "Could not read user.")),
+    }
+}
+
+/// Reads the user from the JSON of the provider.
+/// Ids can be numbers or strings. Only verified
+/// emails are used and the primary one is
+/// preferred. Returns None if there is no id.
+pub fn parse_user(user: &Value, emails: Option<&Value>) -> Option<ProviderUser> {
+    let id = match user.get("id") {
+        Some(&Value::Number(ref id)) => id.to_string(),
+        Some(&Value::String(ref id)) => id.clone(),
+        _ => return None,
+    };
+
+    let username = ["login", "username", "preferred_username"]
+        .iter()
+        .filter_map(|field| user.get(*field).and_then(|name| name.as_str()))
+        .next()
+        .map(|name| name.to_string());
+
+    let email = match emails.and_then(|emails| emails.as_array()) {
+        Some(emails) => {
+            let verified: Vec<&Value> = emails
+                .iter()
+                .filter(|email| email.get("verified").and_then(|v| v.as_bool()) == Some(true))
+                .collect();
+            verified
+                .iter()
+                .find(|email| email.get("primary").and_then(|p| p.as_bool()) == Some(true))
+                .or(verified.first())
+                .and_then(|email| email.get("email"))
+                .and_then(|email| email.as_str())
+                .map(|email| email.trim().to_lowercase())
+        }
+        None => {
+            if user.get("email_verified").and_then(|v| v.as_bool()) == Some(true) {
+                user.get("email")
+                    .and_then(|email| email.as_str())
+                    .map(|email| email.trim().to_lowercase())
+            } else {
+                None
+            }
+        }
+    };
+
+    Some(ProviderUser {
+        id: id,
+        username: username,
+        email: email,
+    })
+}
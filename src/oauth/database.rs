@@ -0,0 +1,96 @@
+use club_coding::models::{Users, UsersIdentity};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel;
+use diesel::prelude::*;
+
+/// Gets the identity linked to the
+/// user at the provider, if any.
+pub fn get_identity(
+    connection: &DbConn,
+    provider_name: &str,
+    uid: &str,
+) -> Option<UsersIdentity> {
+    use club_coding::schema::users_identities::dsl::*;
+
+    match users_identities
+        .filter(provider.eq(provider_name))
+        .filter(provider_user_id.eq(uid))
+        .first::<UsersIdentity>(&**connection)
+    {
+        Ok(identity) => Some(identity),
+        Err(_) => None,
+    }
+}
+
+/// Gets every identity linked
+/// to the user.
+pub fn get_identities(connection: &DbConn, uid: i64) -> Vec<UsersIdentity> {
+    use club_coding::schema::users_identities::dsl::*;
+
+    match users_identities
+        .filter(user_id.eq(uid))
+        .load::<UsersIdentity>(&**connection)
+    {
+        Ok(identities) => identities,
+        Err(_) => vec![],
+    }
+}
+
+/// Removes the identity of the
+/// provider from the user.
+pub fn delete_identity(connection: &DbConn, uid: i64, provider_name: &str) -> Result<(), Error> {
+    use club_coding::schema::users_identities::dsl::*;
+
+    match diesel::delete(
+        users_identities
+            .filter(user_id.eq(uid))
+            .filter(provider.eq(provider_name)),
+    ).execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not remove identity.",
+        )),
+    }
+}
+
+/// Gets the user with the email, whether
+/// the user is verified or not.
+pub fn get_user_by_email(connection: &DbConn, name: &str) -> Option<Users> {
+    use club_coding::schema::users::dsl::*;
+
+    match users
+        .filter(email.eq(name.trim().to_lowercase()))
+        .first::<Users>(&**connection)
+    {
+        Ok(user) => Some(user),
+        Err(_) => None,
+    }
+}
+
+/// Checks if the username is taken.
+pub fn username_exists(connection: &DbConn, name: &str) -> bool {
+    use club_coding::schema::users::dsl::*;
+
+    match users.filter(username.eq(name)).first::<Users>(&**connection) {
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Marks the user as verified. Used for
+/// accounts created with a provider that
+/// has already verified the email.
+pub fn verify_user(connection: &DbConn, uid: i64) -> Result<(), Error> {
+    use club_coding::schema::users::dsl::*;
+
+    match diesel::update(users.find(uid))
+        .set(verified.eq(true))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not verify user.")),
+    }
+}
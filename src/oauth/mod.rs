@@ -0,0 +1,625 @@
+pub mod client;
+pub mod database;
+
+use audit::{self, AuditEvent};
+use authentication;
+use club_coding::{create_new_user, create_new_user_identity};
+use database::{DbConn, RedisConnection};
+use diesel;
+use diesel::Connection;
+use passwords::{self, BcryptCost};
+use redis::Commands;
+use rocket::http::{Cookie, Cookies};
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use sessions::{self, ClientInfo};
+use std::io::{Error, ErrorKind};
+use two_factor;
+use users::User;
+
+use self::client::{OAuthProvider, OAuthProviders, ProviderUser};
+
+#[cfg(test)]
+mod tests;
+
+/// Number of seconds the user has to
+/// authorize the application at the
+/// provider.
+static PENDING_AUTHORIZATION_TTL: usize = 600;
+
+/// The authorization that has been started
+/// and is waiting for the provider to
+/// redirect back.
+#[derive(Serialize, Deserialize)]
+struct PendingAuthorization {
+    /// Name of the provider.
+    provider: String,
+    /// The user connecting the provider
+    /// from settings, or None when
+    /// signing in.
+    user_id: Option<i64>,
+}
+
+/// Redis key storing the
+/// pending authorization.
+fn state_key(state: &str) -> String {
+    format!("oauth_state:{}", state)
+}
+
+/// Stores the pending authorization in Redis
+/// and the state in a cookie so only the browser
+/// that started it can finish it. Returns the URL
+/// to send the user to at the provider.
+fn begin_authorization(
+    providers: &OAuthProviders,
+    provider: &OAuthProvider,
+    redis_conn: &RedisConnection,
+    cookies: &mut Cookies,
+    user_id: Option<i64>,
+) -> Result<String, Error> {
+    let state = authentication::generate_token(30);
+    let pending = PendingAuthorization {
+        provider: provider.name.clone(),
+        user_id: user_id,
+    };
+    let value = serde_json::to_string(&pending)?;
+    match redis_conn.set_ex::<String, String, String>(
+        state_key(&state),
+        value,
+        PENDING_AUTHORIZATION_TTL,
+    ) {
+        Ok(_) => {}
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not store state.")),
+    }
+    cookies.add_private(Cookie::new("oauth_state", state.clone()));
+    client::authorize_url(provider, &providers.redirect_uri(provider), &state)
+}
+
+/// Gets the pending authorization of the state
+/// and removes it so it can only be used once.
+fn take_authorization(redis_conn: &RedisConnection, state: &str) -> Option<PendingAuthorization> {
+    let key = state_key(state);
+    let value = match redis_conn.get::<&str, Option<String>>(&key) {
+        Ok(Some(value)) => value,
+        _ => return None,
+    };
+    match redis_conn.del::<&str, i64>(&key) {
+        Ok(1) => serde_json::from_str(&value).ok(),
+        _ => None,
+    }
+}
+
+/// Turns the username at the provider into
+/// a username we allow. Usernames can't
+/// contain @ as they would be read as emails
+/// when logging in.
+pub fn username_candidate(name: Option<&str>) -> String {
+    let name: String = name
+        .unwrap_or("")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(30)
+        .collect();
+    if name.is_empty() {
+        "learner".to_string()
+    } else {
+        name
+    }
+}
+
+/// Finds a username that is not taken by
+/// adding random numbers to the candidate.
+fn available_username(connection: &DbConn, name: Option<&str>) -> Option<String> {
+    let candidate = username_candidate(name);
+    if !database::username_exists(connection, &candidate) {
+        return Some(candidate);
+    }
+    for _ in 0..10 {
+        let username = format!("{}-{}", candidate, rand::random::<u16>());
+        if !database::username_exists(connection, &username) {
+            return Some(username);
+        }
+    }
+    None
+}
+
+/// Creates a verified account for the user at
+/// the provider. The password is random so the
+/// account can only be signed in to with the
+/// provider until the user recovers it.
+/// The user, verification and identity are
+/// stored in one transaction so a failure
+/// doesn't leave an unverified user behind.
+fn create_account(
+    connection: &DbConn,
+    provider: &OAuthProvider,
+    provider_user: &ProviderUser,
+    email: &str,
+    cost: u32,
+) -> Result<i64, Error> {
+    let provider_username = provider_user.username.as_ref().map(|name| name.as_str());
+    let username = match available_username(connection, provider_username) {
+        Some(username) => username,
+        None => return Err(Error::new(ErrorKind::Other, "No username available.")),
+    };
+    let hashed_password =
+        passwords::hash_password(&authentication::generate_token(30), cost)?;
+
+    let created = connection.transaction::<_, diesel::result::Error, _>(|| {
+        let new_user = create_new_user(&**connection, &username, &hashed_password, email)
+            .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        database::verify_user(connection, new_user.id)
+            .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        create_new_user_identity(
+            &**connection,
+            new_user.id,
+            &provider.name,
+            &provider_user.id,
+            provider_username,
+        ).map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        Ok(new_user.id)
+    });
+    match created {
+        Ok(user_id) => Ok(user_id),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not create account.")),
+    }
+}
+
+/// Logs the user in the same way as logging in
+/// with a password, asking for the two-factor
+/// code first if the user has it enabled.
+fn finish_login(
+    conn: &DbConn,
+    redis_conn: &RedisConnection,
+    client: &ClientInfo,
+    cookies: &mut Cookies,
+    provider: &OAuthProvider,
+    user_id: i64,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if two_factor::database::is_enabled(conn, user_id) {
+        return match two_factor::begin_login(redis_conn, cookies, user_id) {
+            Ok(_) => Ok(Flash::success(
+                Redirect::to("/login/two_factor"),
+                "Enter the code from your authenticator app.",
+            )),
+            Err(_) => Err(Flash::error(
+                Redirect::to("/login"),
+                "An error occured, please try again later.",
+            )),
+        };
+    }
+
+    match sessions::start_session(conn, client, cookies, user_id) {
+        Ok(_) => {
+            audit::record(
+                conn,
+                AuditEvent::Login,
+                Some(user_id),
+                None,
+                Some(client),
+                Some(&provider.name),
+            );
+            Ok(Flash::success(Redirect::to("/"), "You're now logged in."))
+        }
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// Signs in with the user at the provider. Uses
+/// the linked account if there is one, otherwise
+/// links the verified account with the same email
+/// or creates a new account.
+fn sign_in(
+    conn: &DbConn,
+    redis_conn: &RedisConnection,
+    client: &ClientInfo,
+    cookies: &mut Cookies,
+    provider: &OAuthProvider,
+    provider_user: &ProviderUser,
+    cost: u32,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if let Some(identity) = database::get_identity(conn, &provider.name, &provider_user.id) {
+        return finish_login(conn, redis_conn, client, cookies, provider, identity.user_id);
+    }
+
+    let email = match provider_user.email {
+        Some(ref email) => email,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/login"),
+                "Your account at the provider has no verified email.",
+            ))
+        }
+    };
+
+    let user_id = match database::get_user_by_email(conn, email) {
+        Some(user) => {
+            if !user.verified {
+                return Err(Flash::error(
+                    Redirect::to("/login"),
+                    "An account with this email has not been verified yet. Verify it and connect the provider from settings.",
+                ));
+            }
+            match create_new_user_identity(
+                &**conn,
+                user.id,
+                &provider.name,
+                &provider_user.id,
+                provider_user.username.as_ref().map(|name| name.as_str()),
+            ) {
+                Ok(_) => user.id,
+                Err(_) => {
+                    return Err(Flash::error(
+                        Redirect::to("/login"),
+                        "Your account is already connected to another account at the provider.",
+                    ))
+                }
+            }
+        }
+        None => match create_account(conn, provider, provider_user, email, cost) {
+            Ok(user_id) => user_id,
+            Err(_) => {
+                return Err(Flash::error(
+                    Redirect::to("/login"),
+                    "An error occured, please try again later.",
+                ))
+            }
+        },
+    };
+
+    finish_login(conn, redis_conn, client, cookies, provider, user_id)
+}
+
+/// Connects the user at the provider
+/// to the user that is logged in.
+fn connect(
+    conn: &DbConn,
+    user: &User,
+    provider: &OAuthProvider,
+    provider_user: &ProviderUser,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if let Some(identity) = database::get_identity(conn, &provider.name, &provider_user.id) {
+        if identity.user_id == user.id {
+            return Ok(Flash::success(
+                Redirect::to("/settings/connections"),
+                "The account is already connected.",
+            ));
+        }
+        return Err(Flash::error(
+            Redirect::to("/settings/connections"),
+            "The account at the provider is connected to another user.",
+        ));
+    }
+
+    match create_new_user_identity(
+        &**conn,
+        user.id,
+        &provider.name,
+        &provider_user.id,
+        provider_user.username.as_ref().map(|name| name.as_str()),
+    ) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/settings/connections"),
+            "The account has been connected.",
+        )),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/settings/connections"),
+            "Disconnect your current account at the provider first.",
+        )),
+    }
+}
+
+/// GET Endpoint to sign in with a provider.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// logged in it redirect the
+/// user to the index, otherwise
+/// it forwards the request.
+#[get("/login/oauth/<_provider>")]
+fn oauth_login_loggedin(_provider: String, _user: User) -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint to sign in with a provider.
+/// This endpoint will kick in if the user is
+/// not logged in. Redirects the user to the
+/// provider to authorize the application.
+#[get("/login/oauth/<provider>", rank = 2)]
+fn oauth_login(
+    providers: State<OAuthProviders>,
+    redis_conn: RedisConnection,
+    mut cookies: Cookies,
+    provider: String,
+) -> Result<Redirect, Flash<Redirect>> {
+    let provider = match providers.get(&provider) {
+        Some(provider) => provider,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/login"),
+                "Unknown provider.",
+            ))
+        }
+    };
+    match begin_authorization(&providers, provider, &redis_conn, &mut cookies, None) {
+        Ok(url) => Ok(Redirect::to(url)),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// GET Endpoint the provider redirects back to.
+/// Checks that the state belongs to this browser,
+/// exchanges the code for an access token and gets
+/// the user at the provider. Either connects the
+/// provider to the logged in user or signs in,
+/// depending on where the authorization was
+/// started. Redirects with an appropriate message.
+#[get("/login/oauth/<provider>/callback?<code>&<state>")]
+fn oauth_callback(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    providers: State<OAuthProviders>,
+    bcrypt_cost: State<BcryptCost>,
+    client_info: ClientInfo,
+    mut cookies: Cookies,
+    user: Option<User>,
+    provider: String,
+    code: Option<String>,
+    state: Option<String>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let failed = || {
+        Flash::error(
+            Redirect::to("/login"),
+            "Signing in with the provider failed.",
+        )
+    };
+
+    let cookie_state = cookies
+        .get_private("oauth_state")
+        .map(|cookie| cookie.value().to_string());
+    cookies.remove_private(Cookie::named("oauth_state"));
+
+    let (code, state) = match (code, state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(failed()),
+    };
+    if cookie_state.as_ref() != Some(&state) {
+        return Err(failed());
+    }
+    let pending = match take_authorization(&redis_conn, &state) {
+        Some(pending) => pending,
+        None => return Err(failed()),
+    };
+    if pending.provider != provider {
+        return Err(failed());
+    }
+    let provider = match providers.get(&provider) {
+        Some(provider) => provider,
+        None => return Err(failed()),
+    };
+
+    let provider_user = match client::exchange_code(
+        provider,
+        &providers.redirect_uri(provider),
+        &code,
+    ).and_then(|access_token| client::get_user(provider, &access_token))
+    {
+        Ok(provider_user) => provider_user,
+        Err(_) => return Err(failed()),
+    };
+
+    match (pending.user_id, user) {
+        (Some(user_id), Some(user)) => {
+            if user.id != user_id {
+                return Err(failed());
+            }
+            connect(&conn, &user, provider, &provider_user)
+        }
+        (None, None) => sign_in(
+            &conn,
+            &redis_conn,
+            &client_info,
+            &mut cookies,
+            provider,
+            &provider_user,
+            bcrypt_cost.0,
+        ),
+        _ => Err(failed()),
+    }
+}
+
+/// A provider on the
+/// connections page.
+#[derive(Serialize)]
+struct Connection {
+    /// Name of the provider.
+    provider: String,
+    /// Boolean of whether the
+    /// user has connected it.
+    connected: bool,
+    /// The username at the
+    /// provider, if connected.
+    username: Option<String>,
+}
+
+/// Context for rendering tera templates
+/// for the connections settings endpoint.
+#[derive(Serialize)]
+struct ConnectionsContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// Every configured provider.
+    connections: Vec<Connection>,
+    /// Flash name if the request is redirected
+    /// with one.
+    flash_name: String,
+    /// Flash message if the request is redirected
+    /// with one.
+    flash_msg: String,
+}
+
+/// GET Endpoint for the page to connect
+/// and disconnect providers. Endpoints
+/// checks if the user is logged in by
+/// using the user request guard. If the
+/// user is not logged in it forwards the
+/// request.
+/// Takes in an optional FlashMessage
+/// incase there is one.
+/// Responds with the Connections Template
+/// in the settings folder.
+#[get("/settings/connections")]
+fn connections_page(
+    conn: DbConn,
+    providers: State<OAuthProviders>,
+    user: User,
+    flash: Option<FlashMessage>,
+) -> Template {
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
+    let identities = database::get_identities(&conn, user.id);
+    let connections = providers
+        .names()
+        .into_iter()
+        .map(|name| {
+            let identity = identities.iter().find(|identity| identity.provider == name);
+            Connection {
+                connected: identity.is_some(),
+                username: identity.and_then(|identity| identity.provider_username.clone()),
+                provider: name,
+            }
+        })
+        .collect();
+    let context = ConnectionsContext {
+        header: "Connections",
+        user: user,
+        connections: connections,
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Template::render("settings/connections", &context)
+}
+
+/// GET Endpoint for the page to connect
+/// and disconnect providers. This endpoint
+/// will kick in if the user is not logged in
+/// and will redirect the user to the index.
+#[get("/settings/connections", rank = 2)]
+fn connections_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint to connect a provider.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request. Redirects the user to the
+/// provider to authorize the application.
+#[get("/settings/connections/<provider>/connect")]
+fn connect_provider(
+    providers: State<OAuthProviders>,
+    redis_conn: RedisConnection,
+    mut cookies: Cookies,
+    user: User,
+    provider: String,
+) -> Result<Redirect, Flash<Redirect>> {
+    let provider = match providers.get(&provider) {
+        Some(provider) => provider,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/settings/connections"),
+                "Unknown provider.",
+            ))
+        }
+    };
+    match begin_authorization(&providers, provider, &redis_conn, &mut cookies, Some(user.id)) {
+        Ok(url) => Ok(Redirect::to(url)),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/settings/connections"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// GET Endpoint to connect a provider.
+/// This endpoint will kick in if the user
+/// is not logged in and will redirect the
+/// user to the index.
+#[get("/settings/connections/<_provider>/connect", rank = 2)]
+fn connect_provider_nouser(_provider: String) -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for disconnecting
+/// a provider.
+#[derive(Deserialize)]
+struct DisconnectStruct {
+    /// Name of the provider.
+    provider: String,
+}
+
+/// Struct for responding with a JSON
+/// message.
+#[derive(Serialize)]
+struct Message<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+}
+
+/// POST Endpoint to disconnect a provider.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// It requires all of the parameters in the
+/// DisconnectStruct in a JSON format.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/connections/disconnect", format = "application/json", data = "<json_data>")]
+fn disconnect_provider<'a>(
+    conn: DbConn,
+    user: User,
+    json_data: Json<DisconnectStruct>,
+) -> Json<Message<'a>> {
+    match database::delete_identity(&conn, user.id, &json_data.provider) {
+        Ok(_) => Json(Message {
+            text: "The account has been disconnected.",
+        }),
+        Err(_) => Json(Message {
+            text: "An unknown error occured. Please try again later.",
+        }),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![
+        oauth_login_loggedin,
+        oauth_login,
+        oauth_callback,
+        connections_page,
+        connections_page_nouser,
+        connect_provider,
+        connect_provider_nouser,
+        disconnect_provider
+    ]
+}
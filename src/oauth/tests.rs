@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+    use oauth::client::{parse_user, ProviderUser};
+    use oauth::username_candidate;
+    use serde_json::{self, Value};
+
+    fn json(text: &str) -> Value {
+        serde_json::from_str(text).expect("valid json")
+    }
+
+    #[test]
+    fn connections_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/connections").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+    }
+
+    #[test]
+    fn github_user_with_emails() {
+        let user = json(r#"{"id": 1234, "login": "octocat", "email": null}"#);
+        let emails = json(
+            r#"[
+                {"email": "old@example.com", "primary": false, "verified": true},
+                {"email": "Octocat@Example.com", "primary": true, "verified": true},
+                {"email": "fake@example.com", "primary": false, "verified": false}
+            ]"#,
+        );
+
+        assert_eq!(
+            parse_user(&user, Some(&emails)),
+            Some(ProviderUser {
+                id: "1234".to_string(),
+                username: Some("octocat".to_string()),
+                email: Some("octocat@example.com".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn unverified_email_is_ignored() {
+        let user = json(r#"{"id": "abc", "username": "learner", "email": "a@example.com"}"#);
+        let emails = json(r#"[{"email": "b@example.com", "primary": true, "verified": false}]"#);
+
+        assert_eq!(parse_user(&user, None).and_then(|user| user.email), None);
+        assert_eq!(parse_user(&user, Some(&emails)).and_then(|user| user.email), None);
+        assert_eq!(parse_user(&json(r#"{"login": "no id"}"#), None), None);
+    }
+
+    #[test]
+    fn usernames_from_providers() {
+        assert_eq!(username_candidate(Some("octo-cat_1")), "octo-cat_1");
+        assert_eq!(username_candidate(Some("me@home")), "mehome");
+        assert_eq!(username_candidate(None), "learner");
+    }
+}
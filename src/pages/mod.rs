@@ -3,8 +3,8 @@ use rocket::request::FlashMessage;
 use rocket_contrib::templates::Template;
 use rocket_contrib::json::Json;
 use users::User;
-use series::PublicSeries;
-use series::database::get_last_10_series;
+use series::{PublicSeries, SerieProgress};
+use series::database::{get_last_10_series, get_series_progress};
 use database::{DbConn, RedisConnection};
 use structs::{Context, LoggedInContext};
 use rocket::response::NamedFile;
@@ -31,6 +31,11 @@ struct IndexLoggedInContext<'a> {
     flash_msg: String,
     /// The last 10 series on the website.
     series: Vec<PublicSeries>,
+    /// The progress of the user in every
+    /// series the user has started, together
+    /// with the next unfinished episode used
+    /// for the continue learning section.
+    progress: Vec<SerieProgress>,
 }
 
 /// Context for rendering tera templates
@@ -76,6 +81,7 @@ fn index(
         flash_name: name,
         flash_msg: msg,
         series: get_last_10_series(&mysql_conn, redis_conn),
+        progress: get_series_progress(&mysql_conn, user.id),
     };
     Template::render("pages/home", &context)
 }
@@ -0,0 +1,212 @@
+use bcrypt::{hash, verify, DEFAULT_COST};
+use rocket::fairing::AdHoc;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+
+#[cfg(test)]
+mod tests;
+
+/// Struct for the bcrypt cost.
+/// Used in endpoints that hash
+/// passwords.
+pub struct BcryptCost(pub u32);
+
+/// Returns a AdHoc Fairing with the bcrypt cost.
+/// Defaults to the default cost of bcrypt if
+/// no bcrypt_cost is set in Rocket.toml File.
+/// Will panic if the cost is not between 4 and 31.
+pub fn bcrypt_cost_fairing() -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("BcryptCost", |rocket| {
+        let config = rocket.config().clone();
+
+        let cost = config
+            .get_int("bcrypt_cost")
+            .unwrap_or(DEFAULT_COST as i64);
+
+        if cost < 4 || cost > 31 {
+            panic!("bcrypt_cost must be between 4 and 31");
+        }
+
+        Ok(rocket.manage(BcryptCost(cost as u32)))
+    })
+}
+
+/// The file with breached passwords used
+/// if none is set in Rocket.toml File.
+static DEFAULT_BREACHED_PASSWORDS_FILE: &'static str = "breached_passwords.txt";
+
+/// The reasons a password
+/// can be rejected for.
+#[derive(Debug, PartialEq)]
+pub enum PasswordError {
+    /// The password is shorter than
+    /// the minimum length.
+    TooShort(usize),
+    /// The password contains
+    /// the username.
+    ContainsUsername,
+    /// The password contains
+    /// the email.
+    ContainsEmail,
+    /// The password is in the list
+    /// of breached passwords.
+    Breached,
+}
+
+impl PasswordError {
+    /// The message shown to the user.
+    pub fn message(&self) -> String {
+        match *self {
+            PasswordError::TooShort(min_length) => format!(
+                "The password must be at least {} characters long.",
+                min_length
+            ),
+            PasswordError::ContainsUsername => {
+                "The password can't contain your username.".to_string()
+            }
+            PasswordError::ContainsEmail => "The password can't contain your email.".to_string(),
+            PasswordError::Breached => {
+                "The password has appeared in a data breach, please choose another one."
+                    .to_string()
+            }
+        }
+    }
+}
+
+/// The password policy used when users
+/// register, change and recover passwords.
+pub struct PasswordPolicy {
+    /// The minimum number of
+    /// characters in a password.
+    pub min_length: usize,
+    /// Known breached passwords
+    /// in lowercase.
+    pub breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    /// Checks the password against the policy.
+    /// The username and email of the user are
+    /// not allowed anywhere in the password,
+    /// regardless of case. Names shorter than
+    /// three characters are ignored as they
+    /// would reject too many passwords.
+    pub fn check(&self, password: &str, username: &str, email: &str) -> Result<(), PasswordError> {
+        if password.chars().count() < self.min_length {
+            return Err(PasswordError::TooShort(self.min_length));
+        }
+
+        let password = password.to_lowercase();
+        let username = username.trim().to_lowercase();
+        if username.chars().count() >= 3 && password.contains(&username) {
+            return Err(PasswordError::ContainsUsername);
+        }
+
+        let email = email.trim().to_lowercase();
+        let local_part = email.split('@').next().unwrap_or("");
+        if local_part.chars().count() >= 3 && password.contains(local_part) {
+            return Err(PasswordError::ContainsEmail);
+        }
+
+        if self.breached.contains(&password) {
+            return Err(PasswordError::Breached);
+        }
+        Ok(())
+    }
+}
+
+/// Reads the breached passwords from the
+/// file, one password on each line.
+fn read_breached_passwords(path: &str) -> Result<HashSet<String>, Error> {
+    let file = File::open(path)?;
+    let mut breached = HashSet::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let password = line.trim();
+        if !password.is_empty() {
+            breached.insert(password.to_lowercase());
+        }
+    }
+    Ok(breached)
+}
+
+/// Returns a AdHoc Fairing with the Password Policy.
+/// The minimum length defaults to 10 characters if
+/// no password_min_length is set in Rocket.toml File.
+/// Will panic if breached_passwords_file is set but
+/// can't be read. If it isn't set the default file
+/// is used when it exists.
+pub fn password_policy_fairing() -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("PasswordPolicy", |rocket| {
+        let config = rocket.config().clone();
+
+        let min_length = config.get_int("password_min_length").unwrap_or(10);
+
+        let breached = match config.get_str("breached_passwords_file") {
+            Ok(path) => {
+                read_breached_passwords(path).expect("breached_passwords_file not readable")
+            }
+            Err(_) => read_breached_passwords(DEFAULT_BREACHED_PASSWORDS_FILE)
+                .unwrap_or(HashSet::new()),
+        };
+
+        Ok(rocket.manage(PasswordPolicy {
+            min_length: min_length as usize,
+            breached: breached,
+        }))
+    })
+}
+
+/// Hashes the password with the cost.
+pub fn hash_password(password: &str, cost: u32) -> Result<String, Error> {
+    match hash(password, cost) {
+        Ok(hashed_password) => Ok(hashed_password),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not hash password.")),
+    }
+}
+
+/// Checks if the password matches the hash.
+/// An error does not mean that the passwords
+/// do not match, only that the hash could not
+/// be verified.
+pub fn verify_password(password: &str, hashed_password: &str) -> Result<bool, Error> {
+    match verify(password, hashed_password) {
+        Ok(passwords_match) => Ok(passwords_match),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not verify password.")),
+    }
+}
+
+/// Salt and hash of a password nobody
+/// knows, used by dummy_hash.
+static DUMMY_SALT_AND_HASH: &'static str = "R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW";
+
+/// Returns a hash with the cost that no
+/// password matches. Verifying a password
+/// against it takes as long as against a
+/// real hash, so logins for accounts that
+/// don't exist aren't faster.
+pub fn dummy_hash(cost: u32) -> String {
+    format!("$2y${:02}${}", cost, DUMMY_SALT_AND_HASH)
+}
+
+/// Gets the cost the hash was made with.
+/// Hashes look like $2y$12$ followed by
+/// the salt and the hash.
+pub fn hash_cost(hashed_password: &str) -> Option<u32> {
+    match hashed_password.split('$').nth(2) {
+        Some(cost) => cost.parse().ok(),
+        None => None,
+    }
+}
+
+/// Checks if the hash was made with a lower
+/// cost than the current one and should be
+/// rehashed the next time the password is
+/// known.
+pub fn needs_rehash(hashed_password: &str, cost: u32) -> bool {
+    match hash_cost(hashed_password) {
+        Some(hashed_cost) => hashed_cost < cost,
+        None => false,
+    }
+}
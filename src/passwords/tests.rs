@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod test {
+    use passwords::{dummy_hash, hash_cost, hash_password, needs_rehash, verify_password,
+                    PasswordError, PasswordPolicy};
+    use std::collections::HashSet;
+
+    fn policy() -> PasswordPolicy {
+        let mut breached = HashSet::new();
+        breached.insert("correcthorse".to_string());
+        PasswordPolicy {
+            min_length: 10,
+            breached: breached,
+        }
+    }
+
+    #[test]
+    fn hash_and_verify() {
+        let hashed_password = hash_password("hunter22", 4).expect("hashed password");
+
+        assert_eq!(verify_password("hunter22", &hashed_password).ok(), Some(true));
+        assert_eq!(verify_password("hunter23", &hashed_password).ok(), Some(false));
+    }
+
+    #[test]
+    fn rehash_lower_cost() {
+        let hashed_password = hash_password("hunter22", 4).expect("hashed password");
+
+        assert_eq!(hash_cost(&hashed_password), Some(4));
+        assert!(needs_rehash(&hashed_password, 5));
+        assert!(!needs_rehash(&hashed_password, 4));
+        assert!(!needs_rehash("not a hash", 12));
+    }
+
+    #[test]
+    fn dummy_hash_matches_nothing() {
+        let hashed_password = dummy_hash(4);
+
+        assert_eq!(hash_cost(&hashed_password), Some(4));
+        assert_eq!(verify_password("hunter22", &hashed_password).ok(), Some(false));
+    }
+
+    #[test]
+    fn policy_min_length() {
+        assert_eq!(
+            policy().check("short", "axel", "axel@clubcoding.com"),
+            Err(PasswordError::TooShort(10))
+        );
+        assert_eq!(
+            policy().check("long enough pass", "axel", "axel@clubcoding.com"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn policy_username_and_email() {
+        assert_eq!(
+            policy().check("my name is AXEL!", "axel", "someone@clubcoding.com"),
+            Err(PasswordError::ContainsUsername)
+        );
+        assert_eq!(
+            policy().check("hello someone 123", "axel", "someone@clubcoding.com"),
+            Err(PasswordError::ContainsEmail)
+        );
+        assert_eq!(policy().check("a password with x", "x", "x@y.com"), Ok(()));
+    }
+
+    #[test]
+    fn policy_breached() {
+        assert_eq!(
+            policy().check("CorrectHorse", "axel", "axel@clubcoding.com"),
+            Err(PasswordError::Breached)
+        );
+    }
+}
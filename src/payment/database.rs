@@ -1,7 +1,12 @@
 use database::DbConn;
-use club_coding::models::{Series, UsersStripeCard, UsersStripeCharge, UsersStripeCustomer};
-use chrono::NaiveDateTime;
+use club_coding::create_new_membership;
+use club_coding::models::{Memberships, Series, UsersStripeCard, UsersStripeCharge,
+                          UsersStripeCustomer};
+use chrono::{NaiveDateTime, Utc};
 use payment::Charge;
+use payment::membership::{self, Subscription};
+use std::io::{Error, ErrorKind};
+use diesel;
 use diesel::prelude::*;
 
 /// Returns vector of charges belonging to
@@ -29,6 +34,7 @@ pub fn get_charges(connection: &DbConn, uid: i64) -> Vec<Charge> {
                     Some(serie) => {
                         to_return.push(Charge {
                             amount: charge.amount,
+                            currency: charge.currency,
                             date: NaiveDateTime::from_timestamp(charge.created_at_stripe, 0)
                                 .to_string(),
                             series: serie.title,
@@ -82,3 +88,92 @@ pub fn delete_and_get_card(connection: &DbConn, uid: i64) -> Option<String> {
         None => None,
     }
 }
+
+/// Gets the membership of the user,
+/// whether it is active or not.
+pub fn get_membership(connection: &DbConn, uid: i64) -> Option<Memberships> {
+    use club_coding::schema::memberships::dsl::*;
+
+    match memberships
+        .filter(user_id.eq(uid))
+        .first::<Memberships>(&**connection)
+    {
+        Ok(membership) => Some(membership),
+        Err(_) => None,
+    }
+}
+
+/// Checks if the user has a membership
+/// that gives access to all of the
+/// membership only videos.
+pub fn is_active_member(connection: &DbConn, uid: i64) -> bool {
+    match get_membership(connection, uid) {
+        Some(membership) => membership::is_active(
+            &membership.status,
+            membership.current_period_end,
+            Utc::now().timestamp(),
+        ),
+        None => false,
+    }
+}
+
+/// Stores the subscription as the membership
+/// of the user. Replaces an earlier membership
+/// that has ended, since a user only has one.
+pub fn save_membership(
+    connection: &DbConn,
+    uid: i64,
+    plan_name: &str,
+    subscription: &Subscription,
+) -> Result<(), Error> {
+    use club_coding::schema::memberships::dsl::*;
+
+    if get_membership(connection, uid).is_none() {
+        return create_new_membership(
+            &*connection,
+            uid,
+            plan_name,
+            &subscription.id,
+            &subscription.status,
+            subscription.cancel_at_period_end,
+            subscription.current_period_end,
+        );
+    }
+
+    match diesel::update(memberships.filter(user_id.eq(uid)))
+        .set((
+            plan.eq(plan_name),
+            stripe_subscription_id.eq(&subscription.id),
+            status.eq(&subscription.status),
+            cancel_at_period_end.eq(subscription.cancel_at_period_end),
+            current_period_end.eq(subscription.current_period_end),
+        ))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not save membership.")),
+    }
+}
+
+/// Updates the membership billed with the
+/// subscription, for example when Stripe
+/// renews or ends it. Subscriptions we
+/// don't have stored are ignored.
+pub fn update_membership(connection: &DbConn, subscription: &Subscription) -> Result<(), Error> {
+    use club_coding::schema::memberships::dsl::*;
+
+    match diesel::update(memberships.filter(stripe_subscription_id.eq(&subscription.id)))
+        .set((
+            status.eq(&subscription.status),
+            cancel_at_period_end.eq(subscription.cancel_at_period_end),
+            current_period_end.eq(subscription.current_period_end),
+        ))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not update membership.",
+        )),
+    }
+}
@@ -0,0 +1,272 @@
+use database::RedisConnection;
+use redis::Commands;
+use rocket::fairing::AdHoc;
+use serde_json::Value;
+use std::io::{Error, ErrorKind};
+
+/// URL of the Stripe endpoint
+/// for subscriptions.
+static STRIPE_SUBSCRIPTIONS_URL: &'static str = "https://api.stripe.com/v1/subscriptions";
+
+/// Number of seconds a membership lock is held
+/// for if it is never released, for example
+/// if the server stops in the middle of it.
+static MEMBERSHIP_LOCK_LIFETIME: usize = 60;
+
+/// Names of the plans we offer.
+/// Used in the config keys and
+/// stored with memberships.
+pub static PLAN_NAMES: [&'static str; 2] = ["monthly", "yearly"];
+
+/// A membership plan and the
+/// Stripe plan it is billed with.
+#[derive(Serialize)]
+pub struct MembershipPlan {
+    /// Name of the plan,
+    /// monthly or yearly.
+    pub name: String,
+    /// Id of the plan at Stripe.
+    #[serde(skip_serializing)]
+    pub stripe_plan: String,
+    /// Price of the plan. The amount
+    /// is defined by USD * 100.
+    pub price: i32,
+}
+
+/// Struct for the configured
+/// membership plans.
+pub struct MembershipPlans(pub Vec<MembershipPlan>);
+
+impl MembershipPlans {
+    /// Gets the plan with the name.
+    pub fn get(&self, name: &str) -> Option<&MembershipPlan> {
+        self.0.iter().find(|plan| plan.name == name)
+    }
+
+    /// Gets the plan billed
+    /// with the Stripe plan.
+    pub fn get_by_stripe_plan(&self, stripe_plan: &str) -> Option<&MembershipPlan> {
+        self.0.iter().find(|plan| plan.stripe_plan == stripe_plan)
+    }
+}
+
+/// Returns a AdHoc Fairing with the Membership Plans.
+/// Each plan is configured with membership_<name>_plan,
+/// the id of the plan at Stripe, and membership_<name>_price
+/// for showing the price. Plans without a Stripe plan
+/// are not offered.
+pub fn membership_plans_fairing() -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("MembershipPlans", |rocket| {
+        let config = rocket.config().clone();
+
+        let mut plans = vec![];
+        for name in PLAN_NAMES.iter() {
+            let stripe_plan = match config.get_str(&format!("membership_{}_plan", name)) {
+                Ok(stripe_plan) if !stripe_plan.is_empty() => stripe_plan.to_string(),
+                _ => continue,
+            };
+            let price = config
+                .get_int(&format!("membership_{}_price", name))
+                .expect("membership price not specified");
+            plans.push(MembershipPlan {
+                name: name.to_string(),
+                stripe_plan: stripe_plan,
+                price: price as i32,
+            });
+        }
+
+        Ok(rocket.manage(MembershipPlans(plans)))
+    })
+}
+
+/// Redis key of the lock held while
+/// the user is becoming a member.
+fn membership_lock_key(user_id: i64) -> String {
+    format!("membership_lock:{}", user_id)
+}
+
+/// Takes the lock for the user becoming a
+/// member. Returns false if another request
+/// is already subscribing the user.
+pub fn acquire_membership_lock(redis_conn: &RedisConnection, user_id: i64) -> bool {
+    match redis::cmd("SET")
+        .arg(membership_lock_key(user_id))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(MEMBERSHIP_LOCK_LIFETIME)
+        .query::<Option<String>>(&**redis_conn)
+    {
+        Ok(Some(_)) => true,
+        _ => false,
+    }
+}
+
+/// Releases the lock for the
+/// user becoming a member.
+pub fn release_membership_lock(redis_conn: &RedisConnection, user_id: i64) {
+    let _ = redis_conn.del::<String, i64>(membership_lock_key(user_id));
+}
+
+/// A subscription at Stripe.
+#[derive(Debug, PartialEq)]
+pub struct Subscription {
+    /// Id of the subscription.
+    pub id: String,
+    /// Id of the Stripe plan.
+    pub plan: String,
+    /// Status of the subscription, for
+    /// example active or canceled.
+    pub status: String,
+    /// If the subscription ends at
+    /// the end of the period.
+    pub cancel_at_period_end: bool,
+    /// Unix timestamp of the end
+    /// of the paid period.
+    pub current_period_end: i64,
+}
+
+/// Checks if a membership with the status
+/// and period gives access to the videos
+/// at the unix timestamp now.
+pub fn is_active(status: &str, current_period_end: i64, now: i64) -> bool {
+    (status == "active" || status == "trialing") && current_period_end > now
+}
+
+/// Checks if a subscription with the status is
+/// still billed or can become active again, for
+/// example while Stripe retries a failed payment.
+/// A new subscription can't be started then.
+pub fn is_ongoing(status: &str) -> bool {
+    status != "canceled" && status != "incomplete_expired"
+}
+
+/// Reads a subscription from the JSON of Stripe.
+/// The plan is read from the first item, falling
+/// back to the plan of older API versions.
+pub fn parse_subscription(subscription: &Value) -> Option<Subscription> {
+    let plan = subscription
+        .get("items")
+        .and_then(|items| items.get("data"))
+        .and_then(|data| data.get(0))
+        .and_then(|item| item.get("plan"))
+        .or(subscription.get("plan"))
+        .and_then(|plan| plan.get("id"))
+        .and_then(|id| id.as_str())?;
+
+    Some(Subscription {
+        id: subscription.get("id")?.as_str()?.to_string(),
+        plan: plan.to_string(),
+        status: subscription.get("status")?.as_str()?.to_string(),
+        cancel_at_period_end: subscription.get("cancel_at_period_end")?.as_bool()?,
+        current_period_end: subscription.get("current_period_end")?.as_i64()?,
+    })
+}
+
+/// Reads the subscription from a Stripe
+/// webhook event about a subscription.
+/// Returns None for other events.
+pub fn parse_subscription_event(event: &Value) -> Option<Subscription> {
+    match event.get("type")?.as_str()? {
+        "customer.subscription.created"
+        | "customer.subscription.updated"
+        | "customer.subscription.deleted" => {
+            parse_subscription(event.get("data")?.get("object")?)
+        }
+        _ => None,
+    }
+}
+
+/// Sends the request to Stripe and
+/// parses the subscription returned.
+fn send_subscription_request(
+    stripe_secret: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<Subscription, Error> {
+    let mut res = match request.basic_auth(stripe_secret, None::<&str>).send() {
+        Ok(res) => res,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not connect to server at URL.",
+            ))
+        }
+    };
+
+    if !res.status().is_success() {
+        return Err(Error::new(ErrorKind::Other, "Stripe returned an error."));
+    }
+
+    let text = match res.text() {
+        Ok(text) => text,
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not read response.")),
+    };
+
+    let value: Value = match serde_json::from_str(&text) {
+        Ok(value) => value,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not serialize response.",
+            ))
+        }
+    };
+
+    match parse_subscription(&value) {
+        Some(subscription) => Ok(subscription),
+        None => Err(Error::new(ErrorKind::Other, "Could not read subscription.")),
+    }
+}
+
+/// Subscribes the Stripe customer to the
+/// plan. The default card of the customer
+/// is charged every period. The idempotency
+/// key makes sure the same form sent twice
+/// only creates one subscription.
+pub fn create_subscription(
+    stripe_secret: &str,
+    idempotency_key: &str,
+    customer: &str,
+    stripe_plan: &str,
+) -> Result<Subscription, Error> {
+    let client = reqwest::Client::new();
+    send_subscription_request(
+        stripe_secret,
+        client
+            .post(STRIPE_SUBSCRIPTIONS_URL)
+            .header("Idempotency-Key", idempotency_key)
+            .form(&[("customer", customer), ("items[0][plan]", stripe_plan)]),
+    )
+}
+
+/// Sets if the subscription ends at the end
+/// of the period. Used both for cancelling
+/// and for resuming a cancelled membership
+/// before the period has ended.
+pub fn set_cancel_at_period_end(
+    stripe_secret: &str,
+    subscription_id: &str,
+    cancel: bool,
+) -> Result<Subscription, Error> {
+    let url = format!("{}/{}", STRIPE_SUBSCRIPTIONS_URL, subscription_id);
+    let client = reqwest::Client::new();
+    send_subscription_request(
+        stripe_secret,
+        client.post(&url).form(&[(
+            "cancel_at_period_end",
+            if cancel { "true" } else { "false" },
+        )]),
+    )
+}
+
+/// Cancels the subscription right away.
+/// Used when the subscription was created
+/// but the membership could not be stored.
+pub fn cancel_subscription(
+    stripe_secret: &str,
+    subscription_id: &str,
+) -> Result<Subscription, Error> {
+    let url = format!("{}/{}", STRIPE_SUBSCRIPTIONS_URL, subscription_id);
+    let client = reqwest::Client::new();
+    send_subscription_request(stripe_secret, client.delete(&url))
+}
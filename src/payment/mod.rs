@@ -1,19 +1,27 @@
 pub mod customer;
 pub mod database;
+pub mod membership;
 
+use audit::{self, AuditEvent};
+use authentication;
 use charge::ChargeContext;
 use charge::Stripe;
+use currency::{self, CURRENCIES};
 use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
-use database::DbConn;
+use database::{DbConn, RedisConnection};
 use payment::customer::{charge, delete};
-use payment::database::{get_charges, get_customer};
+use payment::database::{get_charges, get_customer, get_membership, save_membership};
+use payment::membership::MembershipPlans;
+use chrono::{NaiveDateTime, Utc};
 use rocket::request::FlashMessage;
 use rocket::request::Form;
 use rocket::response::{Flash, Redirect};
 use rocket::{Route, State};
 use rocket_contrib::templates::Template;
+use sessions::ClientInfo;
 use structs::{PostmarkToken, StripeToken};
 use users::User;
+use videos::charge::valid_idempotency_key;
 
 #[cfg(test)]
 mod tests;
@@ -22,9 +30,11 @@ mod tests;
 pub struct Charge {
     /// Amount of the charge.
     /// The amount is defined
-    /// by USD * 100 and therefor
-    /// is not a float.
+    /// by the currency * 100 and
+    /// therefor is not a float.
     amount: i32,
+    /// Currency of the charge.
+    currency: String,
     /// The date in a string format.
     date: String,
     /// The name of the series.
@@ -48,6 +58,14 @@ struct PaymentsContext<'a> {
     /// Vector of charges belonging
     /// to the User.
     charges: Vec<Charge>,
+    /// CSRF Token. Used as a hidden
+    /// input in the currency form.
+    csrf: String,
+    /// Currency the user has chosen.
+    /// Empty if it's picked from the card.
+    currency: String,
+    /// Currencies the user can choose.
+    currencies: &'a [&'static str],
 }
 
 /// GET Endpoint to view payment data
@@ -66,6 +84,7 @@ fn payments_page(
     conn: DbConn,
     user: User,
     flash: Option<FlashMessage>,
+    token: CsrfToken,
 ) -> Result<Template, Redirect> {
     match get_customer(&conn, user.id) {
         Some(_) => {
@@ -74,12 +93,16 @@ fn payments_page(
                 Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
                 None => ("".to_string(), "".to_string()),
             };
+            let chosen = currency::database::get_user_currency(&conn, user.id);
             let context = PaymentsContext {
                 header: "Payments",
                 user: user,
                 flash_name: name,
                 flash_msg: msg,
                 charges: charges,
+                csrf: token.value(),
+                currency: chosen.unwrap_or("".to_string()),
+                currencies: &CURRENCIES,
             };
             Ok(Template::render("payment/payment", &context))
         }
@@ -143,6 +166,7 @@ fn update_card_page(
 fn update_card(
     conn: DbConn,
     user: User,
+    client: ClientInfo,
     csrf_cookie: CsrfCookie,
     stripe_token: State<StripeToken>,
     postmark: State<PostmarkToken>,
@@ -158,6 +182,7 @@ fn update_card(
                     "CSRF Failed.",
                 ));
             }
+            let user_id = user.id;
             match charge(
                 &conn,
                 &stripe_token.secret_key,
@@ -166,10 +191,20 @@ fn update_card(
                 user.id,
                 user.email,
             ) {
-                Ok(()) => Ok(Flash::success(
-                    Redirect::to("/"),
-                    "Card updated. Great choice!",
-                )),
+                Ok(()) => {
+                    audit::record(
+                        &conn,
+                        AuditEvent::CardUpdated,
+                        Some(user_id),
+                        None,
+                        Some(&client),
+                        None,
+                    );
+                    Ok(Flash::success(
+                        Redirect::to("/"),
+                        "Card updated. Great choice!",
+                    ))
+                }
                 _ => Err(Flash::error(
                     Redirect::to("/settings/payment/card/update"),
                     "An error occured, please try again later.",
@@ -198,11 +233,23 @@ fn update_card(
 fn delete_card(
     conn: DbConn,
     postmark: State<PostmarkToken>,
+    client: ClientInfo,
     user: User,
 ) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let user_id = user.id;
     match get_customer(&conn, user.id) {
         Some(_) => match delete(&conn, &postmark.0, user.id, user.email) {
-            Ok(()) => Ok(Flash::success(Redirect::to("/"), "Oh no! Card deleted.")),
+            Ok(()) => {
+                audit::record(
+                    &conn,
+                    AuditEvent::CardDeleted,
+                    Some(user_id),
+                    None,
+                    Some(&client),
+                    None,
+                );
+                Ok(Flash::success(Redirect::to("/"), "Oh no! Card deleted."))
+            }
             _ => Err(Flash::error(
                 Redirect::to("/settings/payment"),
                 "An error occured, please try again later.",
@@ -215,10 +262,406 @@ fn delete_card(
     }
 }
 
+/// The membership of the user
+/// as shown on the membership page.
+#[derive(Serialize)]
+struct PublicMembership {
+    /// Name of the plan.
+    plan: String,
+    /// Status of the subscription.
+    status: String,
+    /// If the membership gives access
+    /// to the videos right now.
+    active: bool,
+    /// If the membership ends at
+    /// the end of the period.
+    cancel_at_period_end: bool,
+    /// The end of the paid period
+    /// in a string format.
+    period_end: String,
+}
+
+#[derive(Serialize)]
+struct MembershipContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// CSRF Token. Used as a hidden
+    /// input in the forms.
+    csrf: String,
+    /// Idempotency key of the subscribe
+    /// forms. A new one is generated each
+    /// time the page is rendered.
+    idempotency_key: String,
+    /// Flash name if the request is redirected
+    /// with one.
+    flash_name: String,
+    /// Flash message if the request is redirected
+    /// with one.
+    flash_msg: String,
+    /// The plans that can be subscribed to.
+    plans: &'a [membership::MembershipPlan],
+    /// If the user has an active membership.
+    member: bool,
+    /// The membership of the user, if any.
+    membership: Option<PublicMembership>,
+}
+
+/// GET Endpoint for the page to view,
+/// start, cancel and resume the membership
+/// of the logged in user. Endpoints checks
+/// if the user is logged in by using the
+/// user request guard. If the user is
+/// not logged in it forwards the request.
+#[get("/membership")]
+fn membership_page(
+    conn: DbConn,
+    user: User,
+    plans: State<MembershipPlans>,
+    flash: Option<FlashMessage>,
+    token: CsrfToken,
+) -> Template {
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
+    let membership = get_membership(&conn, user.id).map(|current| PublicMembership {
+        active: membership::is_active(
+            &current.status,
+            current.current_period_end,
+            Utc::now().timestamp(),
+        ),
+        plan: current.plan,
+        status: current.status,
+        cancel_at_period_end: current.cancel_at_period_end,
+        period_end: NaiveDateTime::from_timestamp(current.current_period_end, 0).to_string(),
+    });
+    let context = MembershipContext {
+        header: "Membership",
+        user: user,
+        csrf: token.value(),
+        idempotency_key: authentication::generate_token(30),
+        flash_name: name,
+        flash_msg: msg,
+        plans: &plans.0,
+        member: membership.as_ref().map_or(false, |current| current.active),
+        membership: membership,
+    };
+    Template::render("payment/membership", &context)
+}
+
+/// Struct for parsing
+/// subscribe forms
+#[derive(FromForm)]
+struct Subscribe {
+    /// CSRF Token from the form
+    csrf: String,
+    /// Name of the plan
+    plan: String,
+    /// Idempotency key generated when the
+    /// membership page was rendered. Sent
+    /// to Stripe so submitting the same form
+    /// twice only creates one subscription.
+    idempotency_key: String,
+}
+
+/// POST Endpoint to subscribe the logged in
+/// user to a membership plan. Endpoints checks
+/// if the user is logged in by using the
+/// user request guard. If the user is
+/// not logged in it forwards the request.
+/// It requires all of the parameters in the
+/// Subscribe struct to be submitted as a form.
+/// The subscription is created while holding a
+/// lock for the user, and the endpoint checks if
+/// the user already is a member after taking the
+/// lock so a double submit can't start two
+/// subscriptions. The user needs a card on the
+/// account and can't have a membership that is
+/// still billed, even if a payment has failed. Redirects to the membership page with
+/// an appropriate message.
+#[post("/membership/subscribe", data = "<form_data>")]
+fn subscribe(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    stripe_token: State<StripeToken>,
+    plans: State<MembershipPlans>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    form_data: Form<Subscribe>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let data = form_data.into_inner();
+    if !csrf_matches(csrf_secret_key.0, &data.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "CSRF Failed.",
+        ));
+    }
+    if !valid_idempotency_key(&data.idempotency_key) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "An error occured, please try again later.",
+        ));
+    }
+    let plan = match plans.get(&data.plan) {
+        Some(plan) => plan,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/settings/payment/membership"),
+                "Plan doesn't exist.",
+            ))
+        }
+    };
+    if !membership::acquire_membership_lock(&redis_conn, user.id) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "Your membership is already being processed.",
+        ));
+    }
+    let result = start_membership(
+        &conn,
+        &stripe_token.secret_key,
+        &user,
+        plan,
+        &data.idempotency_key,
+    );
+    membership::release_membership_lock(&redis_conn, user.id);
+    result
+}
+
+/// Subscribes the user to the plan
+/// while the membership lock is held.
+fn start_membership(
+    conn: &DbConn,
+    stripe_secret: &str,
+    user: &User,
+    plan: &membership::MembershipPlan,
+    idempotency_key: &str,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    match get_membership(conn, user.id) {
+        Some(ref current) if membership::is_ongoing(&current.status) => {
+            return Err(Flash::error(
+                Redirect::to("/settings/payment/membership"),
+                "You're already a member.",
+            ))
+        }
+        _ => {}
+    }
+    let customer = match get_customer(conn, user.id) {
+        Some(customer) => customer,
+        None => {
+            return Err(Flash::error(
+                Redirect::to("/card/add"),
+                "Add a card to your account to become a member.",
+            ))
+        }
+    };
+
+    match membership::create_subscription(
+        stripe_secret,
+        &format!("membership-{}-{}", user.id, idempotency_key),
+        &customer.uuid,
+        &plan.stripe_plan,
+    ).and_then(|subscription| {
+        let saved = save_membership(conn, user.id, &plan.name, &subscription);
+        if saved.is_err() {
+            // Nothing would stop the subscription from
+            // billing the user, so it's cancelled.
+            let _ = membership::cancel_subscription(stripe_secret, &subscription.id);
+        }
+        saved
+    }) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/settings/payment/membership"),
+            "Welcome! You're now a member.",
+        )),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// Struct for parsing forms
+/// cancelling or resuming
+/// the membership.
+#[derive(FromForm)]
+struct MembershipForm {
+    /// CSRF Token from the form
+    csrf: String,
+}
+
+/// Cancels or resumes the membership of the user
+/// at the end of the period. Used by both the
+/// cancel and the resume endpoints. Only memberships
+/// that haven't ended yet can be changed.
+fn set_membership_cancelled(
+    conn: &DbConn,
+    stripe_secret: &str,
+    user_id: i64,
+    cancel: bool,
+) -> Result<(), &'static str> {
+    let subscription_id = match get_membership(conn, user_id) {
+        Some(ref current)
+            if membership::is_active(
+                &current.status,
+                current.current_period_end,
+                Utc::now().timestamp(),
+            ) =>
+        {
+            current.stripe_subscription_id.clone()
+        }
+        _ => return Err("You don't have an active membership."),
+    };
+    match membership::set_cancel_at_period_end(stripe_secret, &subscription_id, cancel)
+        .and_then(|subscription| database::update_membership(conn, &subscription))
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err("An error occured, please try again later."),
+    }
+}
+
+/// POST Endpoint to cancel the membership
+/// of the logged in user. The membership
+/// stays active until the end of the period.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// not logged in it forwards the
+/// request.
+#[post("/membership/cancel", data = "<form_data>")]
+fn cancel_membership(
+    conn: DbConn,
+    user: User,
+    stripe_token: State<StripeToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    form_data: Form<MembershipForm>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if !csrf_matches(csrf_secret_key.0, &form_data.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "CSRF Failed.",
+        ));
+    }
+    match set_membership_cancelled(&conn, &stripe_token.secret_key, user.id, true) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/settings/payment/membership"),
+            "Membership cancelled. You keep access until the end of the period.",
+        )),
+        Err(msg) => Err(Flash::error(Redirect::to("/settings/payment/membership"), msg)),
+    }
+}
+
+/// POST Endpoint to resume a cancelled
+/// membership of the logged in user before
+/// the period has ended.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// not logged in it forwards the
+/// request.
+#[post("/membership/resume", data = "<form_data>")]
+fn resume_membership(
+    conn: DbConn,
+    user: User,
+    stripe_token: State<StripeToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    form_data: Form<MembershipForm>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if !csrf_matches(csrf_secret_key.0, &form_data.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment/membership"),
+            "CSRF Failed.",
+        ));
+    }
+    match set_membership_cancelled(&conn, &stripe_token.secret_key, user.id, false) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/settings/payment/membership"),
+            "Membership resumed. Welcome back!",
+        )),
+        Err(msg) => Err(Flash::error(Redirect::to("/settings/payment/membership"), msg)),
+    }
+}
+
+/// Struct for parsing
+/// currency forms.
+#[derive(FromForm)]
+struct CurrencyForm {
+    /// CSRF Token from the form
+    csrf: String,
+    /// Currency to pay in. Empty to
+    /// pick it from the card.
+    currency: String,
+}
+
+/// POST Endpoint to set the currency the logged
+/// in user pays for series in. An empty currency
+/// goes back to picking it from the country of
+/// the card.
+/// Endpoints checks if the user is
+/// logged in by using the user
+/// request guard. If the user is
+/// not logged in it forwards the
+/// request.
+#[post("/currency", data = "<form_data>")]
+fn update_currency(
+    conn: DbConn,
+    user: User,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    form_data: Form<CurrencyForm>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    if !csrf_matches(csrf_secret_key.0, &form_data.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/settings/payment"),
+            "CSRF Failed.",
+        ));
+    }
+    let choice = if form_data.currency.is_empty() {
+        None
+    } else {
+        match currency::supported(&form_data.currency) {
+            Some(choice) => Some(choice),
+            None => {
+                return Err(Flash::error(
+                    Redirect::to("/settings/payment"),
+                    "We don't charge in that currency.",
+                ))
+            }
+        }
+    };
+    match currency::database::set_user_currency(&conn, user.id, choice) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to("/settings/payment"),
+            "Currency updated.",
+        )),
+        Err(_) => Err(Flash::error(
+            Redirect::to("/settings/payment"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
 /// instead we can keep all of the changes in here.
 pub fn endpoints() -> Vec<Route> {
-    routes![payments_page, update_card_page, update_card, delete_card]
+    routes![
+        payments_page,
+        update_card_page,
+        update_card,
+        delete_card,
+        membership_page,
+        subscribe,
+        cancel_membership,
+        resume_membership,
+        update_currency
+    ]
 }
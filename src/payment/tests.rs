@@ -4,6 +4,33 @@ mod test {
     use rocket::local::Client;
     use rocket::http::Status;
     use rocket::http::ContentType;
+    use payment::membership::{is_active, is_ongoing, parse_subscription, parse_subscription_event};
+    use serde_json::Value;
+
+    static SUBSCRIPTION_UPDATED: &'static str = r#"{
+        "id": "evt_1CkR8p2eZvKYlo2CpW1nHfKd",
+        "object": "event",
+        "type": "customer.subscription.updated",
+        "data": {
+            "object": {
+                "id": "sub_D9kdR7N1RvbXQy",
+                "object": "subscription",
+                "cancel_at_period_end": true,
+                "current_period_end": 1533571200,
+                "customer": "cus_D9jqjvE2G4FKzV",
+                "items": {
+                    "object": "list",
+                    "data": [
+                        {
+                            "id": "si_D9kdf1DNkO4aYd",
+                            "plan": { "id": "club-coding-monthly", "interval": "month" }
+                        }
+                    ]
+                },
+                "status": "active"
+            }
+        }
+    }"#;
 
     #[test]
     fn payment() {
@@ -13,4 +40,57 @@ mod test {
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(response.content_type(), Some(ContentType::HTML));
     }
+
+    #[test]
+    fn membership_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/payment/membership").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn currency_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client
+            .post("/settings/payment/currency")
+            .header(ContentType::Form)
+            .body("csrf=token&currency=eur")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn subscription_events() {
+        let event: Value = serde_json::from_str(SUBSCRIPTION_UPDATED).expect("valid event");
+        let subscription = parse_subscription_event(&event).expect("subscription event");
+
+        assert_eq!(subscription.id, "sub_D9kdR7N1RvbXQy");
+        assert_eq!(subscription.plan, "club-coding-monthly");
+        assert_eq!(subscription.status, "active");
+        assert!(subscription.cancel_at_period_end);
+        assert_eq!(subscription.current_period_end, 1533571200);
+
+        let object = event.get("data").and_then(|data| data.get("object")).unwrap();
+        assert_eq!(parse_subscription(object), Some(subscription));
+    }
+
+    #[test]
+    fn active_memberships() {
+        assert!(is_active("active", 2000, 1000));
+        assert!(is_active("trialing", 2000, 1000));
+        assert!(!is_active("active", 1000, 2000));
+        assert!(!is_active("past_due", 2000, 1000));
+        assert!(!is_active("canceled", 2000, 1000));
+    }
+
+    #[test]
+    fn ongoing_subscriptions() {
+        assert!(is_ongoing("active"));
+        assert!(is_ongoing("past_due"));
+        assert!(is_ongoing("unpaid"));
+        assert!(!is_ongoing("canceled"));
+        assert!(!is_ongoing("incomplete_expired"));
+    }
 }
@@ -1,3 +1,38 @@
+table! {
+    coupons (id) {
+        id -> Bigint,
+        code -> Varchar,
+        kind -> Varchar,
+        value -> Integer,
+        expires -> Nullable<Timestamp>,
+        max_redemptions -> Nullable<Integer>,
+        times_redeemed -> Integer,
+        created -> Timestamp,
+        updated -> Timestamp,
+        currency -> Varchar,
+    }
+}
+
+table! {
+    coupons_redemptions (id) {
+        id -> Bigint,
+        coupon_id -> Bigint,
+        user_id -> Bigint,
+        series_id -> Bigint,
+        charge_uuid -> Nullable<Varchar>,
+        discount -> Integer,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    coupons_series (id) {
+        id -> Bigint,
+        coupon_id -> Bigint,
+        series_id -> Bigint,
+    }
+}
+
 table! {
     groups (id) {
         id -> Bigint,
@@ -8,6 +43,20 @@ table! {
     }
 }
 
+table! {
+    memberships (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        plan -> Varchar,
+        stripe_subscription_id -> Varchar,
+        status -> Varchar,
+        cancel_at_period_end -> Bool,
+        current_period_end -> Bigint,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     newsletter_subscribers (id) {
         id -> Bigint,
@@ -59,6 +108,17 @@ table! {
     }
 }
 
+table! {
+    series_prices (id) {
+        id -> Bigint,
+        series_id -> Bigint,
+        currency -> Varchar,
+        price -> Integer,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     users (id) {
         id -> Bigint,
@@ -68,6 +128,38 @@ table! {
         verified -> Bool,
         updated -> Timestamp,
         created -> Timestamp,
+        currency -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    users_audit_log (id) {
+        id -> Bigint,
+        user_id -> Nullable<Bigint>,
+        actor_id -> Nullable<Bigint>,
+        event -> Varchar,
+        ip -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        details -> Nullable<Text>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    users_certificates (id) {
+        id -> Bigint,
+        uuid -> Varchar,
+        user_id -> Bigint,
+        serie_id -> Bigint,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
+table! {
+    users_email_backup (user_id) {
+        user_id -> Bigint,
+        email -> Varchar,
     }
 }
 
@@ -81,6 +173,18 @@ table! {
     }
 }
 
+table! {
+    users_identities (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        provider -> Varchar,
+        provider_user_id -> Varchar,
+        provider_username -> Nullable<Varchar>,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     users_recover_email (id) {
         id -> Bigint,
@@ -110,6 +214,10 @@ table! {
         token -> Varchar,
         created -> Timestamp,
         updated -> Timestamp,
+        ip -> Varchar,
+        user_agent -> Varchar,
+        last_seen -> Timestamp,
+        expires -> Timestamp,
     }
 }
 
@@ -166,6 +274,8 @@ table! {
         source_transfer -> Nullable<Varchar>,
         statement_descriptor -> Nullable<Varchar>,
         status -> Varchar,
+        currency -> Varchar,
+        last_event_at -> Bigint,
     }
 }
 
@@ -204,6 +314,29 @@ table! {
     }
 }
 
+table! {
+    users_two_factor (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        secret -> Varchar,
+        enabled -> Bool,
+        last_step -> Bigint,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
+table! {
+    users_two_factor_recovery (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        code -> Varchar,
+        used -> Bool,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     users_verify_email (id) {
         id -> Bigint,
@@ -212,6 +345,33 @@ table! {
         used -> Bool,
         created -> Timestamp,
         updated -> Timestamp,
+        new_email -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    users_videos_comments (id) {
+        id -> Bigint,
+        uuid -> Varchar,
+        user_id -> Bigint,
+        video_id -> Bigint,
+        parent_id -> Nullable<Bigint>,
+        body -> Text,
+        hidden -> Bool,
+        deleted -> Bool,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
+table! {
+    users_videos_votes (id) {
+        id -> Bigint,
+        user_id -> Bigint,
+        video_id -> Bigint,
+        is_like -> Bool,
+        created -> Timestamp,
+        updated -> Timestamp,
     }
 }
 
@@ -222,6 +382,9 @@ table! {
         video_id -> Bigint,
         created -> Timestamp,
         updated -> Timestamp,
+        seconds -> Integer,
+        duration -> Integer,
+        completed -> Bool,
     }
 }
 
@@ -244,13 +407,22 @@ table! {
 }
 
 allow_tables_to_appear_in_same_query!(
+    coupons,
+    coupons_redemptions,
+    coupons_series,
     groups,
+    memberships,
     newsletter_subscribers,
     request_network_hashes,
     request_network_payments,
     series,
+    series_prices,
     users,
+    users_audit_log,
+    users_certificates,
+    users_email_backup,
     users_group,
+    users_identities,
     users_recover_email,
     users_series_access,
     users_sessions,
@@ -258,7 +430,11 @@ allow_tables_to_appear_in_same_query!(
     users_stripe_charge,
     users_stripe_customer,
     users_stripe_token,
+    users_two_factor,
+    users_two_factor_recovery,
     users_verify_email,
+    users_videos_comments,
+    users_videos_votes,
     users_views,
     videos,
 );
@@ -1,8 +1,9 @@
 use club_coding::models::{Series, UsersViews, Videos};
 use database::{DbConn, RedisConnection};
-use series::{PublicSeries, PublicVideo};
+use series::{NextVideo, PublicSeries, PublicVideo, SerieProgress};
 use redis::Commands;
 use diesel::prelude::*;
+use std::collections::HashSet;
 
 /// Gets all of the videos in the
 /// database that are published and
@@ -109,13 +110,14 @@ pub fn get_serie_by_id(connection: &DbConn, sid: i64) -> Option<Series> {
     }
 }
 
-/// Checks if the user defined by
-/// the user_id has watched the video
-/// defined by the video id. Returns
-/// a boolean True if the user has watched
-/// the video  and false if the user has
-/// not watched the video.
-pub fn get_video_watched(connection: &DbConn, uid: i64, vid: i64) -> bool {
+/// Gets how much of the video defined by
+/// the video id the user defined by the
+/// user_id has watched. Returns the
+/// percentage between 0 and 100 together
+/// with whether the user has completed
+/// the video, and (0, false) if the user
+/// has not watched the video.
+pub fn get_video_progress(connection: &DbConn, uid: i64, vid: i64) -> (i32, bool) {
     use club_coding::schema::users_views::dsl::*;
 
     match users_views
@@ -123,14 +125,24 @@ pub fn get_video_watched(connection: &DbConn, uid: i64, vid: i64) -> bool {
         .filter(video_id.eq(vid))
         .first::<UsersViews>(&**connection)
     {
-        Ok(_) => true,
-        Err(_) => false,
+        Ok(view) => {
+            let percentage = if view.completed {
+                100
+            } else if view.duration > 0 {
+                (view.seconds as i64 * 100 / view.duration as i64).min(100) as i32
+            } else {
+                0
+            };
+            (percentage, view.completed)
+        }
+        Err(_) => (0, false),
     }
 }
 
 /// Gets all of the videos that belong
-/// to a specific series and checks if
-/// the user has watched the videos.
+/// to a specific series and checks
+/// how much of the videos the user
+/// has watched and completed.
 pub fn get_videos(
     connection: &DbConn,
     redis_conn: RedisConnection,
@@ -144,7 +156,9 @@ pub fn get_videos(
                 Err(_) => return vec![],
             };
             for mut video in &mut videos {
-                video.watched = get_video_watched(connection, uid, video.id);
+                let (percentage, done) = get_video_progress(connection, uid, video.id);
+                video.watched_percentage = percentage;
+                video.completed = done;
             }
             return videos;
         }
@@ -161,13 +175,15 @@ pub fn get_videos(
                 Ok(vec_of_videos) => {
                     let mut to_return: Vec<PublicVideo> = vec![];
                     for video in vec_of_videos {
+                        let (percentage, done) = get_video_progress(connection, uid, video.id);
                         to_return.push(PublicVideo {
                             id: video.id,
                             episode_number: video.episode_number,
                             uuid: video.uuid,
                             title: video.title,
                             description: video.description,
-                            watched: get_video_watched(connection, uid, video.id),
+                            watched_percentage: percentage,
+                            completed: done,
                         });
                     }
                     let json_string = match serde_json::to_string(&to_return) {
@@ -190,8 +206,9 @@ pub fn get_videos(
 }
 
 /// Gets all of the videos that belong
-/// to a specific series and sets watched
-/// of every video to false
+/// to a specific series and sets the
+/// watched percentage of every video to 0
+/// and completed to false
 pub fn get_videos_nologin(
     connection: &DbConn,
     redis_conn: RedisConnection,
@@ -204,7 +221,8 @@ pub fn get_videos_nologin(
                 Err(_) => return vec![],
             };
             for mut video in &mut videos {
-                video.watched = false;
+                video.watched_percentage = 0;
+                video.completed = false;
             }
             return videos;
         }
@@ -227,7 +245,8 @@ pub fn get_videos_nologin(
                             uuid: video.uuid,
                             title: video.title,
                             description: video.description,
-                            watched: false,
+                            watched_percentage: 0,
+                            completed: false,
                         });
                     }
                     let json_string = match serde_json::to_string(&to_return) {
@@ -248,3 +267,84 @@ pub fn get_videos_nologin(
         }
     }
 }
+
+/// Gets the progress of the user in every
+/// published series the user has started
+/// watching. Ordered by when the user last
+/// watched a video in the series in a
+/// descending order.
+pub fn get_series_progress(connection: &DbConn, uid: i64) -> Vec<SerieProgress> {
+    use club_coding::schema::{series, users_views, videos};
+
+    let started: Vec<i64> = match users_views::table
+        .inner_join(videos::table.on(videos::id.eq(users_views::video_id)))
+        .filter(users_views::user_id.eq(uid))
+        .order(users_views::updated.desc())
+        .select(videos::serie_id)
+        .load::<i64>(&**connection)
+    {
+        Ok(started) => started,
+        Err(_) => return vec![],
+    };
+
+    let completed: HashSet<i64> = match users_views::table
+        .filter(users_views::user_id.eq(uid))
+        .filter(users_views::completed.eq(true))
+        .select(users_views::video_id)
+        .load::<i64>(&**connection)
+    {
+        Ok(completed) => completed.into_iter().collect(),
+        Err(_) => return vec![],
+    };
+
+    let mut seen: HashSet<i64> = HashSet::new();
+    let mut to_return: Vec<SerieProgress> = vec![];
+    for sid in started {
+        if !seen.insert(sid) {
+            continue;
+        }
+        let serie = match series::table
+            .find(sid)
+            .filter(series::published.eq(true))
+            .filter(series::archived.eq(false))
+            .first::<Series>(&**connection)
+        {
+            Ok(serie) => serie,
+            Err(_) => continue,
+        };
+        let vec_of_videos = match videos::table
+            .filter(videos::serie_id.eq(sid))
+            .filter(videos::published.eq(true))
+            .filter(videos::archived.eq(false))
+            .order(videos::episode_number.asc())
+            .load::<Videos>(&**connection)
+        {
+            Ok(vec_of_videos) => vec_of_videos,
+            Err(_) => continue,
+        };
+        if vec_of_videos.is_empty() {
+            continue;
+        }
+
+        let total = vec_of_videos.len();
+        let done = vec_of_videos
+            .iter()
+            .filter(|video| completed.contains(&video.id))
+            .count();
+        let next_video = vec_of_videos
+            .into_iter()
+            .find(|video| !completed.contains(&video.id))
+            .map(|video| NextVideo {
+                uuid: video.uuid,
+                title: video.title,
+                episode_number: video.episode_number,
+            });
+        to_return.push(SerieProgress {
+            uuid: serie.uuid,
+            title: serie.title,
+            completion: (done * 100 / total) as i32,
+            next_video: next_video,
+        });
+    }
+    to_return
+}
@@ -1,9 +1,11 @@
 pub mod database;
 
 use rocket::Route;
+use rocket::request::FlashMessage;
 use rocket_contrib::templates::Template;
 use database::{DbConn, RedisConnection};
 use users::User;
+use currency;
 
 #[cfg(test)]
 mod tests;
@@ -36,9 +38,54 @@ pub struct PublicVideo {
     pub title: String,
     /// Description of the video.
     pub description: String,
+    /// How much of the video the user
+    /// has watched in percent.
+    #[serde(default)]
+    pub watched_percentage: i32,
     /// Boolean of whether the user
-    /// has watched the video or not.
-    pub watched: bool,
+    /// has completed the video or not.
+    #[serde(default)]
+    pub completed: bool,
+}
+
+/// The progress of a user in a series
+/// the user has started watching.
+#[derive(Serialize)]
+pub struct SerieProgress {
+    /// UUID of the series.
+    pub uuid: String,
+    /// Title of the series.
+    pub title: String,
+    /// How many of the videos in the
+    /// series the user has completed
+    /// in percent.
+    pub completion: i32,
+    /// The next episode the user has
+    /// not completed. None if the user
+    /// has completed the series.
+    pub next_video: Option<NextVideo>,
+}
+
+/// The next unfinished episode
+/// in a series.
+#[derive(Serialize)]
+pub struct NextVideo {
+    /// UUID of the Video.
+    pub uuid: String,
+    /// Title of the Video.
+    pub title: String,
+    /// Episode number of the Video.
+    pub episode_number: i32,
+}
+
+/// Calculates how many of the videos the
+/// user has completed in percent.
+pub fn get_completion(videos: &[PublicVideo]) -> i32 {
+    if videos.is_empty() {
+        return 0;
+    }
+    let completed = videos.iter().filter(|video| video.completed).count();
+    (completed * 100 / videos.len()) as i32
 }
 
 #[derive(Serialize)]
@@ -58,11 +105,24 @@ struct SerieStruct<'a> {
     /// Boolean of if the series is
     /// in development or not.
     in_development: bool,
-    /// The price of the series defined
-    /// by USD * 100 and therefor not a float.
+    /// The price of the series in the currency
+    /// of the user, defined by the currency * 100
+    /// and therefor not a float.
     price: i32,
+    /// Currency the user pays in.
+    currency: &'static str,
     /// A Vector of the Videos in the series
     videos: Vec<PublicVideo>,
+    /// How many of the videos in the
+    /// series the user has completed
+    /// in percent.
+    completion: i32,
+    /// Flash name if the request is redirected
+    /// with one.
+    flash_name: String,
+    /// Flash message if the request is redirected
+    /// with one.
+    flash_msg: String,
 }
 
 /// GET Endpoint for the page of
@@ -71,6 +131,8 @@ struct SerieStruct<'a> {
 /// user request guard. If the user
 /// is not logged in it forwards
 /// the request.
+/// Takes in an optional FlashMessage
+/// incase there is one.
 /// Responds with the Series Template in
 /// the series folder.
 #[get("/<uuid>")]
@@ -78,12 +140,20 @@ fn serie(
     mysql_conn: DbConn,
     redis_conn: RedisConnection,
     user: User,
+    flash: Option<FlashMessage>,
     uuid: String,
 ) -> Option<Template> {
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
     match database::get_serie(&mysql_conn, &uuid) {
         Some(serie) => {
             let mut description = serie.description;
             description.retain(|c| c != '\\');
+            let videos = database::get_videos(&mysql_conn, redis_conn, user.id, serie.id);
+            let (currency, price) =
+                currency::user_price(&mysql_conn, serie.id, serie.price, user.id);
             let context = SerieStruct {
                 header: &serie.title,
                 user: &user,
@@ -91,8 +161,12 @@ fn serie(
                 title: &serie.title,
                 description: description,
                 in_development: serie.in_development,
-                price: serie.price,
-                videos: database::get_videos(&mysql_conn, redis_conn, user.id, serie.id),
+                price: price,
+                currency: currency,
+                completion: get_completion(&videos),
+                videos: videos,
+                flash_name: name,
+                flash_msg: msg,
             };
             Some(Template::render("series/series", &context))
         }
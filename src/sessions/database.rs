@@ -0,0 +1,118 @@
+use chrono::Utc;
+use club_coding::models::UsersSessions;
+use database::{DbConn, RedisConnection};
+use redis::Commands;
+use std::io::{Error, ErrorKind};
+use diesel::prelude::*;
+
+/// Gets all of the sessions of the user
+/// that have not expired. Ordered by when
+/// they were last used in a descending order.
+pub fn get_sessions(connection: &DbConn, uid: i64) -> Vec<UsersSessions> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    match users_sessions
+        .filter(user_id.eq(uid))
+        .filter(expires.gt(Utc::now().naive_utc()))
+        .order(last_seen.desc())
+        .load::<UsersSessions>(&**connection)
+    {
+        Ok(sessions) => sessions,
+        Err(_) => vec![],
+    }
+}
+
+/// Deletes the session specified by the
+/// session token from the database and
+/// removes the cached user from Redis.
+pub fn delete_session(
+    connection: &DbConn,
+    redis_conn: &RedisConnection,
+    session_token: &str,
+) -> Result<(), Error> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    match redis_conn.del::<&str, String>(session_token) {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+
+    match diesel::delete(users_sessions.filter(token.eq(session_token))).execute(&**connection) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete session.")),
+    }
+}
+
+/// Deletes the session specified by the
+/// session id if it belongs to the user.
+pub fn revoke_session(
+    connection: &DbConn,
+    redis_conn: &RedisConnection,
+    uid: i64,
+    sid: i64,
+) -> Result<(), Error> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    match users_sessions
+        .filter(id.eq(sid))
+        .filter(user_id.eq(uid))
+        .first::<UsersSessions>(&**connection)
+    {
+        Ok(session) => delete_session(connection, redis_conn, &session.token),
+        Err(_) => Err(Error::new(ErrorKind::Other, "No session found.")),
+    }
+}
+
+/// Deletes all of the sessions of the user
+/// except the one specified by the session
+/// token, if there is one.
+pub fn revoke_other_sessions(
+    connection: &DbConn,
+    redis_conn: &RedisConnection,
+    uid: i64,
+    keep: Option<&str>,
+) -> Result<(), Error> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    let tokens: Vec<String> = match users_sessions
+        .filter(user_id.eq(uid))
+        .select(token)
+        .load::<String>(&**connection)
+    {
+        Ok(tokens) => tokens,
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not load sessions.")),
+    };
+
+    for session_token in tokens {
+        if Some(session_token.as_str()) == keep {
+            continue;
+        }
+        delete_session(connection, redis_conn, &session_token)?;
+    }
+    Ok(())
+}
+
+/// Deletes all of the sessions
+/// that have expired.
+pub fn delete_expired_sessions(connection: &DbConn) -> Result<(), Error> {
+    use club_coding::schema::users_sessions::dsl::*;
+
+    match diesel::delete(users_sessions.filter(expires.le(Utc::now().naive_utc())))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete sessions.")),
+    }
+}
+
+/// Removes the cached user of every session
+/// of the user from Redis so the next request
+/// loads the user from the database again.
+pub fn clear_cached_users(connection: &DbConn, redis_conn: &RedisConnection, uid: i64) {
+    for session in get_sessions(connection, uid) {
+        match redis_conn.del::<&str, String>(&session.token) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,237 @@
+pub mod database;
+
+use authentication;
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use club_coding::create_new_user_session;
+use database::{DbConn, RedisConnection};
+use rocket::http::{Cookie, Cookies};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::Redirect;
+use rocket::{Outcome, Route};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use std::io::Error;
+use time::Duration;
+use users::User;
+
+#[cfg(test)]
+mod tests;
+
+/// How many hours a session
+/// is valid after logging in.
+pub static SESSION_LIFETIME_HOURS: i64 = 24;
+
+/// How many seconds a logged in user is
+/// cached in Redis. Kept short so the
+/// last seen timestamp stays up to date.
+pub static SESSION_CACHE_TTL: usize = 300;
+
+/// Request guard with the IP address
+/// and the user agent of the client.
+/// Stored together with new sessions.
+pub struct ClientInfo {
+    /// The IP address of the client.
+    pub ip: String,
+    /// The user agent of the client.
+    pub user_agent: String,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for ClientInfo {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<ClientInfo, ()> {
+        let ip = match request.client_ip() {
+            Some(ip) => ip.to_string(),
+            None => "".to_string(),
+        };
+        let user_agent: String = request
+            .headers()
+            .get_one("User-Agent")
+            .unwrap_or("")
+            .chars()
+            .take(255)
+            .collect();
+        Outcome::Success(ClientInfo {
+            ip: ip,
+            user_agent: user_agent,
+        })
+    }
+}
+
+/// Creates a new session for the user
+/// and sets the session cookie. Expired
+/// sessions are removed at the same time
+/// so the table does not keep growing.
+pub fn start_session(
+    conn: &DbConn,
+    client: &ClientInfo,
+    cookies: &mut Cookies,
+    user_id: i64,
+) -> Result<(), Error> {
+    match database::delete_expired_sessions(conn) {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+
+    let session_token = authentication::generate_token(64);
+    let expires = Utc::now().naive_utc() + ChronoDuration::hours(SESSION_LIFETIME_HOURS);
+    create_new_user_session(
+        &**conn,
+        user_id,
+        &session_token,
+        &client.ip,
+        &client.user_agent,
+        expires,
+    )?;
+
+    let mut c = Cookie::new("session_token", session_token);
+    c.set_max_age(Duration::hours(SESSION_LIFETIME_HOURS));
+    cookies.add_private(c);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct PublicSession {
+    /// Unique ID of the session in
+    /// the database. Used to revoke it.
+    id: i64,
+    /// The IP address the user
+    /// logged in from.
+    ip: String,
+    /// The user agent the user
+    /// logged in with.
+    user_agent: String,
+    /// When the user logged in.
+    created: NaiveDateTime,
+    /// When the session was last used.
+    last_seen: NaiveDateTime,
+    /// Boolean of whether it is the
+    /// session of the current request.
+    current: bool,
+}
+
+/// Context for rendering tera templates
+/// for the sessions endpoint.
+#[derive(Serialize)]
+struct SessionsContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// The active sessions of the user.
+    sessions: Vec<PublicSession>,
+}
+
+/// Struct for responding with a JSON
+/// message.
+#[derive(Serialize)]
+struct Message<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+}
+
+/// GET Endpoint for the page to manage
+/// your sessions. Endpoints checks if the
+/// user is logged in by using the
+/// user request guard. If the user
+/// is not logged in it forwards
+/// the request.
+/// Responds with the Sessions Template
+/// in the settings folder.
+#[get("/settings/sessions")]
+fn sessions_page(conn: DbConn, user: User, mut cookies: Cookies) -> Template {
+    let current_token = cookies
+        .get_private("session_token")
+        .map(|cookie| cookie.value().to_string());
+    let sessions = database::get_sessions(&conn, user.id)
+        .into_iter()
+        .map(|session| PublicSession {
+            id: session.id,
+            current: Some(&session.token) == current_token.as_ref(),
+            ip: session.ip,
+            user_agent: session.user_agent,
+            created: session.created,
+            last_seen: session.last_seen,
+        })
+        .collect();
+    let context = SessionsContext {
+        header: "Sessions",
+        user: user,
+        sessions: sessions,
+    };
+    Template::render("settings/sessions", &context)
+}
+
+/// GET Endpoint for the page to manage
+/// your sessions. This endpoint will kick
+/// in if the user is not logged in and will
+/// redirect the user to the index.
+#[get("/settings/sessions", rank = 2)]
+fn sessions_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// POST Endpoint to revoke a session.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// Users can only revoke their own sessions.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/sessions/revoke/<id>")]
+fn revoke<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    id: i64,
+) -> Json<Message<'a>> {
+    match database::revoke_session(&conn, &redis_conn, user.id, id) {
+        Ok(_) => Json(Message {
+            text: "The session has been revoked.",
+        }),
+        Err(_) => Json(Message {
+            text: "Could not revoke the session.",
+        }),
+    }
+}
+
+/// POST Endpoint to log out everywhere.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is not logged in it forwards
+/// the request.
+/// Deletes every session of the user,
+/// including the current one.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/sessions/revoke_all")]
+fn revoke_all<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    mut cookies: Cookies,
+) -> Json<Message<'a>> {
+    match database::revoke_other_sessions(&conn, &redis_conn, user.id, None) {
+        Ok(_) => {
+            cookies.remove_private(Cookie::named("session_token"));
+            Json(Message {
+                text: "You have been logged out everywhere.",
+            })
+        }
+        Err(_) => Json(Message {
+            text: "Could not revoke the sessions.",
+        }),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![sessions_page, sessions_page_nouser, revoke, revoke_all]
+}
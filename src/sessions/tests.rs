@@ -0,0 +1,15 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+
+    #[test]
+    fn sessions_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/sessions").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
+}
@@ -0,0 +1,315 @@
+use authentication;
+use authentication::rate_limit;
+use club_coding::create_new_users_verify_email;
+use database::{DbConn, RedisConnection};
+use email::{EmailBody, PostmarkClient};
+use passwords;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use sessions;
+use settings;
+use std::io::{Error, ErrorKind};
+use structs::{EmailRegex, PostmarkToken, TokenLifetime};
+use users::User;
+
+/// Context for rendering tera templates
+/// for the email settings endpoint.
+#[derive(Serialize)]
+struct EmailContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// The new email waiting to be
+    /// confirmed, if there is one.
+    pending_email: Option<String>,
+}
+
+/// Struct for emails about
+/// changing the email.
+#[derive(Serialize)]
+struct ChangeEmail<'a> {
+    /// Confirm email token
+    /// to be rendered using tera.
+    token: &'a str,
+    /// The new email of the user.
+    new_email: &'a str,
+}
+
+/// Renders the email template and
+/// sends it to the email.
+fn send_mail(
+    postmark_token: &str,
+    template: &str,
+    subject: &str,
+    email: &str,
+    change: &ChangeEmail,
+) -> Result<(), Error> {
+    let tera = compile_templates!("templates/emails/**/*");
+    match tera.render(template, change) {
+        Ok(html_body) => {
+            let body = EmailBody {
+                from: "axel@clubcoding.com".to_string(),
+                to: email.to_string(),
+                subject: Some(subject.to_string()),
+                html_body: Some(html_body),
+                cc: None,
+                bcc: None,
+                tag: None,
+                text_body: None,
+                reply_to: None,
+                headers: None,
+                track_opens: None,
+                track_links: None,
+            };
+            let postmark_client = PostmarkClient::new(postmark_token);
+            postmark_client.send_email(&body)?;
+            Ok(())
+        }
+        Err(_) => Err(Error::new(ErrorKind::Other, "couldn't render template")),
+    }
+}
+
+/// Generates a token for the new email and
+/// inserts it into the database. Sends a link
+/// with the token to the new email and lets
+/// the old email know about the change.
+fn create_and_send_email_change(
+    connection: &DbConn,
+    postmark_token: &str,
+    user: &User,
+    new_email: &str,
+) -> Result<(), Error> {
+    settings::database::invalidate_email_changes(connection, user.id)?;
+    let token = authentication::generate_token(30);
+    create_new_users_verify_email(&**connection, user.id, &token, Some(new_email))?;
+
+    let change = ChangeEmail {
+        token: &token,
+        new_email: new_email,
+    };
+    send_mail(
+        postmark_token,
+        "confirm_email_change.html.tera",
+        "Confirm your new email",
+        new_email,
+        &change,
+    )?;
+    send_mail(
+        postmark_token,
+        "email_change_requested.html.tera",
+        "Your email is being changed",
+        &user.email,
+        &change,
+    )
+}
+
+/// GET Endpoint for the page to change your
+/// email. Endpoints checks if the user is
+/// logged in by using the user request guard.
+/// If the user is not logged in it forwards
+/// the request.
+/// Responds with the Email Template
+/// in the settings folder.
+#[get("/settings/email")]
+fn email_page(conn: DbConn, lifetime: State<TokenLifetime>, user: User) -> Template {
+    let pending_email =
+        settings::database::get_pending_email_change(&conn, user.id, lifetime.verify_hours)
+            .and_then(|change| change.new_email);
+    let context = EmailContext {
+        header: "Update Email",
+        user: user,
+        pending_email: pending_email,
+    };
+    Template::render("settings/email", &context)
+}
+
+/// GET Endpoint for the page to change your
+/// email. This endpoint will kick in if the
+/// user is not logged in and will redirect
+/// the user to the index.
+#[get("/settings/email", rank = 2)]
+fn email_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for changing
+/// the email of a user.
+#[derive(Deserialize)]
+struct UpdateEmailStruct {
+    /// The current password of the user.
+    password: String,
+    /// The new email for the user.
+    new_email: String,
+}
+
+/// Struct for responding with a JSON
+/// message.
+#[derive(Serialize)]
+struct Message<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+}
+
+/// POST Endpoint for the page to change your
+/// email. Endpoints checks if the user is
+/// logged in by using the user request guard.
+/// If the user is not logged in it forwards
+/// the request.
+/// It requires all of the parameters in the
+/// UpdateEmailStruct in a JSON format.
+/// The email is not changed until the user
+/// has clicked the link sent to the new email.
+/// Requests are rate limited by the user and
+/// by the new email.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/email", format = "application/json", data = "<json_data>")]
+fn update_email<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    email_regex: State<EmailRegex>,
+    postmark_token: State<PostmarkToken>,
+    user: User,
+    json_data: Json<UpdateEmailStruct>,
+) -> Json<Message<'a>> {
+    let new_email = json_data.new_email.trim().to_lowercase();
+    if !email_regex.0.is_match(&new_email) {
+        return Json(Message {
+            text: "Email is not valid.",
+        });
+    }
+    if new_email == user.email {
+        return Json(Message {
+            text: "That is already your email.",
+        });
+    }
+
+    // Counted before the password is checked so
+    // it can't be used to guess the password.
+    let user_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::email_change_user_key(user.id),
+        rate_limit::RECOVER_WINDOW,
+    );
+    if user_attempts > rate_limit::EMAIL_CHANGE_ATTEMPTS_PER_USER {
+        return Json(Message {
+            text: "Too many attempts. Please try again later.",
+        });
+    }
+
+    let password_hash = match settings::database::get_password_hash_from_userid(&conn, user.id)
+    {
+        Ok(password_hash) => password_hash,
+        Err(_) => {
+            return Json(Message {
+                text: "An unknown error occured. Please try again later.",
+            })
+        }
+    };
+    match passwords::verify_password(&json_data.password, &password_hash) {
+        Ok(true) => {}
+        _ => {
+            return Json(Message {
+                text: "The password is incorrect.",
+            })
+        }
+    }
+
+    if authentication::database::email_exists(&conn, &new_email) {
+        return Json(Message {
+            text: "Email is already in use.",
+        });
+    }
+
+    let email_attempts = rate_limit::increment(
+        &redis_conn,
+        &rate_limit::verify_email_key(&new_email),
+        rate_limit::RECOVER_WINDOW,
+    );
+    if email_attempts > rate_limit::RECOVER_ATTEMPTS_PER_EMAIL {
+        return Json(Message {
+            text: "Too many attempts. Please try again later.",
+        });
+    }
+
+    match create_and_send_email_change(&conn, &postmark_token.0, &user, &new_email) {
+        Ok(_) => Json(Message {
+            text: "Check your new email for a link to confirm the change.",
+        }),
+        Err(_) => Json(Message {
+            text: "An unknown error occured. Please try again later.",
+        }),
+    }
+}
+
+/// GET Endpoint to confirm a new email.
+/// Checks if the UUID is in the database
+/// and is valid. If it is, it updates the
+/// email of the user and makes the UUID
+/// invalid. Works whether the user is
+/// logged in or not. Redirects to the
+/// index with an appropriate message.
+#[get("/email/change/<uuid>")]
+fn confirm_email(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    lifetime: State<TokenLifetime>,
+    uuid: String,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let result = match settings::database::get_email_change_by_token(&conn, &uuid) {
+        Some(result) => result,
+        None => return Err(Flash::error(Redirect::to("/"), "Link incorrect.")),
+    };
+    if result.used {
+        return Err(Flash::error(Redirect::to("/"), "Link already used."));
+    }
+    if authentication::token_expired(result.created, lifetime.verify_hours) {
+        return Err(Flash::error(
+            Redirect::to("/"),
+            "The link has expired. Please change your email again.",
+        ));
+    }
+    let new_email = match result.new_email {
+        Some(new_email) => new_email,
+        None => return Err(Flash::error(Redirect::to("/"), "Link incorrect.")),
+    };
+    if authentication::database::email_exists(&conn, &new_email) {
+        return Err(Flash::error(
+            Redirect::to("/"),
+            "Email is already in use.",
+        ));
+    }
+
+    match settings::database::invalidate_token_and_update_email(
+        &conn,
+        result.id,
+        result.user_id,
+        &new_email,
+    ) {
+        Ok(_) => {
+            sessions::database::clear_cached_users(&conn, &redis_conn, result.user_id);
+            Ok(Flash::success(
+                Redirect::to("/"),
+                "Your email has been updated.",
+            ))
+        }
+        Err(_) => Err(Flash::error(
+            Redirect::to("/"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![email_page, email_page_nouser, update_email, confirm_email]
+}
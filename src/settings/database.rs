@@ -0,0 +1,114 @@
+use authentication;
+use club_coding::models::{Users, UsersVerifyEmail};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel;
+use diesel::Connection;
+use diesel::prelude::*;
+
+/// The function gets the password hash
+/// stored in the database for the user
+/// by using the userid.
+/// Responds with either the password hash
+/// or an error.
+pub fn get_password_hash_from_userid(connection: &DbConn, user_id: i64) -> Result<String, Error> {
+    use club_coding::schema::users::dsl::*;
+
+    match users.find(user_id).first::<Users>(&**connection) {
+        Ok(result) => Ok(result.password),
+        Err(_) => Err(Error::new(ErrorKind::Other, "No user found")),
+    }
+}
+
+/// Gets the latest unused email change
+/// of the user that has not expired.
+pub fn get_pending_email_change(
+    connection: &DbConn,
+    uid: i64,
+    lifetime_hours: i64,
+) -> Option<UsersVerifyEmail> {
+    use club_coding::schema::users_verify_email::dsl::*;
+
+    match users_verify_email
+        .filter(user_id.eq(uid))
+        .filter(used.eq(false))
+        .filter(new_email.is_not_null())
+        .order(created.desc())
+        .first::<UsersVerifyEmail>(&**connection)
+    {
+        Ok(result) => {
+            if authentication::token_expired(result.created, lifetime_hours) {
+                None
+            } else {
+                Some(result)
+            }
+        }
+        Err(_) => None,
+    }
+}
+
+/// Gets the email change from the token.
+pub fn get_email_change_by_token(
+    connection: &DbConn,
+    uuid_token: &str,
+) -> Option<UsersVerifyEmail> {
+    use club_coding::schema::users_verify_email::dsl::*;
+
+    match users_verify_email
+        .filter(token.eq(uuid_token))
+        .filter(new_email.is_not_null())
+        .first::<UsersVerifyEmail>(&**connection)
+    {
+        Ok(result) => Some(result),
+        Err(_) => None,
+    }
+}
+
+/// Invalidates every unused email change
+/// of the user. Used when a new email
+/// change is requested so only the
+/// latest link works.
+pub fn invalidate_email_changes(connection: &DbConn, uid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_verify_email::dsl::*;
+
+    match diesel::update(
+        users_verify_email
+            .filter(user_id.eq(uid))
+            .filter(used.eq(false))
+            .filter(new_email.is_not_null()),
+    ).set(used.eq(true))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not update email changes.",
+        )),
+    }
+}
+
+/// Invalidates the token and updates the
+/// email of the user in one transaction, so
+/// the token is only used if the email is.
+pub fn invalidate_token_and_update_email(
+    connection: &DbConn,
+    verification_id: i64,
+    uid: i64,
+    updated_email: &str,
+) -> Result<(), Error> {
+    use club_coding::schema::{users, users_verify_email};
+
+    let updated = connection.transaction::<_, diesel::result::Error, _>(|| {
+        diesel::update(users_verify_email::table.find(verification_id))
+            .set(users_verify_email::used.eq(true))
+            .execute(&**connection)?;
+        diesel::update(users::table.find(uid))
+            .set(users::email.eq(updated_email))
+            .execute(&**connection)?;
+        Ok(())
+    });
+    match updated {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update email.")),
+    }
+}
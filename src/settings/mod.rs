@@ -1,15 +1,21 @@
 use rocket::Route;
-use bcrypt::{hash, verify, DEFAULT_COST};
 use rocket_contrib::templates::Template;
 use rocket_contrib::json::Json;
 use rocket::response::Redirect;
-use club_coding::models::Users;
-use database::DbConn;
+use rocket::http::Cookies;
+use audit::{self, AuditEvent};
+use database::{DbConn, RedisConnection};
+use passwords::{self, BcryptCost, PasswordPolicy};
+use rocket::State;
+use sessions::{self, ClientInfo};
 use users::User;
 use std::io::{Error, ErrorKind};
 use diesel;
 use diesel::prelude::*;
 
+mod change_email;
+pub mod database;
+
 #[cfg(test)]
 mod tests;
 
@@ -23,6 +29,9 @@ pub struct PasswordContext<'a> {
     /// The user struct used by templates.
     /// For example the username for the toolbar.
     pub user: User,
+    /// The minimum number of characters
+    /// in a password, shown as a hint.
+    pub min_length: usize,
 }
 
 /// GET Endpoint for the page to change your
@@ -34,10 +43,11 @@ pub struct PasswordContext<'a> {
 /// Responds with the Password Template
 /// in the settings folder.
 #[get("/settings/password")]
-fn password_page(user: User) -> Template {
+fn password_page(password_policy: State<PasswordPolicy>, user: User) -> Template {
     let context = PasswordContext {
         header: "Update Password",
         user: user,
+        min_length: password_policy.min_length,
     };
     Template::render("settings/password", &context)
 }
@@ -68,24 +78,10 @@ struct UpdatePasswordStruct {
 /// Struct for responding with a JSON
 /// message.
 #[derive(Serialize)]
-struct Message<'a> {
+struct Message {
     /// The message that will be
     /// forwarded to the user.
-    text: &'a str,
-}
-
-/// The function gets the password hash
-/// stored in the database for the user
-/// by using the userid.
-/// Responds with either the password hash
-/// or an error.
-fn get_password_hash_from_userid(connection: &DbConn, user_id: i64) -> Result<String, Error> {
-    use club_coding::schema::users::dsl::*;
-
-    match users.find(user_id).first::<Users>(&**connection) {
-        Ok(result) => Ok(result.password),
-        Err(_) => return Err(Error::new(ErrorKind::Other, "No user found")),
-    }
+    text: String,
 }
 
 /// Hashes the password sent in
@@ -96,23 +92,21 @@ fn hash_and_update_password(
     connection: &DbConn,
     user_id: i64,
     new_password: &str,
+    cost: u32,
 ) -> Result<(), Error> {
-    match hash(new_password, DEFAULT_COST) {
-        Ok(hashed_password) => {
-            use club_coding::schema::users::dsl::*;
-
-            match diesel::update(users.filter(id.eq(user_id)))
-                .set(password.eq(hashed_password))
-                .execute(&**connection)
-            {
-                Ok(_) => Ok(()),
-                Err(_) => Err(Error::new(
-                    ErrorKind::Other,
-                    "Could not update database with new password.",
-                )),
-            }
-        }
-        Err(_) => Err(Error::new(ErrorKind::Other, "Could not hash password.")),
+    let hashed_password = passwords::hash_password(new_password, cost)?;
+
+    use club_coding::schema::users::dsl::*;
+
+    match diesel::update(users.filter(id.eq(user_id)))
+        .set(password.eq(hashed_password))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not update database with new password.",
+        )),
     }
 }
 
@@ -124,15 +118,17 @@ fn compare_and_insert_password<'a>(
     user_id: i64,
     old_password: &str,
     new_password: &str,
+    cost: u32,
 ) -> Result<(), &'a str> {
-    let password_hash: String = match get_password_hash_from_userid(connection, user_id) {
+    let password_hash: String = match database::get_password_hash_from_userid(connection, user_id)
+    {
         Ok(password_hash) => password_hash,
         Err(_) => return Err("No password found in database for the user."),
     };
 
     // If it fails, it is the hashing and verifying that fails. It does not
     // mean that the passwords match or do not match.
-    match verify(old_password, &password_hash) {
+    match passwords::verify_password(old_password, &password_hash) {
         Ok(passwords_match) => {
             if !passwords_match {
                 return Err("The old password is incorrect.");
@@ -143,7 +139,7 @@ fn compare_and_insert_password<'a>(
         }
     };
 
-    match hash_and_update_password(connection, user_id, new_password) {
+    match hash_and_update_password(connection, user_id, new_password, cost) {
         Ok(_) => Ok(()),
         Err(_) => Err("An unknown error occured. Please try again later."),
     }
@@ -158,17 +154,31 @@ fn compare_and_insert_password<'a>(
 /// It requires all of the parameters in the
 /// UpdatePasswordStruct in a JSON format.
 /// If everything is successful, it will update
-/// the users password and return the Message
+/// the users password, log the user out of
+/// every other session and return the Message
 /// struct in a JSON format.
 #[post("/settings/password", data = "<json_data>")]
-fn update_password<'a>(
+fn update_password(
     conn: DbConn,
+    redis_conn: RedisConnection,
+    bcrypt_cost: State<BcryptCost>,
+    password_policy: State<PasswordPolicy>,
+    client: ClientInfo,
     user: User,
+    mut cookies: Cookies,
     json_data: Json<UpdatePasswordStruct>,
-) -> Json<Message<'a>> {
+) -> Json<Message> {
     if !(json_data.new_password == json_data.confirm_new_password) {
         return Json(Message {
-            text: "The new passwords are not matching.",
+            text: "The new passwords are not matching.".to_string(),
+        });
+    }
+
+    let policy_result =
+        password_policy.check(&json_data.new_password, &user.username, &user.email);
+    if let Err(error) = policy_result {
+        return Json(Message {
+            text: error.message(),
         });
     }
 
@@ -177,13 +187,38 @@ fn update_password<'a>(
         user.id,
         &json_data.old_password,
         &json_data.new_password,
+        bcrypt_cost.0,
     ) {
         Ok(_) => {
+            let current_token = cookies
+                .get_private("session_token")
+                .map(|cookie| cookie.value().to_string());
+            match sessions::database::revoke_other_sessions(
+                &conn,
+                &redis_conn,
+                user.id,
+                current_token.as_ref().map(|token| token.as_str()),
+            ) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+            audit::record(
+                &conn,
+                AuditEvent::PasswordChanged,
+                Some(user.id),
+                None,
+                Some(&client),
+                None,
+            );
+            return Json(Message {
+                text: "Your password has been updated".to_string(),
+            });
+        }
+        Err(error) => {
             return Json(Message {
-                text: "Your password has been updated",
+                text: error.to_string(),
             })
         }
-        Err(error) => return Json(Message { text: error }),
     }
 }
 
@@ -192,5 +227,10 @@ fn update_password<'a>(
 /// is that we don't have to update the main function but
 /// instead we can keep all of the changes in here.
 pub fn endpoints() -> Vec<Route> {
-    routes![password_page, password_page_nouser, update_password]
+    let mut total = routes![password_page, password_page_nouser, update_password];
+
+    let mut change_email = change_email::endpoints();
+    total.append(&mut change_email);
+
+    total
 }
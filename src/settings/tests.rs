@@ -12,4 +12,13 @@ mod test {
         assert_eq!(response.status(), Status::SeeOther);
         assert_eq!(response.content_type(), None);
     }
+
+    #[test]
+    fn update_email_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/email").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
 }
@@ -36,9 +36,13 @@ pub struct StripeToken {
     /// show in public. Only to be used
     /// in Rust code.
     pub secret_key: String,
+    /// Signing secret of the webhook
+    /// endpoint. Used to verify that
+    /// events were sent by Stripe.
+    pub webhook_secret: String,
 }
 
-/// Returns a AdHoc Fairing with two Stripe Tokens
+/// Returns a AdHoc Fairing with the Stripe Tokens
 /// Will panic if no Stripe Tokens are set in
 /// Rocket.toml File
 pub fn stripe_token_fairing() -> rocket::fairing::AdHoc {
@@ -53,9 +57,14 @@ pub fn stripe_token_fairing() -> rocket::fairing::AdHoc {
             .get_str("stripe_secret")
             .expect("stripe_secret key not specified");
 
+        let webhook_secret = config
+            .get_str("stripe_webhook_secret")
+            .expect("stripe_webhook_secret key not specified");
+
         Ok(rocket.manage(StripeToken {
             publishable_key: publishable.to_string(),
             secret_key: secret.to_string(),
+            webhook_secret: webhook_secret.to_string(),
         }))
     })
 }
@@ -98,3 +107,38 @@ pub fn email_regex_fairing() -> rocket::fairing::AdHoc {
         Ok(rocket.manage(EmailRegex(email_regex)))
     })
 }
+
+/// Struct for the lifetime of the tokens
+/// sent by email. Used in endpoints that
+/// verify emails and recover accounts.
+pub struct TokenLifetime {
+    /// Number of hours a verify
+    /// email token is valid.
+    pub verify_hours: i64,
+    /// Number of hours a recover
+    /// account token is valid.
+    pub recover_hours: i64,
+}
+
+/// Returns a AdHoc Fairing with the Token Lifetimes.
+/// Defaults to 48 hours for verify email tokens and
+/// 2 hours for recover account tokens if they are not
+/// set in Rocket.toml File
+pub fn token_lifetime_fairing() -> rocket::fairing::AdHoc {
+    AdHoc::on_attach("TokenLifetime", |rocket| {
+        let config = rocket.config().clone();
+
+        let verify_hours = config
+            .get_int("verify_token_lifetime_hours")
+            .unwrap_or(48);
+
+        let recover_hours = config
+            .get_int("recover_token_lifetime_hours")
+            .unwrap_or(2);
+
+        Ok(rocket.manage(TokenLifetime {
+            verify_hours: verify_hours,
+            recover_hours: recover_hours,
+        }))
+    })
+}
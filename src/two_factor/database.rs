@@ -0,0 +1,166 @@
+use club_coding::models::{UsersTwoFactor, UsersTwoFactorRecovery};
+use club_coding::{create_new_user_two_factor, create_new_user_two_factor_recovery};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel::prelude::*;
+
+/// Gets the two-factor settings of the
+/// user. Returns either Some settings or
+/// None if the user has never started
+/// enrolling.
+pub fn get_two_factor(connection: &DbConn, uid: i64) -> Option<UsersTwoFactor> {
+    use club_coding::schema::users_two_factor::dsl::*;
+
+    match users_two_factor
+        .filter(user_id.eq(uid))
+        .first::<UsersTwoFactor>(&**connection)
+    {
+        Ok(two_factor) => Some(two_factor),
+        Err(_) => None,
+    }
+}
+
+/// Checks if the user has enabled
+/// two-factor authentication.
+pub fn is_enabled(connection: &DbConn, uid: i64) -> bool {
+    match get_two_factor(connection, uid) {
+        Some(two_factor) => two_factor.enabled,
+        None => false,
+    }
+}
+
+/// Stores a new secret for the user that
+/// has not been confirmed yet. Replaces
+/// the secret of an earlier enrolment
+/// that was never confirmed.
+pub fn set_pending_secret(connection: &DbConn, uid: i64, new_secret: &str) -> Result<(), Error> {
+    use club_coding::schema::users_two_factor::dsl::*;
+
+    match get_two_factor(connection, uid) {
+        Some(two_factor) => {
+            if two_factor.enabled {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "Two-factor authentication already enabled.",
+                ));
+            }
+            match diesel::update(users_two_factor.find(two_factor.id))
+                .set((secret.eq(new_secret), last_step.eq(0)))
+                .execute(&**connection)
+            {
+                Ok(_) => Ok(()),
+                Err(_) => Err(Error::new(ErrorKind::Other, "Could not update secret.")),
+            }
+        }
+        None => create_new_user_two_factor(&**connection, uid, new_secret),
+    }
+}
+
+/// Enables two-factor authentication
+/// for the user and stores the time step
+/// of the code used to confirm it.
+pub fn enable(connection: &DbConn, uid: i64, step: i64) -> Result<(), Error> {
+    use club_coding::schema::users_two_factor::dsl::*;
+
+    match diesel::update(users_two_factor.filter(user_id.eq(uid)))
+        .set((enabled.eq(true), last_step.eq(step)))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not enable two-factor authentication.",
+        )),
+    }
+}
+
+/// Stores the time step of the last code
+/// that was used so it can not be used
+/// again.
+pub fn set_last_step(connection: &DbConn, uid: i64, step: i64) -> Result<(), Error> {
+    use club_coding::schema::users_two_factor::dsl::*;
+
+    match diesel::update(users_two_factor.filter(user_id.eq(uid)))
+        .set(last_step.eq(step))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update time step.")),
+    }
+}
+
+/// Disables two-factor authentication
+/// for the user by deleting the secret
+/// and all of the recovery codes.
+pub fn disable(connection: &DbConn, uid: i64) -> Result<(), Error> {
+    use club_coding::schema::{users_two_factor, users_two_factor_recovery};
+
+    match diesel::delete(
+        users_two_factor_recovery::table.filter(users_two_factor_recovery::user_id.eq(uid)),
+    ).execute(&**connection)
+    {
+        Ok(_) => {}
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not delete recovery codes.")),
+    }
+
+    match diesel::delete(users_two_factor::table.filter(users_two_factor::user_id.eq(uid)))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not disable two-factor authentication.",
+        )),
+    }
+}
+
+/// Gets the recovery codes of the
+/// user that have not been used.
+pub fn get_unused_recovery_codes(connection: &DbConn, uid: i64) -> Vec<UsersTwoFactorRecovery> {
+    use club_coding::schema::users_two_factor_recovery::dsl::*;
+
+    match users_two_factor_recovery
+        .filter(user_id.eq(uid))
+        .filter(used.eq(false))
+        .load::<UsersTwoFactorRecovery>(&**connection)
+    {
+        Ok(codes) => codes,
+        Err(_) => vec![],
+    }
+}
+
+/// Replaces all of the recovery codes of
+/// the user with the hashed codes.
+pub fn replace_recovery_codes(
+    connection: &DbConn,
+    uid: i64,
+    hashed_codes: &[String],
+) -> Result<(), Error> {
+    use club_coding::schema::users_two_factor_recovery::dsl::*;
+
+    match diesel::delete(users_two_factor_recovery.filter(user_id.eq(uid)))
+        .execute(&**connection)
+    {
+        Ok(_) => {}
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not delete recovery codes.")),
+    }
+
+    for hashed_code in hashed_codes {
+        create_new_user_two_factor_recovery(&**connection, uid, hashed_code)?;
+    }
+    Ok(())
+}
+
+/// Marks the recovery code specified
+/// by the id as used.
+pub fn use_recovery_code(connection: &DbConn, rid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_two_factor_recovery::dsl::*;
+
+    match diesel::update(users_two_factor_recovery.find(rid))
+        .set(used.eq(true))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not use recovery code.")),
+    }
+}
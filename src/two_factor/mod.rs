@@ -0,0 +1,689 @@
+pub mod database;
+
+use audit::{self, AuditEvent};
+use authentication;
+use authentication::rate_limit;
+use chrono::Utc;
+use club_coding::models::Users;
+use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use database::{DbConn, RedisConnection};
+use hmac::{Hmac, Mac};
+use passwords;
+use redis::Commands;
+use rocket::http::{Cookie, Cookies};
+use rocket::request::{FlashMessage, Form};
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_contrib::json::Json;
+use rocket_contrib::templates::Template;
+use sessions::{self, ClientInfo};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::{Error, ErrorKind};
+use structs::PostmarkToken;
+use users::User;
+use diesel::prelude::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of seconds each
+/// code is valid for.
+static TIME_STEP: u64 = 30;
+
+/// Number of digits in a code.
+static DIGITS: u32 = 6;
+
+/// Number of time steps before and
+/// after the current one that are
+/// accepted to allow for clock drift.
+static WINDOW: i64 = 1;
+
+/// Number of recovery codes
+/// generated for a user.
+static RECOVERY_CODES: usize = 10;
+
+/// Number of seconds the user has
+/// to enter the code after entering
+/// the password.
+static PENDING_LOGIN_TTL: usize = 300;
+
+/// Number of wrong codes allowed
+/// before the user has to enter
+/// the password again.
+static MAX_ATTEMPTS: i64 = 5;
+
+/// The issuer shown in
+/// authenticator apps.
+static ISSUER: &'static str = "Club Coding";
+
+/// Generates a new random secret
+/// encoded in base32.
+fn generate_secret() -> String {
+    let bytes: Vec<u8> = (0..20).map(|_| rand::random::<u8>()).collect();
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// Calculates the HOTP value for the
+/// counter as defined in RFC 4226.
+pub fn hotp(key: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = match Hmac::<Sha1>::new_varkey(key) {
+        Ok(mac) => mac,
+        Err(_) => return 0,
+    };
+    let message: Vec<u8> = (0..8).map(|i| (counter >> (56 - i * 8)) as u8).collect();
+    mac.input(&message);
+    let result = mac.result().code();
+
+    let offset = (result[result.len() - 1] & 0x0f) as usize;
+    let binary = ((result[offset] as u32 & 0x7f) << 24)
+        | ((result[offset + 1] as u32) << 16)
+        | ((result[offset + 2] as u32) << 8)
+        | (result[offset + 3] as u32);
+    binary % 10u32.pow(digits)
+}
+
+/// Calculates the TOTP value for the
+/// unix time as defined in RFC 6238.
+pub fn totp(key: &[u8], time: u64, digits: u32) -> u32 {
+    hotp(key, time / TIME_STEP, digits)
+}
+
+/// Checks the code against the secret
+/// for the current time. Codes from time
+/// steps at or before the last used one are
+/// rejected so a code can only be used once.
+/// Returns the time step of the code if
+/// it is valid.
+fn verify_totp(secret: &str, code: &str, last_step: i64) -> Option<i64> {
+    let key = match BASE32_NOPAD.decode(secret.as_bytes()) {
+        Ok(key) => key,
+        Err(_) => return None,
+    };
+    let code = code.trim().replace(" ", "");
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code: u32 = match code.parse() {
+        Ok(code) => code,
+        Err(_) => return None,
+    };
+
+    let current = Utc::now().timestamp() / TIME_STEP as i64;
+    for step in (current - WINDOW)..(current + WINDOW + 1) {
+        if step <= last_step || step < 0 {
+            continue;
+        }
+        if hotp(&key, step as u64, DIGITS) == code {
+            return Some(step);
+        }
+    }
+    None
+}
+
+/// Percent encodes the text so it
+/// can be used in an otpauth URI.
+fn encode_uri_component(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Builds the otpauth URI that authenticator
+/// apps read from the QR code.
+fn otpauth_uri(username: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = encode_uri_component(ISSUER),
+        username = encode_uri_component(username),
+        secret = secret,
+        digits = DIGITS,
+        period = TIME_STEP,
+    )
+}
+
+/// Hashes the recovery code with SHA-256.
+/// Recovery codes are random so they don't
+/// need a slow hash like passwords do.
+pub fn hash_recovery_code(code: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(code.trim().to_lowercase().as_bytes()))
+}
+
+/// Compares the two values in constant
+/// time so the time taken doesn't reveal
+/// how much of them is the same.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Checks the code entered by the user. The
+/// code can either be a code from the
+/// authenticator app or one of the recovery
+/// codes. A code that is accepted can not
+/// be used again. The codes checked are
+/// rate limited per user, and every code is
+/// rejected once the limit is reached.
+fn check_code(conn: &DbConn, redis_conn: &RedisConnection, user_id: i64, code: &str) -> bool {
+    let attempts = rate_limit::increment(
+        redis_conn,
+        &rate_limit::two_factor_user_key(user_id),
+        rate_limit::TWO_FACTOR_WINDOW,
+    );
+    if attempts > rate_limit::TWO_FACTOR_ATTEMPTS_PER_USER {
+        return false;
+    }
+
+    let two_factor = match database::get_two_factor(conn, user_id) {
+        Some(two_factor) => two_factor,
+        None => return false,
+    };
+
+    if let Some(step) = verify_totp(&two_factor.secret, code, two_factor.last_step) {
+        rate_limit::clear(redis_conn, &rate_limit::two_factor_user_key(user_id));
+        return database::set_last_step(conn, user_id, step).is_ok();
+    }
+
+    let hashed_code = hash_recovery_code(code);
+    for recovery_code in database::get_unused_recovery_codes(conn, user_id) {
+        if constant_time_eq(hashed_code.as_bytes(), recovery_code.code.as_bytes()) {
+            rate_limit::clear(redis_conn, &rate_limit::two_factor_user_key(user_id));
+            return database::use_recovery_code(conn, recovery_code.id).is_ok();
+        }
+    }
+    false
+}
+
+/// Generates new recovery codes for the
+/// user and stores them hashed. Returns
+/// the codes in plain text so they can be
+/// shown to the user once.
+fn generate_recovery_codes(conn: &DbConn, user_id: i64) -> Result<Vec<String>, Error> {
+    let codes: Vec<String> = (0..RECOVERY_CODES)
+        .map(|_| authentication::generate_token(5).to_lowercase())
+        .collect();
+    let hashed_codes: Vec<String> = codes.iter().map(|code| hash_recovery_code(code)).collect();
+    database::replace_recovery_codes(conn, user_id, &hashed_codes)?;
+    Ok(codes)
+}
+
+/// Removes the cached user of the current
+/// session from Redis so the request guards
+/// pick up the new two-factor status.
+fn clear_cached_user(redis_conn: &RedisConnection, cookies: &mut Cookies) {
+    if let Some(cookie) = cookies.get_private("session_token") {
+        match redis_conn.del::<&str, String>(cookie.value()) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+    }
+}
+
+/// Starts the second step of the login for
+/// a user with two-factor authentication.
+/// The user id is stored in Redis under a
+/// random token that is set as a cookie
+/// until the user has entered the code.
+pub fn begin_login(
+    redis_conn: &RedisConnection,
+    cookies: &mut Cookies,
+    user_id: i64,
+) -> Result<(), Error> {
+    let token = authentication::generate_token(32);
+    match redis_conn.set_ex::<String, i64, String>(
+        format!("two_factor:{}", token),
+        user_id,
+        PENDING_LOGIN_TTL,
+    ) {
+        Ok(_) => {
+            cookies.add_private(Cookie::new("two_factor_token", token));
+            Ok(())
+        }
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not store login.")),
+    }
+}
+
+/// Context for rendering tera templates
+/// for the two-factor settings endpoint.
+#[derive(Serialize)]
+struct TwoFactorContext<'a> {
+    /// Header used in tera templates.
+    /// Mainly used for the title.
+    header: &'a str,
+    /// The user struct used by templates.
+    /// For example the username for the toolbar.
+    user: User,
+    /// Boolean of whether two-factor
+    /// authentication is enabled.
+    enabled: bool,
+    /// The secret to enter in the
+    /// authenticator app. Empty if
+    /// already enabled.
+    secret: String,
+    /// The otpauth URI shown as a QR
+    /// code. Empty if already enabled.
+    otpauth_uri: String,
+    /// Number of recovery codes
+    /// that have not been used.
+    recovery_codes_left: usize,
+}
+
+/// GET Endpoint for the page to set up
+/// two-factor authentication. Endpoints
+/// checks if the user is logged in by
+/// using the user request guard. If the
+/// user is not logged in it forwards
+/// the request.
+/// A new secret is generated every time
+/// the page is opened until the user has
+/// confirmed it with a code.
+/// Responds with the Two Factor Template
+/// in the settings folder.
+#[get("/settings/two_factor")]
+fn settings_page(conn: DbConn, user: User) -> Template {
+    let enabled = database::is_enabled(&conn, user.id);
+    let (secret, uri) = if enabled {
+        ("".to_string(), "".to_string())
+    } else {
+        let secret = generate_secret();
+        match database::set_pending_secret(&conn, user.id, &secret) {
+            Ok(_) => {
+                let uri = otpauth_uri(&user.username, &secret);
+                (secret, uri)
+            }
+            Err(_) => ("".to_string(), "".to_string()),
+        }
+    };
+    let context = TwoFactorContext {
+        header: "Two-Factor Authentication",
+        recovery_codes_left: database::get_unused_recovery_codes(&conn, user.id).len(),
+        user: user,
+        enabled: enabled,
+        secret: secret,
+        otpauth_uri: uri,
+    };
+    Template::render("settings/two_factor", &context)
+}
+
+/// GET Endpoint for the page to set up
+/// two-factor authentication. This endpoint
+/// will kick in if the user is not logged
+/// in and will redirect the user to the index.
+#[get("/settings/two_factor", rank = 2)]
+fn settings_page_nouser() -> Redirect {
+    Redirect::to("/")
+}
+
+/// Struct for confirming a code
+/// from the authenticator app.
+#[derive(Deserialize)]
+struct CodeStruct {
+    /// The code from the authenticator
+    /// app or a recovery code.
+    code: String,
+}
+
+/// Struct for responding with a JSON
+/// message and recovery codes.
+#[derive(Serialize)]
+struct RecoveryCodesMessage<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+    /// The new recovery codes. Only
+    /// shown to the user once.
+    recovery_codes: Vec<String>,
+}
+
+/// POST Endpoint to enable two-factor
+/// authentication. Endpoints checks if the
+/// user is logged in by using the user
+/// request guard. If the user is not logged
+/// in it forwards the request.
+/// It requires the parameter in the
+/// CodeStruct in a JSON format. The code
+/// has to match the secret shown on the
+/// settings page.
+/// Responds with the recovery codes in
+/// a JSON format.
+#[post("/settings/two_factor/enable", format = "application/json", data = "<data>")]
+fn enable<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    mut cookies: Cookies,
+    data: Json<CodeStruct>,
+) -> Json<RecoveryCodesMessage<'a>> {
+    let two_factor = match database::get_two_factor(&conn, user.id) {
+        Some(ref two_factor) if !two_factor.enabled => two_factor.secret.clone(),
+        _ => {
+            return Json(RecoveryCodesMessage {
+                text: "Reload the page and try again.",
+                recovery_codes: vec![],
+            })
+        }
+    };
+
+    let step = match verify_totp(&two_factor, &data.0.code, 0) {
+        Some(step) => step,
+        None => {
+            return Json(RecoveryCodesMessage {
+                text: "The code is incorrect.",
+                recovery_codes: vec![],
+            })
+        }
+    };
+
+    let codes = match database::enable(&conn, user.id, step)
+        .and_then(|_| generate_recovery_codes(&conn, user.id))
+    {
+        Ok(codes) => codes,
+        Err(_) => {
+            return Json(RecoveryCodesMessage {
+                text: "An unknown error occured. Please try again later.",
+                recovery_codes: vec![],
+            })
+        }
+    };
+
+    clear_cached_user(&redis_conn, &mut cookies);
+    Json(RecoveryCodesMessage {
+        text: "Two-factor authentication has been enabled.",
+        recovery_codes: codes,
+    })
+}
+
+/// POST Endpoint to generate new recovery
+/// codes. Endpoints checks if the user is
+/// logged in by using the user request
+/// guard. If the user is not logged in it
+/// forwards the request.
+/// It requires the parameter in the
+/// CodeStruct in a JSON format. The old
+/// recovery codes stop working.
+/// Responds with the new recovery codes
+/// in a JSON format.
+#[post("/settings/two_factor/recovery_codes", format = "application/json", data = "<data>")]
+fn recovery_codes<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    data: Json<CodeStruct>,
+) -> Json<RecoveryCodesMessage<'a>> {
+    if !database::is_enabled(&conn, user.id)
+        || !check_code(&conn, &redis_conn, user.id, &data.0.code)
+    {
+        return Json(RecoveryCodesMessage {
+            text: "The code is incorrect.",
+            recovery_codes: vec![],
+        });
+    }
+
+    match generate_recovery_codes(&conn, user.id) {
+        Ok(codes) => Json(RecoveryCodesMessage {
+            text: "New recovery codes have been generated.",
+            recovery_codes: codes,
+        }),
+        Err(_) => Json(RecoveryCodesMessage {
+            text: "An unknown error occured. Please try again later.",
+            recovery_codes: vec![],
+        }),
+    }
+}
+
+/// Struct for disabling two-factor
+/// authentication.
+#[derive(Deserialize)]
+struct DisableStruct {
+    /// The current password of the user.
+    password: String,
+    /// The code from the authenticator
+    /// app or a recovery code.
+    code: String,
+}
+
+/// Struct for responding with a JSON
+/// message.
+#[derive(Serialize)]
+struct Message<'a> {
+    /// The message that will be
+    /// forwarded to the user.
+    text: &'a str,
+}
+
+/// POST Endpoint to disable two-factor
+/// authentication. Endpoints checks if the
+/// user is logged in by using the user
+/// request guard. If the user is not logged
+/// in it forwards the request.
+/// It requires all of the parameters in the
+/// DisableStruct in a JSON format.
+/// Responds with the Message struct in a
+/// JSON format.
+#[post("/settings/two_factor/disable", format = "application/json", data = "<data>")]
+fn disable<'a>(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    user: User,
+    mut cookies: Cookies,
+    data: Json<DisableStruct>,
+) -> Json<Message<'a>> {
+    use club_coding::schema::users::dsl::*;
+
+    let password_hash = match users.find(user.id).first::<Users>(&*conn) {
+        Ok(result) => result.password,
+        Err(_) => {
+            return Json(Message {
+                text: "An unknown error occured. Please try again later.",
+            })
+        }
+    };
+    match passwords::verify_password(&data.0.password, &password_hash) {
+        Ok(true) => {}
+        _ => {
+            return Json(Message {
+                text: "The password is incorrect.",
+            })
+        }
+    }
+    if !check_code(&conn, &redis_conn, user.id, &data.0.code) {
+        return Json(Message {
+            text: "The code is incorrect.",
+        });
+    }
+
+    match database::disable(&conn, user.id) {
+        Ok(_) => {
+            clear_cached_user(&redis_conn, &mut cookies);
+            Json(Message {
+                text: "Two-factor authentication has been disabled.",
+            })
+        }
+        Err(_) => Json(Message {
+            text: "An unknown error occured. Please try again later.",
+        }),
+    }
+}
+
+/// GET Endpoint for the second login step.
+/// Endpoints checks if the user is logged
+/// in by using the user request guard. If
+/// the user is logged in it redirects the
+/// user to the index, otherwise it forwards
+/// the request.
+#[get("/login/two_factor")]
+fn login_page_loggedin(_user: User) -> Redirect {
+    Redirect::to("/")
+}
+
+/// GET Endpoint for the second login step.
+/// This endpoint will kick in if the user
+/// is not logged in.
+/// Takes in an optional FlashMessage
+/// incase there is one.
+/// Responds with the Two Factor Template
+/// in the authentication folder.
+#[get("/login/two_factor", rank = 2)]
+fn login_page(token: CsrfToken, flash: Option<FlashMessage>) -> Template {
+    let (name, msg) = match flash {
+        Some(flash) => (flash.name().to_string(), flash.msg().to_string()),
+        None => ("".to_string(), "".to_string()),
+    };
+    let context = authentication::login::LoginContext {
+        header: "Two-Factor Authentication",
+        csrf: token.value(),
+        flash_name: name,
+        flash_msg: msg,
+    };
+    Template::render("authentication/two_factor", &context)
+}
+
+/// Struct for parsing the form
+/// of the second login step.
+#[derive(FromForm)]
+struct LoginCode {
+    /// The code from the authenticator
+    /// app or a recovery code.
+    code: String,
+    /// CSRF Token from the form
+    csrf: String,
+}
+
+/// POST Endpoint for the second login step.
+/// It requires all of the parameters in the
+/// LoginCode struct to be submitted as a form.
+/// The user must have entered the password
+/// in the last few minutes. Wrong codes count
+/// towards locking the account. If the code is
+/// correct it will set a Session Token and
+/// redirect the user to the index. Otherwise
+/// it will redirect back with an appropriate
+/// message.
+#[post("/login/two_factor", data = "<form>")]
+fn login(
+    conn: DbConn,
+    redis_conn: RedisConnection,
+    postmark_token: State<PostmarkToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
+    client: ClientInfo,
+    mut cookies: Cookies,
+    form: Form<LoginCode>,
+) -> Result<Flash<Redirect>, Flash<Redirect>> {
+    let input: LoginCode = form.into_inner();
+    if !csrf_matches(csrf_secret_key.0, &input.csrf, &csrf_cookie.value()) {
+        return Err(Flash::error(
+            Redirect::to("/login/two_factor"),
+            "CSRF Failed.",
+        ));
+    }
+
+    let token = match cookies.get_private("two_factor_token") {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Err(Flash::error(Redirect::to("/login"), "Please login again.")),
+    };
+    let key = format!("two_factor:{}", token);
+    let user_id = match redis_conn.get::<&str, i64>(&key) {
+        Ok(user_id) => user_id,
+        Err(_) => return Err(Flash::error(Redirect::to("/login"), "Please login again.")),
+    };
+
+    let account = match authentication::database::get_user(&conn, user_id) {
+        Some(account) => account,
+        None => return Err(Flash::error(Redirect::to("/login"), "Please login again.")),
+    };
+    if rate_limit::is_locked(&redis_conn, &account.username) {
+        return Err(Flash::error(
+            Redirect::to("/login"),
+            "Too many failed attempts. Please try again later.",
+        ));
+    }
+
+    if !check_code(&conn, &redis_conn, user_id, &input.code) {
+        // Wrong codes count towards locking the
+        // account just like wrong passwords.
+        let locked = authentication::login::count_failed_login(
+            &conn,
+            &redis_conn,
+            &postmark_token.0,
+            &client,
+            &account.username,
+            Some((account.id, &account.email)),
+            "two-factor code",
+        );
+        let attempts_key = format!("two_factor_attempts:{}", token);
+        let attempts = redis_conn.incr::<&str, i64, i64>(&attempts_key, 1).unwrap_or(0);
+        match redis_conn.expire::<&str, i64>(&attempts_key, PENDING_LOGIN_TTL) {
+            Ok(_) => {}
+            Err(_) => {}
+        }
+        if locked || attempts >= MAX_ATTEMPTS {
+            match redis_conn.del::<&str, i64>(&key) {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+            cookies.remove_private(Cookie::named("two_factor_token"));
+            return Err(Flash::error(
+                Redirect::to("/login"),
+                "Too many incorrect codes, please login again.",
+            ));
+        }
+        return Err(Flash::error(
+            Redirect::to("/login/two_factor"),
+            "The code is incorrect.",
+        ));
+    }
+
+    rate_limit::clear(&redis_conn, &rate_limit::login_user_key(&account.username));
+
+    match redis_conn.del::<&str, i64>(&key) {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+    cookies.remove_private(Cookie::named("two_factor_token"));
+
+    match sessions::start_session(&conn, &client, &mut cookies, user_id) {
+        Ok(_) => {
+            audit::record(
+                &conn,
+                AuditEvent::Login,
+                Some(user_id),
+                None,
+                Some(&client),
+                Some("two-factor code"),
+            );
+            Ok(Flash::success(Redirect::to("/"), "You're now logged in."))
+        }
+        Err(_) => Err(Flash::error(
+            Redirect::to("/login"),
+            "An error occured, please try again later.",
+        )),
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![
+        settings_page,
+        settings_page_nouser,
+        enable,
+        recovery_codes,
+        disable,
+        login_page_loggedin,
+        login_page,
+        login
+    ]
+}
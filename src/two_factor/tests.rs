@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use rocket::local::Client;
+    use rocket::http::Status;
+    use two_factor::{constant_time_eq, hash_recovery_code, totp};
+
+    #[test]
+    fn rfc_6238_test_vectors() {
+        let secret = b"12345678901234567890";
+
+        assert_eq!(totp(secret, 59, 8), 94287082);
+        assert_eq!(totp(secret, 1111111109, 8), 7081804);
+        assert_eq!(totp(secret, 1234567890, 8), 89005924);
+        assert_eq!(totp(secret, 20000000000, 8), 65353130);
+        assert_eq!(totp(secret, 59, 6), 287082);
+    }
+
+    #[test]
+    fn two_factor_settings_nologin() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/settings/two_factor").dispatch();
+
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.content_type(), None);
+    }
+
+    #[test]
+    fn two_factor_login_page() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client.get("/login/two_factor").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn recovery_codes_are_hashed() {
+        let hashed = hash_recovery_code("abcde");
+
+        assert_eq!(hashed.len(), 64);
+        assert_eq!(hashed, hash_recovery_code(" ABCDE "));
+        assert!(hashed != hash_recovery_code("abcdf"));
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"abcd"));
+    }
+}
@@ -1,10 +1,12 @@
-use club_coding::models::{Users, UsersAndSessions, UsersGroup};
+use club_coding::models::{Users, UsersAndSessions, UsersGroup, UsersTwoFactor};
 use rocket::request::{self, FromRequest, Request};
 use rocket::Outcome;
 use database::{DbConn, MySqlPool, RedisPool};
 use rocket::State;
 use redis::Commands;
 use diesel::prelude::*;
+use chrono::Utc;
+use sessions::SESSION_CACHE_TTL;
 
 /// Gets all of the users from the database.
 /// Ordered by their creation date in an
@@ -31,26 +33,41 @@ pub struct User {
     /// A boolean representing if the user
     /// is an administrator or not.
     pub admin: bool,
+    /// A boolean representing if the user
+    /// has enabled two-factor authentication.
+    #[serde(default)]
+    pub two_factor: bool,
 }
 
 /// Gets user by session token and returns
-/// some User if it exists, or None if it does
-/// not exist.
+/// some User if it exists and the session
+/// has not expired, or None otherwise.
+/// Updates when the session was last seen.
 fn get_user_by_token(
     mysql_conn: &r2d2::PooledConnection<diesel::r2d2::ConnectionManager<diesel::MysqlConnection>>,
     redis_conn: &r2d2::PooledConnection<r2d2_redis::RedisConnectionManager>,
     session_token: &str,
 ) -> Option<User> {
-    use club_coding::schema::{users, users_sessions};
+    use club_coding::schema::{users, users_sessions, users_two_factor};
 
     match users_sessions::table
         .inner_join(users::table.on(users::id.eq(users_sessions::user_id)))
         .filter(users_sessions::token.eq(session_token))
+        .filter(users_sessions::expires.gt(Utc::now().naive_utc()))
         .filter(users::verified.eq(true))
         .select((users::id, users::username, users::email))
         .first::<UsersAndSessions>(&**mysql_conn)
     {
         Ok(results) => {
+            match diesel::update(
+                users_sessions::table.filter(users_sessions::token.eq(session_token)),
+            ).set(users_sessions::last_seen.eq(Utc::now().naive_utc()))
+                .execute(&**mysql_conn)
+            {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+
             use club_coding::schema::users_group::dsl::*;
 
             let is_admin = match users_group
@@ -62,17 +79,31 @@ fn get_user_by_token(
                 Err(_) => false,
             };
 
+            let has_two_factor = match users_two_factor::table
+                .filter(users_two_factor::user_id.eq(results.id))
+                .filter(users_two_factor::enabled.eq(true))
+                .first::<UsersTwoFactor>(&**mysql_conn)
+            {
+                Ok(_) => true,
+                Err(_) => false,
+            };
+
             let user = User {
                 id: results.id,
                 username: results.username,
                 email: results.email,
                 admin: is_admin,
+                two_factor: has_two_factor,
             };
             let json_string = match serde_json::to_string(&user) {
                 Ok(json_string) => json_string,
                 Err(_) => return Some(user),
             };
-            match redis_conn.set_ex::<&str, String, String>(session_token, json_string, 86400) {
+            match redis_conn.set_ex::<&str, String, String>(
+                session_token,
+                json_string,
+                SESSION_CACHE_TTL,
+            ) {
                 Ok(_) => {}
                 Err(_) => {}
             }
@@ -1,10 +1,16 @@
-use club_coding::{create_new_request_network_hash, create_new_request_network_payments,
-                  create_new_user_series_access, insert_new_users_stripe_charge};
-use club_coding::models::UsersStripeCustomer;
+use club_coding::{create_new_request_network_hash,
+                  create_new_request_network_payments, create_new_user_series_access,
+                  insert_new_users_stripe_charge};
+use club_coding::models::{Coupons, UsersStripeCustomer};
+use coupons;
+use currency;
 use users::User;
 use std::io::{Error, ErrorKind};
 use stripe::Source::Card;
-use database::DbConn;
+use database::{DbConn, RedisConnection};
+use diesel;
+use diesel::Connection;
+use redis::Commands;
 use videos::database;
 use email::{EmailBody, PostmarkClient};
 use request_network::{wooreq_request, ReqBody};
@@ -51,58 +57,157 @@ fn send_bought_email(postmark_token: &str, email: &str) -> Result<(), Error> {
     }
 }
 
-/// Charges the card that is sent in through the
-/// Stripe Customer by the amount the series costs
-/// as specified in the MySQL Database.
-/// Returns an OK or the error.
-pub fn charge_card(
-    conn: &DbConn,
+/// URL of the Stripe endpoint
+/// for creating charges.
+static STRIPE_CHARGES_URL: &'static str = "https://api.stripe.com/v1/charges";
+
+/// URL of the Stripe endpoint
+/// for refunding charges.
+static STRIPE_REFUNDS_URL: &'static str = "https://api.stripe.com/v1/refunds";
+
+/// Number of seconds a purchase lock is held
+/// for if it is never released, for example
+/// if the server stops in the middle of it.
+static PURCHASE_LOCK_LIFETIME: usize = 60;
+
+/// Redis key of the lock held while the
+/// user is buying the series.
+fn purchase_lock_key(user_id: i64, series_id: i64) -> String {
+    format!("purchase_lock:{}:{}", user_id, series_id)
+}
+
+/// Takes the lock for the user buying the
+/// series. Returns false if another request
+/// is already buying it.
+pub fn acquire_purchase_lock(redis_conn: &RedisConnection, user_id: i64, series_id: i64) -> bool {
+    match redis::cmd("SET")
+        .arg(purchase_lock_key(user_id, series_id))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(PURCHASE_LOCK_LIFETIME)
+        .query::<Option<String>>(&**redis_conn)
+    {
+        Ok(Some(_)) => true,
+        _ => false,
+    }
+}
+
+/// Releases the lock for the
+/// user buying the series.
+pub fn release_purchase_lock(redis_conn: &RedisConnection, user_id: i64, series_id: i64) {
+    let _ = redis_conn.del::<String, i64>(purchase_lock_key(user_id, series_id));
+}
+
+/// Checks that the idempotency key from
+/// the buy form is one we generated.
+pub fn valid_idempotency_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 64 && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Creates the charge at Stripe with the
+/// idempotency key, so the same purchase
+/// sent twice only charges the card once.
+fn create_stripe_charge(
     stripe_secret: &str,
-    postmark_token: &str,
-    series_id: i64,
-    user: &User,
-    stripe_customer: &UsersStripeCustomer,
-) -> Result<(), Error> {
-    let serie = match database::get_serie(&conn, series_id) {
-        Some(serie) => serie,
-        None => return Err(Error::new(ErrorKind::Other, "no serie")),
+    idempotency_key: &str,
+    amount: i32,
+    currency: &str,
+    customer: &str,
+    source: &str,
+) -> Result<stripe::Charge, Error> {
+    let client = reqwest::Client::new();
+    let mut res = match client
+        .post(STRIPE_CHARGES_URL)
+        .basic_auth(stripe_secret, None::<&str>)
+        .header("Idempotency-Key", idempotency_key)
+        .form(&[
+            ("amount", amount.to_string().as_str()),
+            ("currency", currency),
+            ("customer", customer),
+            ("source", source),
+        ])
+        .send()
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not connect to server at URL.",
+            ))
+        }
     };
-    match stripe_customer.default_source {
-        Some(ref customer_source) => {
-            // Create the customer
-            let client = stripe::Client::new(stripe_secret);
 
-            let charge = match stripe::Charge::create(
-                &client,
-                stripe::ChargeParams {
-                    amount: Some(serie.price as u64),
-                    currency: Some(stripe::Currency::USD),
-                    application_fee: None,
-                    capture: None,
-                    description: None,
-                    destination: None,
-                    fraud_details: None,
-                    transfer_group: None,
-                    on_behalf_of: None,
-                    metadata: None,
-                    receipt_email: None,
-                    shipping: None,
-                    customer: Some(stripe_customer.uuid.clone()),
-                    source: Some(stripe::CustomerSource::Token(&customer_source)),
-                    statement_descriptor: None,
-                },
-            ) {
-                Ok(charge) => charge,
-                Err(_) => return Err(Error::new(ErrorKind::Other, "couldn't create charge")),
-            };
+    if !res.status().is_success() {
+        return Err(Error::new(ErrorKind::Other, "couldn't create charge"));
+    }
+
+    let text = match res.text() {
+        Ok(text) => text,
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not read response.")),
+    };
+
+    match serde_json::from_str(&text) {
+        Ok(charge) => Ok(charge),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not serialize response.",
+        )),
+    }
+}
+
+/// Refunds the whole charge at Stripe. Used
+/// when the card was charged but the purchase
+/// could not be stored. The idempotency key is
+/// the charge so it's only refunded once.
+fn refund_stripe_charge(stripe_secret: &str, charge_id: &str) -> Result<(), Error> {
+    let client = reqwest::Client::new();
+    let res = match client
+        .post(STRIPE_REFUNDS_URL)
+        .basic_auth(stripe_secret, None::<&str>)
+        .header("Idempotency-Key", format!("refund-{}", charge_id))
+        .form(&[("charge", charge_id)])
+        .send()
+    {
+        Ok(res) => res,
+        Err(_) => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Could not connect to server at URL.",
+            ))
+        }
+    };
+
+    if res.status().is_success() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::Other, "couldn't refund charge"))
+    }
+}
+
+/// Stores the purchase of the series in one
+/// transaction: the Stripe charge if the card
+/// was charged, the access to the series and
+/// the charge of the redemption if a coupon
+/// was used.
+fn store_purchase(
+    conn: &DbConn,
+    user: &User,
+    series_id: i64,
+    currency: &str,
+    charge: Option<&stripe::Charge>,
+    redemption: Option<i64>,
+) -> Result<(), Error> {
+    let stored = conn.transaction::<(), diesel::result::Error, _>(|| {
+        if let Some(charge) = charge {
             let failure_code: Option<String> = match charge.failure_code {
-                Some(code) => Some(code.to_string()),
+                Some(ref code) => Some(code.to_string()),
                 None => None,
             };
             let source_id = match charge.source {
-                Card(card) => card.id,
+                Card(ref card) => card.id.clone(),
             };
-            let _ = insert_new_users_stripe_charge(
+            insert_new_users_stripe_charge(
                 &*conn,
                 user.id,
                 series_id,
@@ -132,13 +237,106 @@ pub fn charge_card(
                     .as_ref()
                     .map_or(None, |x| Some(x)),
                 &charge.status,
-            )?;
-            let _ = create_new_user_series_access(&*conn, user.id, series_id, true)?;
-            let _ = send_bought_email(postmark_token, &user.email)?;
-            Ok(())
+                currency,
+            ).map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        }
+        create_new_user_series_access(&*conn, user.id, series_id, true)
+            .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        if let (Some(coupon_id), Some(charge)) = (redemption, charge) {
+            coupons::database::set_redemption_charge(conn, coupon_id, user.id, &charge.id)?;
+        }
+        Ok(())
+    });
+    match stored {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "couldn't store charge")),
+    }
+}
+
+/// Buys the series for the user. The price is the
+/// price of the series in the currency of the user
+/// as specified in the MySQL Database, less the
+/// discount of the coupon if one is used. The card
+/// that is sent in through the Stripe Customer is
+/// charged unless the coupon makes the series free. The idempotency key is namespaced
+/// by the user and the series before it is sent to
+/// Stripe. A redemption of the coupon is reserved
+/// before charging and given back if the purchase fails.
+/// If the card is charged but the purchase can't be
+/// stored the charge is refunded.
+/// Returns an OK or the error.
+pub fn charge_card(
+    conn: &DbConn,
+    stripe_secret: &str,
+    postmark_token: &str,
+    series_id: i64,
+    user: &User,
+    stripe_customer: Option<&UsersStripeCustomer>,
+    idempotency_key: &str,
+    coupon: Option<&Coupons>,
+) -> Result<(), Error> {
+    let serie = match database::get_serie(&conn, series_id) {
+        Some(serie) => serie,
+        None => return Err(Error::new(ErrorKind::Other, "no serie")),
+    };
+    let (currency, full_price) = currency::user_price(conn, series_id, serie.price, user.id);
+    if let Some(coupon) = coupon {
+        if !coupons::usable_in(&coupon.kind, &coupon.currency, currency) {
+            return Err(Error::new(ErrorKind::Other, "coupon in other currency"));
+        }
+    }
+    let discount = coupon.map_or(0, |coupon| {
+        coupons::discount(full_price, &coupon.kind, coupon.value)
+    });
+    let price = coupons::discounted_price(full_price, discount, currency);
+
+    if let Some(coupon) = coupon {
+        if !coupons::database::reserve_redemption(conn, coupon.id, user.id, series_id, discount) {
+            return Err(Error::new(ErrorKind::Other, "coupon used up"));
+        }
+    }
+    let redemption = coupon.map(|coupon| coupon.id);
+
+    let result = if price == 0 {
+        store_purchase(conn, user, series_id, currency, None, redemption)
+    } else {
+        match stripe_customer {
+            Some(&UsersStripeCustomer {
+                default_source: Some(ref customer_source),
+                ref uuid,
+                ..
+            }) => match create_stripe_charge(
+                stripe_secret,
+                &format!("purchase-{}-{}-{}", user.id, series_id, idempotency_key),
+                price,
+                currency,
+                uuid,
+                customer_source,
+            ) {
+                Ok(charge) => {
+                    let stored =
+                        store_purchase(conn, user, series_id, currency, Some(&charge), redemption);
+                    if stored.is_err() {
+                        // The card is charged but the user has no access,
+                        // so the charge is given back.
+                        let _ = refund_stripe_charge(stripe_secret, &charge.id);
+                    }
+                    stored
+                }
+                Err(error) => Err(error),
+            },
+            _ => Err(Error::new(ErrorKind::Other, "no customer_source")),
+        }
+    };
+
+    if let Err(error) = result {
+        if let Some(coupon) = coupon {
+            coupons::database::release_redemption(conn, coupon.id, user.id);
         }
-        None => Err(Error::new(ErrorKind::Other, "no customer_source")),
+        return Err(error);
     }
+    let _ = send_bought_email(postmark_token, &user.email);
+    Ok(())
 }
 
 /// Generates a Request Network Payment
@@ -1,9 +1,15 @@
-use club_coding::create_new_user_view;
+use club_coding::{create_new_user_video_vote, create_new_user_view};
 use club_coding::models::{RequestNetworkPayments, Series, Users, UsersSeriesAccess,
-                          UsersStripeCustomer, UsersViews, VideoJoin, Videos};
+                          UsersStripeCustomer, UsersVideosVotes, UsersViews, VideoJoin, Videos};
 use std::io::{Error, ErrorKind};
 use database::DbConn;
 use diesel::prelude::*;
+use payment;
+
+/// How far into a video, in percent,
+/// the user has to watch for the video
+/// to count as completed.
+pub static COMPLETION_THRESHOLD: i32 = 90;
 
 /// Gets all of the videos in the
 /// database that are published and
@@ -72,6 +78,81 @@ pub fn create_new_view(connection: &DbConn, vid: i64, uid: i64) {
     }
 }
 
+/// Gets the view of a video by a user.
+/// Returns either Some view or None if
+/// the user has not opened the video.
+pub fn get_view(connection: &DbConn, vid: i64, uid: i64) -> Option<UsersViews> {
+    use club_coding::schema::users_views::dsl::*;
+
+    match users_views
+        .filter(user_id.eq(uid))
+        .filter(video_id.eq(vid))
+        .first::<UsersViews>(&**connection)
+    {
+        Ok(view) => Some(view),
+        Err(_) => None,
+    }
+}
+
+/// Stores how far into the video the user
+/// has watched and the duration of the video
+/// as reported by the player. Creates the
+/// view if it does not already exist.
+/// The video is marked as completed once
+/// the user has watched past the
+/// COMPLETION_THRESHOLD.
+pub fn update_progress(
+    connection: &DbConn,
+    vid: i64,
+    uid: i64,
+    new_seconds: i32,
+    new_duration: i32,
+) -> Result<(), Error> {
+    create_new_view(connection, vid, uid);
+
+    use club_coding::schema::users_views::dsl::*;
+
+    match diesel::update(users_views.filter(user_id.eq(uid)).filter(video_id.eq(vid)))
+        .set((seconds.eq(new_seconds), duration.eq(new_duration)))
+        .execute(&**connection)
+    {
+        Ok(_) => {}
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not update progress.")),
+    }
+
+    if new_duration > 0
+        && new_seconds as i64 * 100 / new_duration as i64 >= COMPLETION_THRESHOLD as i64
+    {
+        set_completed(connection, vid, uid, true)
+    } else {
+        Ok(())
+    }
+}
+
+/// Marks the video as completed or not
+/// completed for the user. Creates the
+/// view if it does not already exist.
+pub fn set_completed(connection: &DbConn, vid: i64, uid: i64, done: bool) -> Result<(), Error> {
+    create_new_view(connection, vid, uid);
+
+    use club_coding::schema::users_views::dsl::*;
+
+    match diesel::update(users_views.filter(user_id.eq(uid)).filter(video_id.eq(vid)))
+        .set(completed.eq(done))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update completion.")),
+    }
+}
+
+/// Checks if a user can watch the membership
+/// only videos of a series, either by having
+/// bought the series or by being a member.
+pub fn user_has_access(connection: &DbConn, sid: i64, uid: i64) -> bool {
+    user_has_bought(connection, sid, uid) || payment::database::is_active_member(connection, uid)
+}
+
 /// Checks if a user has bought a series.
 /// Returns a boolean of if the user has
 /// bought the series or not.
@@ -161,3 +242,94 @@ pub fn get_user(conn: &DbConn, user_id: i64) -> Option<Users> {
         Err(_) => None,
     }
 }
+
+/// Gets the vote of a user on a video.
+/// Returns Some true if the user likes the
+/// video, Some false if the user dislikes
+/// it and None if the user has not voted.
+pub fn get_vote(connection: &DbConn, uid: i64, vid: i64) -> Option<bool> {
+    use club_coding::schema::users_videos_votes::dsl::*;
+
+    match users_videos_votes
+        .filter(user_id.eq(uid))
+        .filter(video_id.eq(vid))
+        .first::<UsersVideosVotes>(&**connection)
+    {
+        Ok(vote) => Some(vote.is_like),
+        Err(_) => None,
+    }
+}
+
+/// Votes on a video as a user. Voting the
+/// same way twice removes the vote and voting
+/// the other way changes the vote.
+/// Returns the vote the user has after
+/// the change or an error.
+pub fn toggle_vote(
+    connection: &DbConn,
+    uid: i64,
+    vid: i64,
+    like: bool,
+) -> Result<Option<bool>, Error> {
+    match get_vote(connection, uid, vid) {
+        Some(current) if current == like => {
+            delete_vote(connection, uid, vid)?;
+            Ok(None)
+        }
+        Some(_) => {
+            use club_coding::schema::users_videos_votes::dsl::*;
+
+            match diesel::update(
+                users_videos_votes
+                    .filter(user_id.eq(uid))
+                    .filter(video_id.eq(vid)),
+            ).set(is_like.eq(like))
+                .execute(&**connection)
+            {
+                Ok(_) => Ok(Some(like)),
+                Err(_) => Err(Error::new(ErrorKind::Other, "Could not update vote.")),
+            }
+        }
+        None => {
+            create_new_user_video_vote(connection, uid, vid, like)?;
+            Ok(Some(like))
+        }
+    }
+}
+
+/// Removes the vote of a user on a video.
+/// Returns OK even if the user had not voted.
+pub fn delete_vote(connection: &DbConn, uid: i64, vid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_videos_votes::dsl::*;
+
+    match diesel::delete(
+        users_videos_votes
+            .filter(user_id.eq(uid))
+            .filter(video_id.eq(vid)),
+    ).execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not delete vote.")),
+    }
+}
+
+/// Counts the votes on a video.
+/// Returns the number of likes and
+/// the number of dislikes.
+pub fn get_vote_totals(connection: &DbConn, vid: i64) -> (i64, i64) {
+    use club_coding::schema::users_videos_votes::dsl::*;
+
+    let count_votes = |like: bool| -> i64 {
+        match users_videos_votes
+            .filter(video_id.eq(vid))
+            .filter(is_like.eq(like))
+            .count()
+            .get_result::<i64>(&**connection)
+        {
+            Ok(votes) => votes,
+            Err(_) => 0,
+        }
+    };
+
+    (count_votes(true), count_votes(false))
+}
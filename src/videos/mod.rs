@@ -4,14 +4,21 @@ pub mod charge;
 use rocket::Route;
 use rocket_contrib::templates::Template;
 use rocket::response::{Flash, Redirect};
+use rocket_contrib::json::Json;
 use users::User;
 use series::PublicVideo;
 use rocket::request::FlashMessage;
 use database::{DbConn, RedisConnection};
 use structs::{PostmarkToken, StripeToken};
 use rocket::State;
+use rocket::request::Form;
+use custom_csrf::{csrf_matches, CSRFSecretToken, CsrfCookie, CsrfToken};
+use authentication;
 use videos::charge::charge_card;
+use coupons;
+use currency;
 use series;
+use comments::{get_video_comments, PublicComment};
 
 #[cfg(test)]
 mod tests;
@@ -24,8 +31,20 @@ struct WatchContext<'a> {
     uuid: String,
     /// Title of the series being watched.
     series_title: String,
-    /// Price of the series.
+    /// Price of the series in
+    /// the currency of the user.
     price: i32,
+    /// Currency the user pays in.
+    currency: &'static str,
+    /// Coupon code applied on the buy
+    /// page. Empty if there is none.
+    coupon: String,
+    /// Price of the series after
+    /// the discount of the coupon.
+    discounted_price: i32,
+    /// Why the coupon can't be used.
+    /// Empty if it can be used.
+    coupon_error: String,
     /// Title of the Video.
     title: String,
     /// Description of the Video.
@@ -33,6 +52,13 @@ struct WatchContext<'a> {
     /// The user struct used by templates.
     /// For example the username for the toolbar.
     user: &'a User,
+    /// CSRF Token. Used as a hidden
+    /// input in the buy form.
+    csrf: String,
+    /// Idempotency key of the buy form.
+    /// A new one is generated each time
+    /// the page is rendered.
+    idempotency_key: String,
     /// The Vimeo ID of the video being watched.
     vimeo_id: String,
     /// Boolean of if the series is
@@ -41,6 +67,22 @@ struct WatchContext<'a> {
     /// A Vector of the Videos in the same series
     /// as the one currently watched.
     videos: Vec<PublicVideo>,
+    /// Number of likes on the video.
+    likes: i64,
+    /// Number of dislikes on the video.
+    dislikes: i64,
+    /// The vote of the user on the video.
+    /// True if liked, false if disliked
+    /// and None if the user has not voted.
+    vote: Option<bool>,
+    /// The comment threads on the video.
+    comments: Vec<PublicComment>,
+    /// The second in the video where the
+    /// user left off. Used to resume playback.
+    progress: i32,
+    /// Boolean of whether the user has
+    /// completed the video or not.
+    completed: bool,
     /// Flash name if the request is redirected
     /// with one.
     flash_name: String,
@@ -60,20 +102,24 @@ struct WatchContext<'a> {
 /// The endpoint checks if the video
 /// requires that the series is bought and if
 /// it requires that it will check if the user
-/// has the permission. If the user does not
+/// has the permission, by having bought the
+/// series or by being a member. If the user does not
 /// have the persmission it will respond with a
-/// buy page (Watch No Member in the videos folder).
+/// buy page (Watch No Member in the videos folder),
+/// showing the discounted price if a coupon is given.
 /// If the user does have the persmission or the
 /// series does not require it, it will respond
 /// with the Watch Member Template in the videos
 /// folder.
-#[get("/watch/<uuid>")]
+#[get("/watch/<uuid>?<coupon>")]
 fn watch_as_user(
     mysql_conn: DbConn,
     redis_conn: RedisConnection,
     user: User,
+    token: CsrfToken,
     flash: Option<FlashMessage>,
     uuid: String,
+    coupon: Option<String>,
 ) -> Result<Template, Redirect> {
     match database::get_video_data_from_uuid(&mysql_conn, &uuid) {
         Ok(video) => {
@@ -88,21 +134,53 @@ fn watch_as_user(
             // remove it here.
             let mut description = video.description;
             description.retain(|c| c != '\\');
-            let mut context = WatchContext {
+            let (likes, dislikes) = database::get_vote_totals(&mysql_conn, video.id);
+            let (progress, completed) = match database::get_view(&mysql_conn, video.id, user.id) {
+                Some(view) => (view.seconds, view.completed),
+                None => (0, false),
+            };
+            let (currency, price) =
+                currency::user_price(&mysql_conn, video.serie_id, video.price, user.id);
+            let code = coupon.map_or("".to_string(), |code| coupons::normalize_code(&code));
+            let (discount, coupon_error) = if code.is_empty() {
+                (0, "".to_string())
+            } else {
+                match coupons::find_coupon(&mysql_conn, &code, user.id, video.serie_id, currency)
+                {
+                    Ok(coupon) => (
+                        coupons::discount(price, &coupon.kind, coupon.value),
+                        "".to_string(),
+                    ),
+                    Err(error) => (0, error.message().to_string()),
+                }
+            };
+            let context = WatchContext {
                 uuid: video.uuid,
                 series_title: video.series_title,
-                price: video.price,
+                price: price,
+                currency: currency,
+                coupon: code,
+                discounted_price: coupons::discounted_price(price, discount, currency),
+                coupon_error: coupon_error,
                 title: video.title,
                 description: description,
                 user: &user,
+                csrf: token.value(),
+                idempotency_key: authentication::generate_token(30),
                 vimeo_id: video.vimeo_id,
                 in_development: video.in_development,
                 videos: videos,
+                likes: likes,
+                dislikes: dislikes,
+                vote: database::get_vote(&mysql_conn, user.id, video.id),
+                comments: get_video_comments(&mysql_conn, video.id, Some(user.id)),
+                progress: progress,
+                completed: completed,
                 flash_name: name,
                 flash_msg: msg,
             };
             if video.membership_only {
-                if !database::user_has_bought(&mysql_conn, video.serie_id, user.id) {
+                if !database::user_has_access(&mysql_conn, video.serie_id, user.id) {
                     return Ok(Template::render("videos/watch_nomember", &context));
                 }
             }
@@ -180,56 +258,148 @@ fn watch_nouser(
     }
 }
 
-/// GET Endpoint to buy a certain series as
+/// Struct for parsing
+/// buy series forms
+#[derive(FromForm)]
+struct BuySerie {
+    /// CSRF Token from the form
+    csrf: String,
+    /// Idempotency key generated when
+    /// the buy page was rendered. Sent
+    /// to Stripe so submitting the same
+    /// form twice only charges once.
+    idempotency_key: String,
+    /// Coupon code, if the
+    /// user entered one.
+    coupon: Option<String>,
+}
+
+/// POST Endpoint to buy a certain series as
 /// defined by the video the series is in
 /// specified by the UUID. Endpoints checks
 /// if the user is logged in by using the
 /// user request guard. If the user
 /// is not logged in it forwards
-/// the request. The endpoint
-/// checks if the user already has bought it
+/// the request. It requires all of the
+/// parameters in the BuySerie struct to be
+/// submitted as a form. The purchase is done
+/// while holding a lock for the user and the
+/// series, and the endpoint checks if the user
+/// already has bought it after taking the lock
 /// to avoid double purchases. If the series is already
 /// bought it will redirect to the watch page for the
 /// video. If the user doesn't have a card, it will redirect
 /// to the add card page. If the user has a card and
 /// has not already bought the series, it will perform
-/// the purchase and redirect to the video.
-#[get("/watch/<uuid>/buy/fiat")]
+/// the purchase, with the discount of the coupon if
+/// one is given, and redirect to the video.
+#[post("/watch/<uuid>/buy/fiat", data = "<form_data>")]
 fn buy_serie_fiat(
     conn: DbConn,
+    redis_conn: RedisConnection,
     stripe_token: State<StripeToken>,
     postmark_token: State<PostmarkToken>,
+    csrf_secret_key: State<CSRFSecretToken>,
+    csrf_cookie: CsrfCookie,
     user: User,
     uuid: String,
+    form_data: Form<BuySerie>,
 ) -> Result<Flash<Redirect>, Redirect> {
-    match database::get_video_data_from_uuid(&conn, &uuid) {
-        Ok(video) => {
-            if !database::user_has_bought(&conn, video.serie_id, user.id) {
-                match database::get_customer(&conn, user.id) {
-                    Some(stripe_customer) => match charge_card(
-                        &conn,
-                        &stripe_token.secret_key,
-                        &postmark_token.0,
-                        video.serie_id,
-                        &user,
-                        &stripe_customer,
-                    ) {
-                        Ok(_) => Ok(Flash::success(
-                            Redirect::to(format!("/watch/{}", uuid)),
-                            "Series unlocked! Congratulations!",
-                        )),
-                        Err(_) => Ok(Flash::error(
-                            Redirect::to(format!("/watch/{}", uuid)),
-                            "An error occured, please try again later.",
-                        )),
-                    },
-                    None => Err(Redirect::to(format!("/card/add/{}", uuid))),
+    let data = form_data.into_inner();
+    if !csrf_matches(csrf_secret_key.0, &data.csrf, &csrf_cookie.value()) {
+        return Ok(Flash::error(
+            Redirect::to(format!("/watch/{}", uuid)),
+            "CSRF Failed.",
+        ));
+    }
+    if !charge::valid_idempotency_key(&data.idempotency_key) {
+        return Ok(Flash::error(
+            Redirect::to(format!("/watch/{}", uuid)),
+            "An error occured, please try again later.",
+        ));
+    }
+    let video = match database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => video,
+        Err(_video_not_found) => return Err(Redirect::to("/")),
+    };
+    if !charge::acquire_purchase_lock(&redis_conn, user.id, video.serie_id) {
+        return Ok(Flash::error(
+            Redirect::to(format!("/watch/{}", uuid)),
+            "Your purchase is already being processed.",
+        ));
+    }
+    let result = buy_with_card(
+        &conn,
+        &stripe_token.secret_key,
+        &postmark_token.0,
+        &user,
+        &uuid,
+        video.serie_id,
+        video.price,
+        &data,
+    );
+    charge::release_purchase_lock(&redis_conn, user.id, video.serie_id);
+    result
+}
+
+/// Buys the series with the card of the user
+/// while the purchase lock is held. Checks the
+/// coupon if one is submitted. The user only
+/// needs a card if the series isn't free after
+/// the discount.
+fn buy_with_card(
+    conn: &DbConn,
+    stripe_secret: &str,
+    postmark_token: &str,
+    user: &User,
+    uuid: &str,
+    serie_id: i64,
+    price: i32,
+    data: &BuySerie,
+) -> Result<Flash<Redirect>, Redirect> {
+    if database::user_has_bought(conn, serie_id, user.id) {
+        return Err(Redirect::to(format!("/watch/{}", uuid)));
+    }
+    let (currency, price) = currency::user_price(conn, serie_id, price, user.id);
+    let coupon = match data.coupon {
+        Some(ref code) if !code.trim().is_empty() => {
+            match coupons::find_coupon(conn, code, user.id, serie_id, currency) {
+                Ok(coupon) => Some(coupon),
+                Err(error) => {
+                    return Ok(Flash::error(
+                        Redirect::to(format!("/watch/{}", uuid)),
+                        error.message(),
+                    ))
                 }
-            } else {
-                Err(Redirect::to(format!("/watch/{}", uuid)))
             }
         }
-        Err(_video_not_found) => Err(Redirect::to("/")),
+        _ => None,
+    };
+    let discount = coupon.as_ref().map_or(0, |coupon| {
+        coupons::discount(price, &coupon.kind, coupon.value)
+    });
+    let stripe_customer = database::get_customer(conn, user.id);
+    if coupons::discounted_price(price, discount, currency) > 0 && stripe_customer.is_none() {
+        return Err(Redirect::to(format!("/card/add/{}", uuid)));
+    }
+    match charge_card(
+        conn,
+        stripe_secret,
+        postmark_token,
+        serie_id,
+        user,
+        stripe_customer.as_ref(),
+        &data.idempotency_key,
+        coupon.as_ref(),
+    ) {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(format!("/watch/{}", uuid)),
+            "Series unlocked! Congratulations!",
+        )),
+        Err(_) => Ok(Flash::error(
+            Redirect::to(format!("/watch/{}", uuid)),
+            "An error occured, please try again later.",
+        )),
     }
 }
 
@@ -300,6 +470,181 @@ fn validate_bought_series_req(
     }
 }
 
+/// Longest duration in seconds the
+/// player can report for a video.
+static MAX_DURATION: i32 = 24 * 60 * 60;
+
+/// Checks that the position reported by the
+/// player is within a video of a sane length.
+pub fn valid_progress(seconds: i32, duration: i32) -> bool {
+    duration > 0 && duration <= MAX_DURATION && seconds >= 0
+}
+
+/// Struct for the current position
+/// of the player in a video.
+#[derive(Deserialize)]
+struct ProgressStruct {
+    /// The current second in the video.
+    seconds: i32,
+    /// The duration of the video
+    /// in seconds.
+    duration: i32,
+}
+
+/// POST Endpoint the player calls
+/// periodically with the current position
+/// in the video. Endpoints checks if the
+/// user is logged in by using the user
+/// request guard. If the user is not
+/// logged in it forwards the request.
+/// It requires the parameters in the
+/// ProgressStruct in a JSON format.
+/// Durations longer than MAX_DURATION
+/// are rejected. The position is stored so the user
+/// can resume where they left off.
+#[post("/watch/<uuid>/progress", format = "application/json", data = "<data>")]
+fn progress(conn: DbConn, user: User, uuid: String, data: Json<ProgressStruct>) -> Result<(), ()> {
+    if !valid_progress(data.0.seconds, data.0.duration) {
+        return Err(());
+    }
+    match database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => {
+            if video.membership_only
+                && !database::user_has_access(&conn, video.serie_id, user.id)
+            {
+                return Err(());
+            }
+            let seconds = if data.0.seconds > data.0.duration {
+                data.0.duration
+            } else {
+                data.0.seconds
+            };
+            match database::update_progress(&conn, video.id, user.id, seconds, data.0.duration) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(()),
+            }
+        }
+        Err(_video_not_found) => Err(()),
+    }
+}
+
+/// Struct for marking a video
+/// as completed.
+#[derive(Deserialize)]
+struct CompleteStruct {
+    /// True to mark the video as
+    /// completed and false to mark
+    /// it as not completed.
+    completed: bool,
+}
+
+/// POST Endpoint to mark a video as
+/// completed or not completed. Endpoints
+/// checks if the user is logged in by
+/// using the user request guard. If the
+/// user is not logged in it forwards
+/// the request.
+/// It requires the parameter in the
+/// CompleteStruct in a JSON format.
+#[post("/watch/<uuid>/complete", format = "application/json", data = "<data>")]
+fn complete(conn: DbConn, user: User, uuid: String, data: Json<CompleteStruct>) -> Result<(), ()> {
+    match database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => {
+            if video.membership_only
+                && !database::user_has_access(&conn, video.serie_id, user.id)
+            {
+                return Err(());
+            }
+            match database::set_completed(&conn, video.id, user.id, data.0.completed) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(()),
+            }
+        }
+        Err(_video_not_found) => Err(()),
+    }
+}
+
+/// Struct for voting on a video.
+#[derive(Deserialize)]
+struct VoteStruct {
+    /// True to like the video and
+    /// false to dislike it.
+    like: bool,
+}
+
+/// Struct for responding with the
+/// votes on a video in a JSON format.
+#[derive(Serialize)]
+struct VoteResponse {
+    /// Number of likes on the video.
+    likes: i64,
+    /// Number of dislikes on the video.
+    dislikes: i64,
+    /// The vote of the user after the
+    /// change. None if the user has
+    /// not voted.
+    vote: Option<bool>,
+}
+
+/// POST Endpoint to like or dislike
+/// a video. Endpoints checks if the
+/// user is logged in by using the
+/// user request guard. If the user
+/// is not logged in it forwards
+/// the request.
+/// It requires the parameter in the
+/// VoteStruct in a JSON format.
+/// Voting the same way twice removes
+/// the vote. Responds with the new
+/// totals in a JSON format.
+#[post("/watch/<uuid>/vote", format = "application/json", data = "<data>")]
+fn vote(
+    conn: DbConn,
+    user: User,
+    uuid: String,
+    data: Json<VoteStruct>,
+) -> Result<Json<VoteResponse>, ()> {
+    match database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => match database::toggle_vote(&conn, user.id, video.id, data.0.like) {
+            Ok(vote) => {
+                let (likes, dislikes) = database::get_vote_totals(&conn, video.id);
+                Ok(Json(VoteResponse {
+                    likes: likes,
+                    dislikes: dislikes,
+                    vote: vote,
+                }))
+            }
+            Err(_) => Err(()),
+        },
+        Err(_video_not_found) => Err(()),
+    }
+}
+
+/// POST Endpoint to remove the vote
+/// of the user on a video. Endpoints
+/// checks if the user is logged in by
+/// using the user request guard. If the
+/// user is not logged in it forwards
+/// the request. Responds with the new
+/// totals in a JSON format.
+#[post("/watch/<uuid>/unvote")]
+fn unvote(conn: DbConn, user: User, uuid: String) -> Result<Json<VoteResponse>, ()> {
+    match database::get_video_data_from_uuid(&conn, &uuid) {
+        Ok(video) => match database::delete_vote(&conn, user.id, video.id) {
+            Ok(_) => {
+                let (likes, dislikes) = database::get_vote_totals(&conn, video.id);
+                Ok(Json(VoteResponse {
+                    likes: likes,
+                    dislikes: dislikes,
+                    vote: None,
+                }))
+            }
+            Err(_) => Err(()),
+        },
+        Err(_video_not_found) => Err(()),
+    }
+}
+
 /// Assembles all of the endpoints.
 /// The upside of assembling all of the endpoints here
 /// is that we don't have to update the main function but
@@ -310,6 +655,10 @@ pub fn endpoints() -> Vec<Route> {
         watch_nouser,
         buy_serie_fiat,
         buy_serie_req,
-        validate_bought_series_req
+        validate_bought_series_req,
+        progress,
+        complete,
+        vote,
+        unvote
     ]
 }
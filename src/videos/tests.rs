@@ -4,6 +4,8 @@ mod test {
     use rocket::local::Client;
     use rocket::http::Status;
     use rocket::http::ContentType;
+    use videos::charge::valid_idempotency_key;
+    use videos::valid_progress;
 
     #[test]
     fn watch() {
@@ -15,4 +17,32 @@ mod test {
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(response.content_type(), Some(ContentType::HTML));
     }
+
+    #[test]
+    fn buy_fiat_get() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client
+            .get("/watch/07F812BDA6CAB3CA44CE372E8CD511D58551167FA0945D93/buy/fiat")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn progress_bounds() {
+        assert!(valid_progress(0, 600));
+        assert!(valid_progress(600, 86400));
+        assert!(!valid_progress(0, 0));
+        assert!(!valid_progress(-1, 600));
+        assert!(!valid_progress(10, 86401));
+        assert!(!valid_progress(2147483647, 2147483647));
+    }
+
+    #[test]
+    fn idempotency_keys() {
+        assert!(valid_idempotency_key("07F812BDA6CAB3CA44CE372E8CD511D5"));
+        assert!(!valid_idempotency_key(""));
+        assert!(!valid_idempotency_key("07F812BD-A6CAB3CA"));
+        assert!(!valid_idempotency_key(&"A".repeat(65)));
+    }
 }
@@ -0,0 +1,88 @@
+use club_coding::models::{UsersSeriesAccess, UsersStripeCharge};
+use database::DbConn;
+use std::io::{Error, ErrorKind};
+use diesel;
+use diesel::prelude::*;
+
+/// Gets the stored charge with the Stripe
+/// charge id, if any. The row is locked until
+/// the transaction ends so events for the same
+/// charge are applied one at a time.
+pub fn get_charge_for_update(connection: &DbConn, charge_id: &str) -> Option<UsersStripeCharge> {
+    use club_coding::schema::users_stripe_charge::dsl::*;
+
+    match users_stripe_charge
+        .filter(uuid.eq(charge_id))
+        .for_update()
+        .first::<UsersStripeCharge>(&**connection)
+    {
+        Ok(charge) => Some(charge),
+        Err(_) => None,
+    }
+}
+
+/// Updates the refund, dispute and
+/// status fields of the stored charge
+/// and when the event was created.
+pub fn update_charge(
+    connection: &DbConn,
+    cid: i64,
+    new_refunded: bool,
+    new_amount_refunded: i32,
+    new_dispute: Option<&str>,
+    new_status: &str,
+    event_created: i64,
+) -> Result<(), Error> {
+    use club_coding::schema::users_stripe_charge::dsl::*;
+
+    match diesel::update(users_stripe_charge.find(cid))
+        .set((
+            refunded.eq(new_refunded),
+            amount_refunded.eq(new_amount_refunded),
+            dispute.eq(new_dispute),
+            status.eq(new_status),
+            last_event_at.eq(event_created),
+        ))
+        .execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(ErrorKind::Other, "Could not update charge.")),
+    }
+}
+
+/// Checks if the user has bought
+/// access to the series.
+pub fn has_bought_access(connection: &DbConn, uid: i64, sid: i64) -> bool {
+    use club_coding::schema::users_series_access::dsl::*;
+
+    match users_series_access
+        .filter(user_id.eq(uid))
+        .filter(series_id.eq(sid))
+        .filter(bought.eq(true))
+        .first::<UsersSeriesAccess>(&**connection)
+    {
+        Ok(_) => true,
+        Err(_) => false,
+    }
+}
+
+/// Removes the bought access to the
+/// series from the user. Access given
+/// by an administrator is kept.
+pub fn revoke_bought_access(connection: &DbConn, uid: i64, sid: i64) -> Result<(), Error> {
+    use club_coding::schema::users_series_access::dsl::*;
+
+    match diesel::delete(
+        users_series_access
+            .filter(user_id.eq(uid))
+            .filter(series_id.eq(sid))
+            .filter(bought.eq(true)),
+    ).execute(&**connection)
+    {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Error::new(
+            ErrorKind::Other,
+            "Could not revoke series access.",
+        )),
+    }
+}
@@ -0,0 +1,495 @@
+pub mod database;
+use authentication;
+use club_coding::create_new_user_series_access;
+use chrono::Utc;
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use database::DbConn;
+use diesel;
+use diesel::Connection;
+use email::{EmailBody, PostmarkClient};
+use hmac::{Hmac, Mac};
+use payment;
+use payment::membership;
+use rocket::data::Data;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::{Outcome, Route, State};
+use serde_json::Value;
+use sha2::Sha256;
+use std::io::{Error, ErrorKind, Read};
+use structs::{PostmarkToken, StripeToken};
+use videos;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of seconds the timestamp of a
+/// signature may differ from the current
+/// time. Stops old events being replayed.
+pub static SIGNATURE_TOLERANCE: i64 = 300;
+
+/// Largest event payload read, in bytes.
+static PAYLOAD_LIMIT: u64 = 65536;
+
+/// Request guard with the
+/// Stripe-Signature header.
+/// Fails with a Bad Request
+/// if the header is missing.
+pub struct StripeSignature(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for StripeSignature {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<StripeSignature, ()> {
+        match request.headers().get_one("Stripe-Signature") {
+            Some(signature) => Outcome::Success(StripeSignature(signature.to_string())),
+            None => Outcome::Failure((Status::BadRequest, ())),
+        }
+    }
+}
+
+/// Computes the hex encoded signature
+/// Stripe sends for the payload at
+/// the timestamp.
+pub fn signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.input(format!("{}.{}", timestamp, payload).as_bytes());
+    HEXLOWER.encode(&mac.result().code())
+}
+
+/// Verifies the Stripe-Signature header of the
+/// payload. The header has the form t=<timestamp>,
+/// v1=<signature> and can hold more than one v1
+/// signature while the secret is being rolled.
+/// Signatures are compared in constant time and
+/// the timestamp must be within SIGNATURE_TOLERANCE
+/// seconds of now. Always fails without a secret.
+pub fn verify_signature(secret: &str, header: &str, payload: &str, now: i64) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+
+    let mut timestamp: Option<i64> = None;
+    let mut signatures: Vec<&str> = vec![];
+    for part in header.split(',') {
+        let mut pair = part.trim().splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some("t"), Some(value)) => timestamp = value.parse::<i64>().ok(),
+            (Some("v1"), Some(value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => return false,
+    };
+    if (now - timestamp).abs() > SIGNATURE_TOLERANCE {
+        return false;
+    }
+
+    let signed_payload = format!("{}.{}", timestamp, payload);
+    signatures.iter().any(|signature| {
+        let signature = match HEXLOWER_PERMISSIVE.decode(signature.as_bytes()) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take key of any size");
+        mac.input(signed_payload.as_bytes());
+        mac.verify(&signature).is_ok()
+    })
+}
+
+/// Change to a stored charge
+/// read from a Stripe event.
+#[derive(Debug, PartialEq)]
+pub enum ChargeChange {
+    /// The charge was updated, for
+    /// example refunded or failed.
+    Charge {
+        /// Stripe id of the charge.
+        id: String,
+        /// If the whole charge is refunded.
+        refunded: bool,
+        /// Amount refunded in cents.
+        amount_refunded: i32,
+        /// Status of the charge.
+        status: String,
+    },
+    /// A dispute of the charge was
+    /// created, updated or closed.
+    Dispute {
+        /// Stripe id of the dispute.
+        id: String,
+        /// Stripe id of the charge.
+        charge: String,
+        /// Status of the dispute.
+        status: String,
+    },
+}
+
+impl ChargeChange {
+    /// Stripe id of the charge
+    /// that is changed.
+    pub fn charge_id(&self) -> &str {
+        match *self {
+            ChargeChange::Charge { ref id, .. } => id,
+            ChargeChange::Dispute { ref charge, .. } => charge,
+        }
+    }
+}
+
+/// Reads the change to a charge from a Stripe
+/// event. Returns None for events we don't
+/// handle, which are acknowledged and ignored.
+pub fn parse_event(event: &Value) -> Option<ChargeChange> {
+    let kind = event.get("type")?.as_str()?;
+    let object = event.get("data")?.get("object")?;
+
+    match kind {
+        "charge.succeeded" | "charge.failed" | "charge.updated" | "charge.refunded" => {
+            Some(ChargeChange::Charge {
+                id: object.get("id")?.as_str()?.to_string(),
+                refunded: object.get("refunded")?.as_bool()?,
+                amount_refunded: object.get("amount_refunded")?.as_i64()? as i32,
+                status: object.get("status")?.as_str()?.to_string(),
+            })
+        }
+        "charge.dispute.created" | "charge.dispute.updated" | "charge.dispute.closed" => {
+            Some(ChargeChange::Dispute {
+                id: object.get("id")?.as_str()?.to_string(),
+                charge: object.get("charge")?.as_str()?.to_string(),
+                status: object.get("status")?.as_str()?.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The fields of a stored charge
+/// that Stripe events update.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChargeState {
+    /// If the whole charge is refunded.
+    pub refunded: bool,
+    /// Amount refunded in cents.
+    pub amount_refunded: i32,
+    /// Stripe id of the dispute while
+    /// it is open or if it was lost.
+    pub dispute: Option<String>,
+    /// Status of the charge.
+    pub status: String,
+}
+
+impl ChargeState {
+    /// Checks if the charge still pays
+    /// for access to the series.
+    pub fn grants_access(&self) -> bool {
+        !self.refunded && self.status != "failed" && self.dispute.is_none()
+    }
+
+    /// Returns the state after the change.
+    /// A dispute is only cleared once it
+    /// is closed in our favour.
+    pub fn apply(&self, change: &ChargeChange) -> ChargeState {
+        match *change {
+            ChargeChange::Charge {
+                refunded,
+                amount_refunded,
+                ref status,
+                ..
+            } => ChargeState {
+                refunded: refunded,
+                amount_refunded: amount_refunded,
+                dispute: self.dispute.clone(),
+                status: status.clone(),
+            },
+            ChargeChange::Dispute {
+                ref id, ref status, ..
+            } => ChargeState {
+                dispute: match status.as_str() {
+                    "won" | "warning_closed" => None,
+                    _ => Some(id.clone()),
+                },
+                ..self.clone()
+            },
+        }
+    }
+}
+
+/// What the user is told
+/// about a changed charge.
+#[derive(Debug, PartialEq)]
+pub enum Notice {
+    /// The charge was refunded and
+    /// access to the series removed.
+    Refunded,
+    /// Part of the charge was refunded,
+    /// access to the series is kept.
+    PartiallyRefunded,
+    /// The charge is disputed and access
+    /// to the series removed.
+    Disputed,
+    /// The charge failed and access
+    /// to the series removed.
+    Failed,
+    /// Access to the series is given
+    /// back, for example after a won
+    /// dispute.
+    Restored,
+}
+
+impl Notice {
+    /// Subject of the email.
+    pub fn subject(&self) -> &'static str {
+        match *self {
+            Notice::Refunded => "Your purchase was refunded",
+            Notice::PartiallyRefunded => "Your purchase was partially refunded",
+            Notice::Disputed => "Your purchase is disputed",
+            Notice::Failed => "Your payment failed",
+            Notice::Restored => "Your access is restored",
+        }
+    }
+
+    /// Message of the email.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            Notice::Refunded => {
+                "Your payment has been refunded and the series has been removed from your account."
+            }
+            Notice::PartiallyRefunded => {
+                "Part of your payment has been refunded. You still have access to the series."
+            }
+            Notice::Disputed => {
+                "Your payment has been disputed with your bank and the series has been removed from your account until the dispute is resolved."
+            }
+            Notice::Failed => {
+                "Your payment failed and the series has been removed from your account."
+            }
+            Notice::Restored => {
+                "Your payment has been resolved and the series is back in your account."
+            }
+        }
+    }
+}
+
+/// Decides what to tell the user about
+/// the change, if anything. Events
+/// that don't change the state give
+/// no notice, so retried events don't
+/// send the email again.
+pub fn notice(old: &ChargeState, new: &ChargeState) -> Option<Notice> {
+    match (old.grants_access(), new.grants_access()) {
+        (true, false) => {
+            if new.refunded {
+                Some(Notice::Refunded)
+            } else if new.dispute.is_some() {
+                Some(Notice::Disputed)
+            } else {
+                Some(Notice::Failed)
+            }
+        }
+        (false, true) => Some(Notice::Restored),
+        _ => {
+            if new.amount_refunded > old.amount_refunded && !new.refunded {
+                Some(Notice::PartiallyRefunded)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Struct for the charge email
+/// rendered using tera.
+#[derive(Serialize)]
+struct ChargeEmail<'a> {
+    /// Heading of the email.
+    heading: &'a str,
+    /// Message of the email.
+    message: &'a str,
+    /// Title of the series.
+    title: &'a str,
+}
+
+/// Sends an email to the user
+/// about the changed charge.
+fn send_charge_email(
+    postmark_token: &str,
+    email: &str,
+    title: &str,
+    notice: &Notice,
+) -> Result<(), Error> {
+    let tera = compile_templates!("templates/emails/**/*");
+    let context = ChargeEmail {
+        heading: notice.subject(),
+        message: notice.message(),
+        title: title,
+    };
+    match tera.render("charge_updated.html.tera", &context) {
+        Ok(html_body) => {
+            let body = EmailBody {
+                from: "axel@clubcoding.com".to_string(),
+                to: email.to_string(),
+                subject: Some(notice.subject().to_string()),
+                html_body: Some(html_body),
+                cc: None,
+                bcc: None,
+                tag: None,
+                text_body: None,
+                reply_to: None,
+                headers: None,
+                track_opens: None,
+                track_links: None,
+            };
+            let postmark_client = PostmarkClient::new(postmark_token);
+            postmark_client.send_email(&body)?;
+            Ok(())
+        }
+        Err(_) => Err(Error::new(ErrorKind::Other, "couldn't render template")),
+    }
+}
+
+/// Updates the stored charge, removes or
+/// gives back access to the series to match
+/// and emails the user. Charges we don't
+/// have stored are ignored. Stripe doesn't
+/// send events in order, so events created
+/// before the last one applied are ignored.
+fn apply_change(
+    conn: &DbConn,
+    postmark_token: &str,
+    change: &ChargeChange,
+    event_created: i64,
+) -> Result<(), Error> {
+    // The charge is read and the access is changed in the
+    // same transaction as the charge is updated, otherwise
+    // a retry would find the new state already stored and
+    // never change the access, and two deliveries at the
+    // same time would both see the old state.
+    let applied = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let charge = match database::get_charge_for_update(conn, change.charge_id()) {
+            Some(charge) => charge,
+            None => return Ok(None),
+        };
+        if event_created < charge.last_event_at {
+            return Ok(None);
+        }
+        let old = ChargeState {
+            refunded: charge.refunded,
+            amount_refunded: charge.amount_refunded,
+            dispute: charge.dispute.clone(),
+            status: charge.status.clone(),
+        };
+        let new = old.apply(change);
+
+        database::update_charge(
+            conn,
+            charge.id,
+            new.refunded,
+            new.amount_refunded,
+            new.dispute.as_ref().map(|dispute| dispute.as_str()),
+            &new.status,
+            event_created,
+        ).map_err(|_| diesel::result::Error::RollbackTransaction)?;
+
+        if old.grants_access() && !new.grants_access() {
+            database::revoke_bought_access(conn, charge.user_id, charge.series_id)
+                .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        } else if !old.grants_access()
+            && new.grants_access()
+            && !database::has_bought_access(conn, charge.user_id, charge.series_id)
+        {
+            create_new_user_series_access(&*conn, charge.user_id, charge.series_id, true)
+                .map_err(|_| diesel::result::Error::RollbackTransaction)?;
+        }
+        Ok(Some((charge.user_id, charge.series_id, notice(&old, &new))))
+    });
+    let (user_id, series_id, charge_notice) = match applied {
+        Ok(Some(applied)) => applied,
+        Ok(None) => return Ok(()),
+        Err(_) => return Err(Error::new(ErrorKind::Other, "Could not update charge.")),
+    };
+
+    if let Some(charge_notice) = charge_notice {
+        let user = authentication::database::get_user(conn, user_id);
+        let serie = videos::database::get_serie(conn, series_id);
+        if let (Some(user), Some(serie)) = (user, serie) {
+            // The charge is already updated, Stripe
+            // retrying the event would not resend it.
+            let _ = send_charge_email(postmark_token, &user.email, &serie.title, &charge_notice);
+        }
+    }
+    Ok(())
+}
+
+/// POST Endpoint for Stripe webhook events.
+/// Checks the Stripe-Signature header against
+/// the raw payload before reading the event.
+/// Subscription events update the membership
+/// billed with the subscription.
+/// Returns Bad Request if the signature or
+/// the event is invalid, Internal Server Error
+/// if the charge couldn't be updated so Stripe
+/// retries it, otherwise OK.
+#[post("/webhooks/stripe", data = "<data>")]
+fn stripe_webhook(
+    conn: DbConn,
+    stripe_token: State<StripeToken>,
+    postmark_token: State<PostmarkToken>,
+    stripe_signature: StripeSignature,
+    data: Data,
+) -> Status {
+    let mut payload = String::new();
+    if data.open()
+        .take(PAYLOAD_LIMIT)
+        .read_to_string(&mut payload)
+        .is_err()
+    {
+        return Status::BadRequest;
+    }
+
+    if !verify_signature(
+        &stripe_token.webhook_secret,
+        &stripe_signature.0,
+        &payload,
+        Utc::now().timestamp(),
+    ) {
+        return Status::BadRequest;
+    }
+
+    let event: Value = match serde_json::from_str(&payload) {
+        Ok(event) => event,
+        Err(_) => return Status::BadRequest,
+    };
+
+    if let Some(subscription) = membership::parse_subscription_event(&event) {
+        return match payment::database::update_membership(&conn, &subscription) {
+            Ok(_) => Status::Ok,
+            Err(_) => Status::InternalServerError,
+        };
+    }
+
+    let created = match event.get("created").and_then(|created| created.as_i64()) {
+        Some(created) => created,
+        None => return Status::BadRequest,
+    };
+
+    match parse_event(&event) {
+        Some(change) => match apply_change(&conn, &postmark_token.0, &change, created) {
+            Ok(_) => Status::Ok,
+            Err(_) => Status::InternalServerError,
+        },
+        None => Status::Ok,
+    }
+}
+
+/// Assembles all of the endpoints.
+/// The upside of assembling all of the endpoints here
+/// is that we don't have to update the main function but
+/// instead we can keep all of the changes in here.
+pub fn endpoints() -> Vec<Route> {
+    routes![stripe_webhook]
+}
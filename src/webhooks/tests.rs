@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod test {
+    use website;
+    use chrono::Utc;
+    use rocket::local::Client;
+    use rocket::http::{Header, Status};
+    use serde_json::Value;
+    use structs::StripeToken;
+    use webhooks::{notice, parse_event, signature, verify_signature, ChargeChange, ChargeState,
+                   Notice};
+
+    static CHARGE_REFUNDED: &'static str = include_str!("fixtures/charge_refunded.json");
+    static DISPUTE_CREATED: &'static str = include_str!("fixtures/charge_dispute_created.json");
+    static DISPUTE_WON: &'static str = include_str!("fixtures/charge_dispute_won.json");
+    static CUSTOMER_CREATED: &'static str = include_str!("fixtures/customer_created.json");
+
+    fn paid() -> ChargeState {
+        ChargeState {
+            refunded: false,
+            amount_refunded: 0,
+            dispute: None,
+            status: "succeeded".to_string(),
+        }
+    }
+
+    fn event(payload: &str) -> ChargeChange {
+        let event: Value = serde_json::from_str(payload).expect("valid fixture");
+        parse_event(&event).expect("handled event")
+    }
+
+    /// Posts the payload signed with the webhook
+    /// secret of the configuration and the
+    /// timestamp, returning the status.
+    fn post_signed(payload: &str, timestamp: i64) -> Status {
+        let rocket = website();
+        let secret = rocket
+            .state::<StripeToken>()
+            .expect("stripe tokens")
+            .webhook_secret
+            .clone();
+        let client = Client::new(rocket).expect("valid rocket instance");
+        let header = format!(
+            "t={},v1={}",
+            timestamp,
+            signature(&secret, timestamp, payload)
+        );
+        let response = client
+            .post("/webhooks/stripe")
+            .header(Header::new("Stripe-Signature", header))
+            .body(payload)
+            .dispatch();
+        response.status()
+    }
+
+    #[test]
+    fn webhook_fixtures() {
+        let now = Utc::now().timestamp();
+        for payload in [CHARGE_REFUNDED, DISPUTE_CREATED, DISPUTE_WON, CUSTOMER_CREATED].iter() {
+            assert_eq!(post_signed(payload, now), Status::Ok);
+        }
+    }
+
+    #[test]
+    fn webhook_old_timestamp() {
+        let old = Utc::now().timestamp() - 3600;
+        assert_eq!(post_signed(CHARGE_REFUNDED, old), Status::BadRequest);
+    }
+
+    #[test]
+    fn webhook_bad_signature() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let header = format!(
+            "t={},v1={}",
+            Utc::now().timestamp(),
+            signature("whsec_wrong", Utc::now().timestamp(), CHARGE_REFUNDED)
+        );
+        let response = client
+            .post("/webhooks/stripe")
+            .header(Header::new("Stripe-Signature", header))
+            .body(CHARGE_REFUNDED)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn webhook_no_signature() {
+        let client = Client::new(website()).expect("valid rocket instance");
+        let response = client
+            .post("/webhooks/stripe")
+            .body(CHARGE_REFUNDED)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn signatures() {
+        let header = format!("t=1000,v1={}", signature("whsec_test", 1000, "{}"));
+        assert!(verify_signature("whsec_test", &header, "{}", 1000));
+        assert!(verify_signature("whsec_test", &header, "{}", 1200));
+        assert!(!verify_signature("whsec_test", &header, "{}", 2000));
+        assert!(!verify_signature("whsec_test", &header, "{ }", 1000));
+        assert!(!verify_signature("whsec_other", &header, "{}", 1000));
+        assert!(!verify_signature("", &header, "{}", 1000));
+        assert!(!verify_signature("whsec_test", "t=1000", "{}", 1000));
+
+        let rolled = format!("t=1000,v1=00ff,v1={}", signature("whsec_test", 1000, "{}"));
+        assert!(verify_signature("whsec_test", &rolled, "{}", 1000));
+    }
+
+    #[test]
+    fn refund_revokes_access() {
+        let refunded = paid().apply(&event(CHARGE_REFUNDED));
+
+        assert!(refunded.refunded);
+        assert_eq!(refunded.amount_refunded, 1500);
+        assert!(!refunded.grants_access());
+        assert_eq!(notice(&paid(), &refunded), Some(Notice::Refunded));
+        assert_eq!(notice(&refunded, &refunded.apply(&event(CHARGE_REFUNDED))), None);
+    }
+
+    #[test]
+    fn partial_refund_keeps_access() {
+        let partial = paid().apply(&ChargeChange::Charge {
+            id: "ch_1CkPq02eZvKYlo2CjWfSCFqP".to_string(),
+            refunded: false,
+            amount_refunded: 500,
+            status: "succeeded".to_string(),
+        });
+
+        assert!(partial.grants_access());
+        assert_eq!(notice(&paid(), &partial), Some(Notice::PartiallyRefunded));
+    }
+
+    #[test]
+    fn won_dispute_restores_access() {
+        let created = event(DISPUTE_CREATED);
+        assert_eq!(created.charge_id(), "ch_1CkPq02eZvKYlo2CjWfSCFqP");
+
+        let disputed = paid().apply(&created);
+        assert_eq!(disputed.dispute, Some("dp_1CkPrM2eZvKYlo2CnR8gkUBH".to_string()));
+        assert_eq!(notice(&paid(), &disputed), Some(Notice::Disputed));
+
+        let won = disputed.apply(&event(DISPUTE_WON));
+        assert_eq!(won.dispute, None);
+        assert_eq!(notice(&disputed, &won), Some(Notice::Restored));
+    }
+
+    #[test]
+    fn unhandled_events() {
+        let customer: Value = serde_json::from_str(CUSTOMER_CREATED).expect("valid fixture");
+        assert_eq!(parse_event(&customer), None);
+    }
+}